@@ -0,0 +1,378 @@
+// static checks of a mamdani rule base: coverage gaps, conflicting and redundant rules, unused terms
+
+use crate::antecedent::Antecedent;
+use crate::defuzz::Defuzzifier;
+use crate::error::{or_panic, FuzzyError};
+use crate::explain::labels;
+use crate::ops::SNorm;
+use crate::parse::format_rule;
+use crate::rule::{DynFuzzyEngine, FuzzyEngine, Rule};
+use crate::table::{grid, spaced};
+use std::collections::HashSet;
+use std::fmt;
+
+/// sampled input vector where no rule fires above the threshold
+#[derive(Debug, Clone)]
+pub struct Gap {
+    pub inputs: Vec<f64>,
+    pub strength: f64, // firing strength of the strongest rule there
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub threshold: f64,
+    pub sampled: usize, // number of input vectors checked for gaps
+    pub gaps: Vec<Gap>,
+    pub conflicts: Vec<(usize, usize)>, // rules with the same condition setting an output to different terms
+    pub subsumed: Vec<(usize, usize)>,  // (rule, by), the rule never adds anything `by` doesn't
+    pub duplicates: Vec<(usize, usize)>, // (rule, of), would be subsumed but still shifts the output
+    pub unused_inputs: Vec<(String, String)>, // (variable, term) no antecedent mentions
+    pub unused_outputs: Vec<(String, String)>, // (variable, term) no consequent sets
+    pub rules: Vec<String>,              // text of every rule, indexed like the engine's rules
+}
+
+impl Analysis {
+    /// true when none of the checks found anything
+    pub fn is_clean(&self) -> bool {
+        self.gaps.is_empty()
+            && self.conflicts.is_empty()
+            && self.subsumed.is_empty()
+            && self.duplicates.is_empty()
+            && self.unused_inputs.is_empty()
+            && self.unused_outputs.is_empty()
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "coverage: no rule fires above {} at {} of {} sampled points",
+            self.threshold,
+            self.gaps.len(),
+            self.sampled
+        )?;
+        for gap in self.gaps.iter().take(10) {
+            let inputs: Vec<String> = gap.inputs.iter().map(|x| x.to_string()).collect();
+            writeln!(f, "  ({}) strongest {:.3}", inputs.join(", "), gap.strength)?;
+        }
+        if self.gaps.len() > 10 {
+            writeln!(f, "  ... and {} more", self.gaps.len() - 10)?;
+        }
+
+        writeln!(f, "conflicts")?;
+        if self.conflicts.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for (a, b) in self.conflicts.iter() {
+            writeln!(f, "  rule {}: {}", a, self.rules[*a])?;
+            writeln!(f, "  rule {}: {}", b, self.rules[*b])?;
+        }
+
+        writeln!(f, "subsumed")?;
+        if self.subsumed.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for (rule, by) in self.subsumed.iter() {
+            writeln!(f, "  rule {} by rule {}: {}", rule, by, self.rules[*rule])?;
+        }
+
+        writeln!(f, "duplicates")?;
+        if self.duplicates.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for (rule, of) in self.duplicates.iter() {
+            writeln!(f, "  rule {} of rule {}: {}", rule, of, self.rules[*rule])?;
+        }
+
+        writeln!(f, "unused terms")?;
+        if self.unused_inputs.is_empty() && self.unused_outputs.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for (var, term) in self.unused_inputs.iter().chain(self.unused_outputs.iter()) {
+            writeln!(f, "  {} IS {}", var, term)?;
+        }
+        Ok(())
+    }
+}
+
+/// condition of a rule as one optional expression per input when every conjunct
+/// only looks at a single input, as built by `add_rule`
+#[derive(PartialEq)]
+enum Condition<'a> {
+    Slots(Vec<Option<&'a Antecedent>>),
+    Other(&'a Antecedent),
+}
+
+/// index of the only input an expression depends on
+fn single_input(a: &Antecedent) -> Option<usize> {
+    match a {
+        Antecedent::Any => None,
+        Antecedent::Is(i, _) => Some(*i),
+        Antecedent::Not(a) | Antecedent::Hedge(_, a) => single_input(a),
+        Antecedent::And(v) | Antecedent::Or(v) => {
+            let first = single_input(v.first()?)?;
+            v.iter()
+                .all(|a| single_input(a) == Some(first))
+                .then_some(first)
+        }
+    }
+}
+
+fn condition(a: &Antecedent, n: usize) -> Condition<'_> {
+    let conjuncts = match a {
+        Antecedent::And(v) => v.iter().collect(),
+        _ => vec![a],
+    };
+    let mut slots = vec![None; n];
+    for c in conjuncts {
+        if *c == Antecedent::Any {
+            continue;
+        }
+        match single_input(c) {
+            Some(i) if i < n && slots[i].is_none() => slots[i] = Some(c),
+            _ => return Condition::Other(a),
+        }
+    }
+    Condition::Slots(slots)
+}
+
+/// true if `general` fires at least as strongly as `specific` everywhere: it keeps a subset
+/// of the other's conjuncts, and a t-norm never exceeds any of its arguments
+fn covers(general: &Condition, specific: &Condition) -> bool {
+    match (general, specific) {
+        (Condition::Slots(g), Condition::Slots(s)) => {
+            g.iter().zip(s.iter()).all(|(g, s)| g.is_none() || g == s)
+        }
+        _ => general == specific,
+    }
+}
+
+fn collect_terms(a: &Antecedent, used: &mut HashSet<(usize, String)>) {
+    match a {
+        Antecedent::Any => {}
+        Antecedent::Is(i, term) => {
+            used.insert((*i, term.clone()));
+        }
+        Antecedent::Not(a) | Antecedent::Hedge(_, a) => collect_terms(a, used),
+        Antecedent::And(v) | Antecedent::Or(v) => v.iter().for_each(|a| collect_terms(a, used)),
+    }
+}
+
+fn redundant(rule: &Rule, by: &Rule) -> bool {
+    rule.weight <= by.weight
+        && rule
+            .consequent
+            .iter()
+            .zip(by.consequent.iter())
+            .all(|(r, b)| r.is_none() || r == b)
+}
+
+/// true when a rule that fires no stronger than another with the same terms can't change the
+/// output: the aggregation is idempotent and the defuzzifier only sees the aggregated set.
+/// otherwise the weaker rule counts again and is reported as a duplicate
+fn absorbs(aggregation: SNorm, defuzzifier: Defuzzifier) -> bool {
+    matches!(aggregation, SNorm::Max)
+        && matches!(
+            defuzzifier,
+            Defuzzifier::Centroid
+                | Defuzzifier::Bisector
+                | Defuzzifier::MeanOfMax
+                | Defuzzifier::SmallestOfMax
+                | Defuzzifier::LargestOfMax
+        )
+}
+
+impl DynFuzzyEngine {
+    /// run every check on the rule base. coverage is sampled on a grid of `points` evenly
+    /// spaced values per input, so it costs points^inputs evaluations
+    pub fn analyze(&self, threshold: f64, points: usize) -> Analysis {
+        or_panic(self.try_analyze(threshold, points))
+    }
+
+    pub fn try_analyze(&self, threshold: f64, points: usize) -> Result<Analysis, FuzzyError> {
+        let input_labels = labels(self.inputs(), "input");
+        let output_labels = labels(self.outputs(), "output");
+        let rules = self.rules();
+
+        let axes: Vec<Vec<f64>> = self
+            .inputs()
+            .iter()
+            .map(|var| spaced(var, points))
+            .collect::<Result<_, _>>()?;
+        let samples = grid(&axes.iter().map(|a| a.as_slice()).collect::<Vec<&[f64]>>());
+        let mut strengths = Vec::with_capacity(rules.len());
+        let mut gaps = vec![];
+        for x in samples.iter() {
+            self.strengths_into(x, &mut strengths)?;
+            let strength = strengths.iter().fold(0f64, |m, s| m.max(*s));
+            if strength <= threshold {
+                gaps.push(Gap {
+                    inputs: x.clone(),
+                    strength,
+                });
+            }
+        }
+
+        let conditions: Vec<Condition> = rules
+            .iter()
+            .map(|r| condition(&r.antecedent, self.inputs().len()))
+            .collect();
+        let mut conflicts = vec![];
+        let mut subsumed = vec![];
+        let mut duplicates = vec![];
+        let absorbed = absorbs(self.operators().aggregation, self.defuzzifier());
+        for i in 0..rules.len() {
+            for j in (i + 1)..rules.len() {
+                let clash = rules[i]
+                    .consequent
+                    .iter()
+                    .zip(rules[j].consequent.iter())
+                    .any(|(a, b)| a.is_some() && b.is_some() && a != b);
+                if clash && conditions[i] == conditions[j] {
+                    conflicts.push((i, j));
+                }
+            }
+            // a rule identical to another is reported once, as subsumed by the earlier one
+            let by = (0..rules.len()).find(|j| {
+                *j != i
+                    && covers(&conditions[*j], &conditions[i])
+                    && redundant(&rules[i], &rules[*j])
+                    && !(*j > i
+                        && covers(&conditions[i], &conditions[*j])
+                        && redundant(&rules[*j], &rules[i]))
+            });
+            match by {
+                Some(j) if absorbed => subsumed.push((i, j)),
+                Some(j) => duplicates.push((i, j)),
+                None => {}
+            }
+        }
+
+        let mut used = HashSet::new();
+        rules
+            .iter()
+            .for_each(|r| collect_terms(&r.antecedent, &mut used));
+        let unused_inputs = self
+            .inputs()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, var)| {
+                var.sets
+                    .iter()
+                    .filter(|set| !used.contains(&(i, set.name.clone())))
+                    .map(|set| (input_labels[i].0.clone(), set.name.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let unused_outputs = self
+            .outputs()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, var)| {
+                var.sets
+                    .iter()
+                    .filter(|set| {
+                        !rules
+                            .iter()
+                            .any(|r| r.consequent[i].as_deref() == Some(set.name.as_str()))
+                    })
+                    .map(|set| (output_labels[i].0.clone(), set.name.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Ok(Analysis {
+            threshold,
+            sampled: samples.len(),
+            gaps,
+            conflicts,
+            subsumed,
+            duplicates,
+            unused_inputs,
+            unused_outputs,
+            rules: rules
+                .iter()
+                .map(|r| format_rule(r, &input_labels, &output_labels))
+                .collect(),
+        })
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// see `DynFuzzyEngine::analyze`
+    pub fn analyze(&self, threshold: f64, points: usize) -> Analysis {
+        self.as_dyn().analyze(threshold, points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antecedent::is;
+    use crate::ops::Operators;
+    use crate::set::*;
+    use crate::shape::*;
+
+    #[test]
+    fn findings() {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 30f64), "low"),
+                (&triangular(50f64, 1.0, 30f64), "medium"),
+                (&triangular(100f64, 1.0, 30f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.1),
+        );
+        let mut engine =
+            FuzzyEngine::new([x.clone().named("rsi"), x.clone().named("bb")], [x.clone()]);
+        engine.add_rule(["low", "any"], ["high"]);
+        engine.add_rule(["low", "low"], ["high"]); // subsumed by 0
+        engine.add_rule(["high", "high"], ["low"]);
+        engine.add_rule(["high", "high"], ["medium"]); // conflicts with 2
+        engine.add_rule(["low", "any"], ["high"]); // duplicate of 0
+        engine.add_rule_weighted(["medium", "high"], ["low"], 0.5);
+        engine.add_rule(["medium", "high"], ["low"]); // covers 5 with a larger weight
+        engine.add_rule_expr(is(0, "high").or(is(1, "low")), ["medium"]);
+
+        let a = engine.analyze(0.1, 11);
+        assert_eq!(a.sampled, 121);
+        assert_eq!(a.conflicts, vec![(2, 3)]);
+        assert_eq!(a.subsumed, vec![(1, 0), (4, 0), (5, 6)]);
+        assert!(a.duplicates.is_empty());
+        assert_eq!(a.unused_inputs, vec![("bb".into(), "medium".into())]);
+        assert!(a.unused_outputs.is_empty());
+        // rsi = 50, bb = 50 only fires rules about a high bb
+        assert!(a.gaps.iter().any(|g| g.inputs == vec![50.0, 50.0]));
+        assert!(a.gaps.iter().all(|g| g.strength <= 0.1));
+        assert!(!a.is_clean());
+
+        let report = a.to_string();
+        assert!(report.contains("rule 3: IF rsi IS high AND bb IS high THEN output0 IS medium"));
+        assert!(report.contains("rule 1 by rule 0"));
+
+        // a sum or a per-rule defuzzifier counts the weaker rule again
+        let mut summed = engine.clone().into_dyn();
+        summed.set_defuzzifier(Defuzzifier::WeightedCentroid);
+        let a = summed.analyze(0.1, 11);
+        assert!(a.subsumed.is_empty());
+        assert_eq!(a.duplicates, vec![(1, 0), (4, 0), (5, 6)]);
+        assert!(a.to_string().contains("rule 4 of rule 0"));
+        summed.set_defuzzifier(Defuzzifier::Centroid);
+        summed.set_operators(Operators {
+            aggregation: SNorm::ProbSum,
+            ..Operators::default()
+        });
+        assert_eq!(summed.analyze(0.1, 11).duplicates.len(), 3);
+
+        let mut sparse = FuzzyEngine::new([x.clone()], [x]);
+        sparse.add_rule(["low"], ["high"]);
+        sparse.add_rule(["medium"], ["low"]);
+        sparse.add_rule(["high"], ["low"]);
+        let a = sparse.analyze(0.0, 101);
+        assert!(a.gaps.is_empty());
+        assert_eq!(a.unused_outputs, vec![("output0".into(), "medium".into())]);
+        assert!(a.to_string().contains("output0 IS medium"));
+        assert!(sparse.as_dyn().try_analyze(0.0, 1).is_err());
+    }
+}
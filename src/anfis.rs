@@ -0,0 +1,808 @@
+// ANFIS hybrid learning of a sugeno engine from samples: each epoch solves the consequent
+// parameters by least squares, then takes a gradient step on the input terms with a smooth shape
+
+use crate::antecedent::{Antecedent, Hedge};
+use crate::error::{or_panic, FuzzyError};
+use crate::ops::{Operators, SNorm, TNorm};
+use crate::set::{FuzzySet, LinguisticVar};
+use crate::shape::{bell, dsigmoid, gauss2, gaussian, psigmoid, sigmoid, Shape};
+use crate::sugeno::{Consequent, DynSugenoEngine, SugenoEngine, SugenoMethod};
+use std::fmt;
+
+/// settings of the training, loss is the mean squared error over samples and outputs.
+/// the step size grows 10% after 4 epochs of falling training loss and shrinks 10% when it rises
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anfis {
+    pub epochs: usize,
+    pub step_size: f64,  // length of the first gradient step in parameter space
+    pub validation: f64, // fraction of the samples held out from the end, in [0, 1)
+    pub patience: usize, // epochs without a better validation loss before stopping
+    pub verbose: bool,   // print the losses after each epoch
+}
+
+impl Default for Anfis {
+    fn default() -> Self {
+        Anfis {
+            epochs: 100,
+            step_size: 0.1,
+            validation: 0.2,
+            patience: 10,
+            verbose: false,
+        }
+    }
+}
+
+/// losses of each epoch, the engine is left with the parameters of `best_epoch`
+#[derive(Debug, Clone)]
+pub struct Training {
+    pub train_loss: Vec<f64>,
+    pub validation_loss: Vec<f64>, // empty without a validation set
+    pub best_epoch: usize,
+    pub stopped_early: bool,
+    pub fixed: Vec<(String, String)>, // (variable, term) without a gradient, left as they were
+}
+
+impl fmt::Display for Training {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, loss) in self.train_loss.iter().enumerate() {
+            write!(f, "epoch {} train {:.6}", i, loss)?;
+            if let Some(v) = self.validation_loss.get(i) {
+                write!(f, " validation {:.6}", v)?;
+            }
+            writeln!(f, "{}", if i == self.best_epoch { " *" } else { "" })?;
+        }
+        if self.stopped_early {
+            writeln!(f, "stopped early")?;
+        }
+        for (var, term) in self.fixed.iter() {
+            writeln!(f, "{} IS {} not trained", var, term)?;
+        }
+        Ok(())
+    }
+}
+
+/// input term learned by gradient descent
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Gaussian, // (c, sigma)
+    Gauss2,   // (c1, s1, c2, s2)
+    Bell,     // (a, b, c)
+    Sigmoid,  // (a, c)
+    DSigmoid, // (a1, c1, a2, c2)
+    PSigmoid, // (a1, c1, a2, c2)
+}
+
+/// gaussian membership and its derivatives by c and sigma
+fn gaussian_grad(c: f64, s: f64, x: f64) -> (f64, f64, f64) {
+    let mu = (-(x - c).powi(2) / (2.0 * s * s)).exp();
+    (
+        mu,
+        mu * (x - c) / (s * s),
+        mu * (x - c).powi(2) / (s * s * s),
+    )
+}
+
+/// sigmoid membership and its derivatives by a and c
+fn sigmoid_grad(a: f64, c: f64, x: f64) -> (f64, f64, f64) {
+    let mu = 1.0 / (1.0 + (-a * (x - c)).exp());
+    let d = mu * (1.0 - mu);
+    (mu, d * (x - c), -d * a)
+}
+
+struct Premise {
+    var: usize,
+    set: usize,
+    kind: Kind,
+    start: usize,
+}
+
+/// trainable input terms and their parameters, laid out in one vector
+struct Premises {
+    terms: Vec<Premise>,
+    slots: Vec<Vec<Option<usize>>>, // premise index of each term of each input
+    fixed: Vec<(String, String)>,   // (variable, term) of the others
+    params: Vec<f64>,
+    floors: Vec<f64>, // smallest value of each parameter, widths stay positive
+}
+
+impl Premises {
+    fn new(inputs: &[LinguisticVar]) -> Premises {
+        let mut res = Premises {
+            terms: vec![],
+            slots: vec![],
+            fixed: vec![],
+            params: vec![],
+            floors: vec![],
+        };
+        for (var, v) in inputs.iter().enumerate() {
+            let width = v.universe[v.universe.len() - 1] - v.universe[0];
+            let tiny = (width * 1e-6).max(f64::MIN_POSITIVE);
+            let mut slots = vec![];
+            for (set, term) in v.sets.iter().enumerate() {
+                let shape = term.shape.as_ref();
+                let (kind, floors) = match shape.map(|s| s.kind()) {
+                    Some("gaussian") => (Kind::Gaussian, vec![f64::MIN, tiny]),
+                    Some("gauss2") => (Kind::Gauss2, vec![f64::MIN, tiny, f64::MIN, tiny]),
+                    Some("bell") => (Kind::Bell, vec![tiny, 1e-3, f64::MIN]),
+                    Some("sigmoid") => (Kind::Sigmoid, vec![f64::MIN; 2]),
+                    Some("dsigmoid") => (Kind::DSigmoid, vec![f64::MIN; 4]),
+                    Some("psigmoid") => (Kind::PSigmoid, vec![f64::MIN; 4]),
+                    _ => {
+                        slots.push(None);
+                        res.fixed.push((v.name.clone(), term.name.clone()));
+                        continue;
+                    }
+                };
+                slots.push(Some(res.terms.len()));
+                res.terms.push(Premise {
+                    var,
+                    set,
+                    kind,
+                    start: res.params.len(),
+                });
+                res.params.extend(shape.unwrap().params());
+                res.floors.extend(floors);
+            }
+            res.slots.push(slots);
+        }
+        res
+    }
+
+    /// membership of `x` in premise `p` and its derivative for each of the premise's parameters
+    fn degree(&self, p: usize, x: f64, grad: &mut [f64]) -> f64 {
+        let term = &self.terms[p];
+        let q = &self.params[term.start..];
+        match term.kind {
+            Kind::Gaussian => {
+                let (mu, dc, ds) = gaussian_grad(q[0], q[1], x);
+                grad[term.start] = dc;
+                grad[term.start + 1] = ds;
+                mu
+            }
+            Kind::Gauss2 => {
+                // each side is a gaussian past its centre and 1 before it
+                let (l, dc1, ds1) = match x < q[0] {
+                    true => gaussian_grad(q[0], q[1], x),
+                    false => (1.0, 0.0, 0.0),
+                };
+                let (r, dc2, ds2) = match x > q[2] {
+                    true => gaussian_grad(q[2], q[3], x),
+                    false => (1.0, 0.0, 0.0),
+                };
+                grad[term.start] = dc1 * r;
+                grad[term.start + 1] = ds1 * r;
+                grad[term.start + 2] = dc2 * l;
+                grad[term.start + 3] = ds2 * l;
+                l * r
+            }
+            Kind::Sigmoid => {
+                let (mu, da, dc) = sigmoid_grad(q[0], q[1], x);
+                grad[term.start] = da;
+                grad[term.start + 1] = dc;
+                mu
+            }
+            Kind::DSigmoid => {
+                let (f1, da1, dc1) = sigmoid_grad(q[0], q[1], x);
+                let (f2, da2, dc2) = sigmoid_grad(q[2], q[3], x);
+                let sign = if f1 >= f2 { 1.0 } else { -1.0 };
+                grad[term.start] = sign * da1;
+                grad[term.start + 1] = sign * dc1;
+                grad[term.start + 2] = -sign * da2;
+                grad[term.start + 3] = -sign * dc2;
+                (f1 - f2).abs()
+            }
+            Kind::PSigmoid => {
+                let (f1, da1, dc1) = sigmoid_grad(q[0], q[1], x);
+                let (f2, da2, dc2) = sigmoid_grad(q[2], q[3], x);
+                grad[term.start] = da1 * f2;
+                grad[term.start + 1] = dc1 * f2;
+                grad[term.start + 2] = da2 * f1;
+                grad[term.start + 3] = dc2 * f1;
+                f1 * f2
+            }
+            Kind::Bell => {
+                let (a, b, c) = (q[0], q[1], q[2]);
+                let u = ((x - c) / a).abs();
+                let t = u.powf(2.0 * b);
+                let mu = 1.0 / (1.0 + t);
+                // d mu / d t = -mu^2
+                let d = -mu * mu;
+                grad[term.start] = d * (-2.0 * b * t / a);
+                if u > 0.0 {
+                    grad[term.start + 1] = d * 2.0 * t * u.ln();
+                    grad[term.start + 2] = d * (-2.0 * b * t / (x - c));
+                }
+                mu
+            }
+        }
+    }
+
+    fn shape(&self, p: usize) -> Box<dyn Shape> {
+        let term = &self.terms[p];
+        let q = &self.params[term.start..];
+        match term.kind {
+            Kind::Gaussian => Box::new(gaussian(q[0], q[1])),
+            Kind::Gauss2 => Box::new(gauss2(q[0], q[1], q[2], q[3])),
+            Kind::Bell => Box::new(bell(q[0], q[1], q[2])),
+            Kind::Sigmoid => Box::new(sigmoid(q[0], q[1])),
+            Kind::DSigmoid => Box::new(dsigmoid(q[0], q[1], q[2], q[3])),
+            Kind::PSigmoid => Box::new(psigmoid(q[0], q[1], q[2], q[3])),
+        }
+    }
+
+    /// write the parameters back into the engine's input terms
+    fn decode(&self, inputs: &mut [LinguisticVar]) {
+        for (p, term) in self.terms.iter().enumerate() {
+            let var = &mut inputs[term.var];
+            let name = std::mem::take(&mut var.sets[term.set].name);
+            var.sets[term.set] = FuzzySet::analytic(&var.universe, self.shape(p).as_ref(), name);
+        }
+    }
+
+    /// degree of an antecedent, `grad` receives its derivative for every premise parameter
+    fn eval(
+        &self,
+        a: &Antecedent,
+        inputs_var: &[LinguisticVar],
+        x: &[f64],
+        ops: &Operators,
+        grad: &mut [f64],
+    ) -> f64 {
+        grad.iter_mut().for_each(|g| *g = 0.0);
+        match a {
+            Antecedent::Any => 1.0,
+            Antecedent::Is(i, term) => {
+                let var = &inputs_var[*i];
+                let set = var.sets.iter().position(|s| s.name == *term).unwrap();
+                match self.slots[*i][set] {
+                    Some(p) => self.degree(p, x[*i], grad),
+                    None => var.sets[set].degree_of(x[*i]),
+                }
+            }
+            Antecedent::Not(a) => {
+                let v = self.eval(a, inputs_var, x, ops, grad);
+                grad.iter_mut().for_each(|g| *g = -*g);
+                1.0 - v
+            }
+            Antecedent::Hedge(h, a) => {
+                let v = self.eval(a, inputs_var, x, ops, grad);
+                let d = match h {
+                    Hedge::Very => 2.0 * v,
+                    Hedge::Extremely => 3.0 * v * v,
+                    Hedge::Somewhat if v > 0.0 => 0.5 / v.sqrt(),
+                    Hedge::Slightly if v > 0.0 => 1.0 / (3.0 * v.cbrt().powi(2)),
+                    _ => 0.0,
+                };
+                grad.iter_mut().for_each(|g| *g *= d);
+                h.apply(v)
+            }
+            Antecedent::And(v) | Antecedent::Or(v) => {
+                let and = matches!(a, Antecedent::And(_));
+                let (mut s, mut child) = (if and { 1.0 } else { 0.0 }, vec![0.0; grad.len()]);
+                for c in v.iter() {
+                    let b = self.eval(c, inputs_var, x, ops, &mut child);
+                    let (value, da, db) = match and {
+                        true => t_norm(ops.and, s, b),
+                        false => s_norm(ops.or, s, b),
+                    };
+                    for (g, cg) in grad.iter_mut().zip(child.iter()) {
+                        *g = da * *g + db * cg;
+                    }
+                    s = value;
+                }
+                s
+            }
+        }
+    }
+}
+
+/// central difference of a norm, for the ones without a closed form below
+fn partials(f: impl Fn(f64, f64) -> f64, a: f64, b: f64) -> (f64, f64, f64) {
+    let h = 1e-6;
+    (
+        f(a, b),
+        (f(a + h, b) - f(a - h, b)) / (2.0 * h),
+        (f(a, b + h) - f(a, b - h)) / (2.0 * h),
+    )
+}
+
+/// value and partial derivatives of a t-norm
+fn t_norm(norm: TNorm, a: f64, b: f64) -> (f64, f64, f64) {
+    match norm {
+        TNorm::Min if a <= b => (a, 1.0, 0.0),
+        TNorm::Min => (b, 0.0, 1.0),
+        TNorm::Product => (a * b, b, a),
+        _ => partials(|a, b| norm.apply(a, b), a, b),
+    }
+}
+
+/// value and partial derivatives of an s-norm
+fn s_norm(norm: SNorm, a: f64, b: f64) -> (f64, f64, f64) {
+    match norm {
+        SNorm::Max if a >= b => (a, 1.0, 0.0),
+        SNorm::Max => (b, 0.0, 1.0),
+        SNorm::ProbSum => (a + b - a * b, 1.0 - b, 1.0 - a),
+        _ => partials(|a, b| norm.apply(a, b), a, b),
+    }
+}
+
+/// solve a x = b for a symmetric positive semi-definite a, with a little ridge
+/// so rank deficient systems (terms no sample fires) still have a solution
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    let scale = (0..n).map(|i| a[i][i]).fold(0f64, f64::max).max(1.0);
+    for (i, row) in a.iter_mut().enumerate() {
+        row[i] += scale * 1e-10;
+    }
+    for k in 0..n {
+        let pivot = (k..n)
+            .max_by(|i, j| a[*i][k].abs().total_cmp(&a[*j][k].abs()))
+            .unwrap();
+        a.swap(k, pivot);
+        b.swap(k, pivot);
+        let (done, rest) = a.split_at_mut(k + 1);
+        let row = &done[k];
+        for (i, other) in rest.iter_mut().enumerate() {
+            let f = other[k] / row[k];
+            for (x, p) in other[k..].iter_mut().zip(&row[k..]) {
+                *x -= f * p;
+            }
+            b[k + 1 + i] -= f * b[k];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for k in (0..n).rev() {
+        let s: f64 = (k + 1..n).map(|j| a[k][j] * x[j]).sum();
+        x[k] = (b[k] - s) / a[k][k];
+    }
+    x
+}
+
+impl DynSugenoEngine {
+    /// firing strength of every rule for one sample, with derivatives for the premise parameters
+    fn strengths_with_grad(&self, premises: &Premises, x: &[f64]) -> Vec<(f64, Vec<f64>)> {
+        let ops = self.operators();
+        self.rules()
+            .iter()
+            .map(|rule| {
+                let mut grad = vec![0.0; premises.params.len()];
+                let w = premises.eval(&rule.antecedent, self.inputs(), x, &ops, &mut grad);
+                grad.iter_mut().for_each(|g| *g *= rule.weight);
+                (rule.weight * w, grad)
+            })
+            .collect()
+    }
+
+    /// least squares fit of every consequent parameter with the input terms held fixed
+    fn fit_consequents(&mut self, inputs: &[&[f64]], targets: &[&[f64]]) {
+        let ops = self.operators();
+        let strengths: Vec<Vec<f64>> = inputs
+            .iter()
+            .map(|x| {
+                self.rules()
+                    .iter()
+                    .map(|rule| rule.weight * rule.antecedent.eval(self.inputs(), x, &ops))
+                    .collect()
+            })
+            .collect();
+
+        for k in 0..self.outputs().len() {
+            let terms = &self.outputs()[k].terms;
+            // parameters of term t start at offsets[t]
+            let mut offsets = vec![0];
+            for (_, f) in terms.iter() {
+                let n = match f {
+                    Consequent::Constant(_) => 1,
+                    Consequent::Linear(coef, _) => coef.len() + 1,
+                };
+                offsets.push(offsets[offsets.len() - 1] + n);
+            }
+            let n = offsets[terms.len()];
+            let index: Vec<Option<usize>> = self
+                .rules()
+                .iter()
+                .map(|r| {
+                    let name = r.consequent[k].as_ref()?;
+                    terms.iter().position(|(t, _)| t == name)
+                })
+                .collect();
+
+            let (mut ata, mut aty) = (vec![vec![0.0; n]; n], vec![0.0; n]);
+            let mut row = vec![0.0; n];
+            for ((x, t), w) in inputs.iter().zip(targets.iter()).zip(strengths.iter()) {
+                let total: f64 = w
+                    .iter()
+                    .zip(index.iter())
+                    .filter(|(_, i)| i.is_some())
+                    .map(|(w, _)| w)
+                    .sum();
+                if total == 0.0 {
+                    continue;
+                }
+                row.iter_mut().for_each(|r| *r = 0.0);
+                for (w, i) in w.iter().zip(index.iter()) {
+                    let t = match i {
+                        Some(t) => *t,
+                        None => continue,
+                    };
+                    let s = w / total;
+                    let o = offsets[t];
+                    match &terms[t].1 {
+                        Consequent::Constant(_) => row[o] += s,
+                        Consequent::Linear(coef, _) => {
+                            for (j, xj) in x.iter().enumerate().take(coef.len()) {
+                                row[o + j] += s * xj;
+                            }
+                            row[o + coef.len()] += s;
+                        }
+                    }
+                }
+                for i in 0..n {
+                    if row[i] == 0.0 {
+                        continue;
+                    }
+                    for j in 0..n {
+                        ata[i][j] += row[i] * row[j];
+                    }
+                    aty[i] += row[i] * t[k];
+                }
+            }
+
+            let theta = solve(ata, aty);
+            let (_, outputs) = self.vars_mut();
+            for (t, (_, f)) in outputs[k].terms.iter_mut().enumerate() {
+                let o = offsets[t];
+                *f = match f {
+                    Consequent::Constant(_) => Consequent::Constant(theta[o]),
+                    Consequent::Linear(coef, _) => {
+                        Consequent::Linear(theta[o..o + coef.len()].to_vec(), theta[o + coef.len()])
+                    }
+                };
+            }
+        }
+    }
+
+    fn loss(&self, inputs: &[&[f64]], targets: &[&[f64]]) -> f64 {
+        let mut sum = 0.0;
+        for (x, t) in inputs.iter().zip(targets.iter()) {
+            let y = self.calculate(x);
+            sum += y
+                .iter()
+                .zip(t.iter())
+                .map(|(y, t)| (y - t).powi(2))
+                .sum::<f64>();
+        }
+        sum / (inputs.len() * self.outputs().len()).max(1) as f64
+    }
+
+    /// gradient of the training loss for the premise parameters, up to a constant factor
+    fn premise_gradient(
+        &self,
+        premises: &Premises,
+        inputs: &[&[f64]],
+        targets: &[&[f64]],
+    ) -> Vec<f64> {
+        let mut grad = vec![0.0; premises.params.len()];
+        for (x, t) in inputs.iter().zip(targets.iter()) {
+            let strengths = self.strengths_with_grad(premises, x);
+            for (k, var) in self.outputs().iter().enumerate() {
+                // rule outputs and strengths of the rules setting output k
+                let fired: Vec<(f64, &Vec<f64>, f64)> = self
+                    .rules()
+                    .iter()
+                    .zip(strengths.iter())
+                    .filter_map(|(r, (w, g))| {
+                        let f = var.term(r.consequent[k].as_ref()?).eval(x);
+                        Some((*w, g, f))
+                    })
+                    .collect();
+                let total: f64 = fired.iter().map(|(w, _, _)| w).sum();
+                if total == 0.0 {
+                    continue;
+                }
+                let y = fired.iter().map(|(w, _, f)| w * f).sum::<f64>() / total;
+                let err = y - t[k];
+                for (_, g, f) in fired.iter() {
+                    let c = err * (f - y) / total;
+                    for (a, b) in grad.iter_mut().zip(g.iter()) {
+                        *a += c * b;
+                    }
+                }
+            }
+        }
+        grad
+    }
+
+    /// learn the engine from `inputs` with one row per sample and `targets` with one value per
+    /// output. the last `validation` fraction of the samples is held out, keep them in time order.
+    /// gaussian, gauss2, bell and the sigmoid input terms are trained, `Training::fixed` lists the rest
+    pub fn train<R: AsRef<[f64]>, T: AsRef<[f64]>>(
+        &mut self,
+        anfis: &Anfis,
+        inputs: &[R],
+        targets: &[T],
+    ) -> Training {
+        or_panic(self.try_train(anfis, inputs, targets))
+    }
+
+    pub fn try_train<R: AsRef<[f64]>, T: AsRef<[f64]>>(
+        &mut self,
+        anfis: &Anfis,
+        inputs: &[R],
+        targets: &[T],
+    ) -> Result<Training, FuzzyError> {
+        if !(0.0..1.0).contains(&anfis.validation) {
+            return Err(FuzzyError::InvalidRange(
+                "validation fraction must be in [0, 1)".into(),
+            ));
+        }
+        if !anfis.step_size.is_finite() || anfis.step_size <= 0.0 {
+            return Err(FuzzyError::InvalidRange("step size must be > 0".into()));
+        }
+        if self.method() != SugenoMethod::WeightedAverage {
+            return Err(FuzzyError::InvalidRange(
+                "anfis only trains weighted average sugeno engines".into(),
+            ));
+        }
+        if targets.len() != inputs.len() {
+            return Err(FuzzyError::Arity(
+                "targets".into(),
+                inputs.len(),
+                targets.len(),
+            ));
+        }
+        let inputs: Vec<&[f64]> = inputs.iter().map(|r| r.as_ref()).collect();
+        let targets: Vec<&[f64]> = targets.iter().map(|r| r.as_ref()).collect();
+        for (x, t) in inputs.iter().zip(targets.iter()) {
+            if x.len() != self.inputs().len() {
+                return Err(FuzzyError::Arity(
+                    "inputs".into(),
+                    self.inputs().len(),
+                    x.len(),
+                ));
+            }
+            if t.len() != self.outputs().len() {
+                return Err(FuzzyError::Arity(
+                    "targets".into(),
+                    self.outputs().len(),
+                    t.len(),
+                ));
+            }
+        }
+        let split = inputs.len() - (inputs.len() as f64 * anfis.validation).round() as usize;
+        if split == 0 {
+            return Err(FuzzyError::InvalidRange(
+                "no samples left to train on".into(),
+            ));
+        }
+        let (train_x, valid_x) = inputs.split_at(split);
+        let (train_t, valid_t) = targets.split_at(split);
+
+        let mut premises = Premises::new(self.inputs());
+        let mut training = Training {
+            train_loss: vec![],
+            validation_loss: vec![],
+            best_epoch: 0,
+            stopped_early: false,
+            fixed: premises.fixed.clone(),
+        };
+        let (mut best, mut best_loss) = (self.clone(), f64::INFINITY);
+        let (mut step, mut improving) = (anfis.step_size, 0);
+        for epoch in 0..anfis.epochs {
+            self.fit_consequents(train_x, train_t);
+            let loss = self.loss(train_x, train_t);
+            let score = match valid_x.is_empty() {
+                true => loss,
+                false => {
+                    let v = self.loss(valid_x, valid_t);
+                    training.validation_loss.push(v);
+                    v
+                }
+            };
+            if anfis.verbose {
+                match valid_x.is_empty() {
+                    true => println!("epoch {} train {:.6}", epoch, loss),
+                    false => println!("epoch {} train {:.6} validation {:.6}", epoch, loss, score),
+                }
+            }
+            match training.train_loss.last() {
+                Some(last) if loss < *last => {
+                    improving += 1;
+                    if improving >= 4 {
+                        step *= 1.1;
+                        improving = 0;
+                    }
+                }
+                Some(_) => {
+                    step *= 0.9;
+                    improving = 0;
+                }
+                None => {}
+            }
+            training.train_loss.push(loss);
+            if score < best_loss {
+                (best, best_loss, training.best_epoch) = (self.clone(), score, epoch);
+            } else if epoch - training.best_epoch >= anfis.patience {
+                training.stopped_early = true;
+                break;
+            }
+
+            let grad = self.premise_gradient(&premises, train_x, train_t);
+            let norm = grad.iter().map(|g| g * g).sum::<f64>().sqrt();
+            if norm == 0.0 || !norm.is_finite() {
+                continue;
+            }
+            for ((p, g), floor) in premises
+                .params
+                .iter_mut()
+                .zip(grad.iter())
+                .zip(premises.floors.iter())
+            {
+                *p = (*p - step * g / norm).max(*floor);
+            }
+            premises.decode(self.vars_mut().0);
+        }
+        *self = best;
+        Ok(training)
+    }
+}
+
+impl<const N: usize, const M: usize> SugenoEngine<N, M> {
+    /// see `DynSugenoEngine::train`
+    pub fn train(&mut self, anfis: &Anfis, inputs: &[[f64; N]], targets: &[[f64; M]]) -> Training {
+        self.as_dyn_mut().train(anfis, inputs, targets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::arange;
+    use crate::sugeno::*;
+
+    fn engine(shape: impl Fn(f64) -> Box<dyn Shape>) -> SugenoEngine<1, 1> {
+        let x = LinguisticVar::new(
+            vec![
+                (shape(0.0).as_ref(), "low"),
+                (shape(5.0).as_ref(), "medium"),
+                (shape(10.0).as_ref(), "high"),
+            ],
+            arange(0f64, 10f64, 0.01),
+        );
+        let y = SugenoVar::new(vec![
+            (linear(vec![0.0], 0.0), "a"),
+            (linear(vec![0.0], 0.0), "b"),
+            (constant(0.0), "c"),
+        ]);
+        let mut engine = SugenoEngine::new([x], [y]);
+        engine.add_rule(["low"], ["a"]);
+        engine.add_rule(["medium"], ["b"]);
+        engine.add_rule(["high"], ["c"]);
+        engine
+    }
+
+    fn gaussian_at(c: f64) -> Box<dyn Shape> {
+        Box::new(gaussian(c, 2.0))
+    }
+
+    fn bell_at(c: f64) -> Box<dyn Shape> {
+        Box::new(bell(2.5, 2.0, c))
+    }
+
+    fn samples() -> (Vec<[f64; 1]>, Vec<[f64; 1]>) {
+        let x: Vec<[f64; 1]> = (0..200).map(|i| [(i * 37 % 200) as f64 / 20.0]).collect();
+        let y = x
+            .iter()
+            .map(|x| [(x[0] / 2.0).sin() * 3.0 + x[0]])
+            .collect();
+        (x, y)
+    }
+
+    #[test]
+    fn hybrid_learning() {
+        let (x, y) = samples();
+        for shape in [gaussian_at, bell_at] {
+            let mut engine = engine(shape);
+            let anfis = Anfis {
+                epochs: 40,
+                patience: 40,
+                ..Anfis::default()
+            };
+            let training = engine.train(&anfis, &x, &y);
+            let first = training.train_loss[0];
+            let best = training.train_loss[training.best_epoch];
+            // least squares alone fits well, the premise steps improve on it
+            assert!(first < 0.5, "{}", first);
+            assert!(best < first, "{}", training);
+            assert_eq!(training.validation_loss.len(), training.train_loss.len());
+
+            let loss = x
+                .iter()
+                .zip(y.iter())
+                .skip(160)
+                .map(|(x, y)| (engine.calculate(*x)[0] - y[0]).powi(2))
+                .sum::<f64>()
+                / 40.0;
+            assert!((loss - training.validation_loss[training.best_epoch]).abs() < 1e-9);
+            // the input terms moved and stay analytic
+            assert!(
+                engine.inputs()[0].sets[1].shape.as_ref().unwrap().params() != shape(5.0).params()
+            );
+        }
+    }
+
+    #[test]
+    fn premise_gradients() {
+        let x = LinguisticVar::new(
+            vec![
+                (&gaussian(2.0, 1.5) as &dyn Shape, "a"),
+                (&gauss2(3.0, 1.0, 5.0, 2.0), "b"),
+                (&bell(2.0, 3.0, 5.0), "c"),
+                (&sigmoid(1.5, 4.0), "d"),
+                (&dsigmoid(2.0, 3.0, 1.0, 6.0), "e"),
+                (&psigmoid(2.0, 3.0, -1.0, 7.0), "f"),
+                (&crate::shape::triangular(5.0, 1.0, 2.0), "g"),
+            ],
+            arange(0f64, 10f64, 0.01),
+        )
+        .named("x");
+        let mut premises = Premises::new(&[x]);
+        assert_eq!(premises.terms.len(), 6);
+        assert_eq!(premises.fixed, vec![("x".to_string(), "g".to_string())]);
+
+        let n = premises.params.len();
+        let (mut grad, mut unused) = (vec![0.0; n], vec![0.0; n]);
+        for p in 0..premises.terms.len() {
+            for x in [0.5, 2.5, 4.2, 5.5, 8.0] {
+                premises.degree(p, x, &mut grad);
+                let (start, len) = (premises.terms[p].start, premises.shape(p).params().len());
+                for (k, g) in grad.iter().enumerate().skip(start).take(len) {
+                    let h = 1e-6;
+                    premises.params[k] += h;
+                    let up = premises.degree(p, x, &mut unused);
+                    premises.params[k] -= 2.0 * h;
+                    let down = premises.degree(p, x, &mut unused);
+                    premises.params[k] += h;
+                    let numeric = (up - down) / (2.0 * h);
+                    assert!((g - numeric).abs() < 1e-5, "{:?} {} {}", p, x, k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn early_stopping() {
+        let (x, mut y) = samples();
+        // validation targets unrelated to the training ones
+        for t in y.iter_mut().skip(150) {
+            t[0] = -t[0];
+        }
+        let mut engine = engine(gaussian_at);
+        let anfis = Anfis {
+            epochs: 200,
+            patience: 3,
+            validation: 0.25,
+            ..Anfis::default()
+        };
+        let training = engine.train(&anfis, &x, &y);
+        assert!(training.stopped_early);
+        assert_eq!(training.train_loss.len(), training.best_epoch + 4);
+        assert!(training.to_string().contains("stopped early"));
+
+        let mut dynamic = engine.into_dyn();
+        assert!(dynamic.try_train(&anfis, &x, &y[..10]).is_err());
+        assert!(dynamic
+            .try_train(
+                &Anfis {
+                    validation: 1.0,
+                    ..anfis
+                },
+                &x,
+                &y
+            )
+            .is_err());
+        assert!(dynamic.try_train(&anfis, &[[1.0, 2.0]], &[[1.0]]).is_err());
+        dynamic.set_method(SugenoMethod::WeightedSum);
+        assert!(dynamic.try_train(&anfis, &x, &y).is_err());
+    }
+}
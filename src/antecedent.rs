@@ -0,0 +1,233 @@
+use crate::error::{or_panic, FuzzyError};
+use crate::ops::Operators;
+use crate::parse::Terms;
+use crate::set::LinguisticVar;
+use serde::{Deserialize, Serialize};
+use std::ops::Not;
+
+/// linguistic modifiers applied to a membership degree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Hedge {
+    Very,      // μ^2
+    Extremely, // μ^3
+    Somewhat,  // μ^(1/2)
+    Slightly,  // μ^(1/3)
+}
+
+impl Hedge {
+    pub fn apply(&self, v: f64) -> f64 {
+        match self {
+            Hedge::Very => v.powi(2),
+            Hedge::Extremely => v.powi(3),
+            Hedge::Somewhat => v.sqrt(),
+            Hedge::Slightly => v.cbrt(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hedge::Very => "very",
+            Hedge::Extremely => "extremely",
+            Hedge::Somewhat => "somewhat",
+            Hedge::Slightly => "slightly",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Hedge> {
+        match name {
+            "very" => Some(Hedge::Very),
+            "extremely" => Some(Hedge::Extremely),
+            "somewhat" => Some(Hedge::Somewhat),
+            "slightly" => Some(Hedge::Slightly),
+            _ => None,
+        }
+    }
+}
+
+/// condition part of a rule. serialized as {"op": variant, "args": ...} since YAML can't
+/// write an enum directly inside another one, like Not(Is(..))
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "args")]
+pub enum Antecedent {
+    Any,                  // don't care, always fully satisfied
+    Is(usize, String),    // (input index, term name)
+    Not(Box<Antecedent>), // 1 - μ
+    Hedge(Hedge, Box<Antecedent>),
+    And(Vec<Antecedent>),
+    Or(Vec<Antecedent>),
+}
+
+pub fn is(var: usize, term: &str) -> Antecedent {
+    Antecedent::Is(var, term.to_string())
+}
+
+pub fn any() -> Antecedent {
+    Antecedent::Any
+}
+
+impl Not for Antecedent {
+    type Output = Antecedent;
+
+    fn not(self) -> Antecedent {
+        Antecedent::Not(Box::new(self))
+    }
+}
+
+impl Antecedent {
+    pub fn and(self, other: Antecedent) -> Antecedent {
+        match self {
+            Antecedent::And(mut v) => {
+                v.push(other);
+                Antecedent::And(v)
+            }
+            _ => Antecedent::And(vec![self, other]),
+        }
+    }
+
+    pub fn or(self, other: Antecedent) -> Antecedent {
+        match self {
+            Antecedent::Or(mut v) => {
+                v.push(other);
+                Antecedent::Or(v)
+            }
+            _ => Antecedent::Or(vec![self, other]),
+        }
+    }
+
+    pub fn hedge(self, hedge: Hedge) -> Antecedent {
+        Antecedent::Hedge(hedge, Box::new(self))
+    }
+
+    /// parse one slot of `add_rule`: "any" (or "-"), or `[not] [hedge ...] term`,
+    /// e.g. "not high", "very low", "not somewhat high". panics on an unknown modifier
+    pub fn from_slot(var: usize, slot: &str) -> Antecedent {
+        or_panic(Antecedent::try_from_slot(var, slot))
+    }
+
+    pub fn try_from_slot(var: usize, slot: &str) -> Result<Antecedent, FuzzyError> {
+        let words: Vec<&str> = slot.split_whitespace().collect();
+        if words.is_empty() || words == ["any"] || words == ["-"] {
+            return Ok(Antecedent::Any);
+        }
+        let mut res = is(var, words[words.len() - 1]);
+        for word in words[..words.len() - 1].iter().rev() {
+            res = match Hedge::from_name(word) {
+                Some(h) => res.hedge(h),
+                None if *word == "not" => !res,
+                None => {
+                    return Err(FuzzyError::UnknownModifier(
+                        word.to_string(),
+                        slot.to_string(),
+                    ))
+                }
+            };
+        }
+        Ok(res)
+    }
+
+    /// one slot per input variable joined by AND, as taken by `add_rule`
+    pub fn from_slots(slots: &[&str]) -> Antecedent {
+        or_panic(Antecedent::try_from_slots(slots))
+    }
+
+    pub fn try_from_slots(slots: &[&str]) -> Result<Antecedent, FuzzyError> {
+        Ok(Antecedent::And(
+            slots
+                .iter()
+                .enumerate()
+                .map(|(i, slot)| Antecedent::try_from_slot(i, slot))
+                .collect::<Result<Vec<Antecedent>, FuzzyError>>()?,
+        ))
+    }
+
+    /// panics if a term doesn't exist in its input variable
+    pub fn check<V: Terms>(&self, inputs_var: &[V]) {
+        or_panic(self.try_check(inputs_var))
+    }
+
+    pub fn try_check<V: Terms>(&self, inputs_var: &[V]) -> Result<(), FuzzyError> {
+        match self {
+            Antecedent::Any => Ok(()),
+            Antecedent::Is(i, term) => match inputs_var.get(*i) {
+                Some(var) if var.has_term(term) => Ok(()),
+                Some(var) => Err(FuzzyError::UnknownTerm(
+                    var.name().to_string(),
+                    term.to_string(),
+                )),
+                None => Err(FuzzyError::UnknownVariable(format!("#{}", i))),
+            },
+            Antecedent::Not(a) | Antecedent::Hedge(_, a) => a.try_check(inputs_var),
+            Antecedent::And(v) | Antecedent::Or(v) => {
+                v.iter().try_for_each(|a| a.try_check(inputs_var))
+            }
+        }
+    }
+
+    /// degree to which the inputs satisfy this condition
+    pub fn eval(&self, inputs_var: &[LinguisticVar], inputs: &[f64], ops: &Operators) -> f64 {
+        match self {
+            Antecedent::Any => 1.0,
+            Antecedent::Is(i, term) => inputs_var[*i].term(term).degree_of(inputs[*i]),
+            Antecedent::Not(a) => 1.0 - a.eval(inputs_var, inputs, ops),
+            Antecedent::Hedge(h, a) => h.apply(a.eval(inputs_var, inputs, ops)),
+            Antecedent::And(v) => v.iter().fold(1.0, |s, a| {
+                ops.and.apply(s, a.eval(inputs_var, inputs, ops))
+            }),
+            Antecedent::Or(v) => v
+                .iter()
+                .fold(0.0, |s, a| ops.or.apply(s, a.eval(inputs_var, inputs, ops))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::arange;
+    use crate::shape::*;
+
+    #[test]
+    fn eval_expressions() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let vars = [rsi.clone(), rsi];
+        let ops = Operators::default();
+        let inputs = [25.0, 75.0];
+
+        assert_eq!(
+            Antecedent::from_slot(0, "any").eval(&vars, &inputs, &ops),
+            1.0
+        );
+        assert_eq!(
+            Antecedent::from_slot(0, "low").eval(&vars, &inputs, &ops),
+            0.5
+        );
+        assert_eq!(
+            Antecedent::from_slot(0, "very low").eval(&vars, &inputs, &ops),
+            0.25
+        );
+        assert_eq!(
+            Antecedent::from_slot(1, "not very high").eval(&vars, &inputs, &ops),
+            0.75
+        );
+        assert_eq!(
+            Antecedent::from_slot(1, "not somewhat high"),
+            !is(1, "high").hedge(Hedge::Somewhat)
+        );
+
+        let c = is(0, "high").or(is(1, "high")).and(!is(0, "low"));
+        assert_eq!(c.eval(&vars, &inputs, &ops), 0.5);
+        assert_eq!(is(0, "high").and(any()).eval(&vars, &inputs, &ops), 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unknown_modifier() {
+        Antecedent::from_slot(0, "kinda low");
+    }
+}
@@ -23,7 +23,34 @@ impl Position {
     }
 }
 
-fn realizing_pos(capital: f64, price: &Vec<f64>, pos_list: &mut Vec<Position>, pos_type: bool) {
+/// closed trades of a backtest
+struct Summary {
+    trades: usize,
+    profits: Vec<f64>,
+    losses: Vec<f64>,
+}
+
+impl Summary {
+    fn net_profit(&self) -> f64 {
+        self.profits.iter().fold(0.0, |s, x| s + x) + self.losses.iter().fold(0.0, |s, x| s + x)
+    }
+
+    fn report(&self) {
+        let total_profit = self.profits.iter().fold(0.0, |s, x| s + x);
+        let total_losses = self.losses.iter().fold(0.0, |s, x| s + x);
+        println!("total trade: {:.3}", self.trades);
+        println!("net profit: {:.3}", total_profit + total_losses);
+        println!(
+            "count: {}, profits: {:.3}",
+            self.profits.len(),
+            total_profit
+        );
+        println!("count: {}, losses: {:.3}", self.losses.len(), total_losses);
+        println!("---------------");
+    }
+}
+
+fn realizing_pos(price: &[f64], pos_list: &mut Vec<Position>, pos_type: bool) -> Summary {
     let mut profit: Vec<f64> = vec![];
     let mut losses: Vec<f64> = vec![];
     for (i, p) in price.iter().enumerate() {
@@ -52,24 +79,25 @@ fn realizing_pos(capital: f64, price: &Vec<f64>, pos_list: &mut Vec<Position>, p
         }
     }
 
-    let total_profit = profit.iter().fold(0.0, |s, x| s + x);
-    let total_losses = losses.iter().fold(0.0, |s, x| s + x);
-    println!("total trade: {:.3}", pos_list.len());
-    println!("net profit: {:.3}", total_profit + total_losses);
-    println!("count: {}, profits: {:.3}", profit.len(), total_profit);
-    println!("count: {}, losses: {:.3}", losses.len(), total_losses);
-    println!("---------------");
-    /*
-    println!(
-        "total capital(+net_profit)): {}",
-        capital + (1000.0 - capital) + total_profit + total_losses
-    );
-    */
+    Summary {
+        trades: pos_list.len(),
+        profits: profit,
+        losses,
+    }
 }
 
 /// Fuzzy BackTest
 /// pos_type - false for long, true for short
 pub fn f_backtest(price: &Vec<f64>, signal: &Vec<f64>, pos_type: bool) {
+    f_positions(price, signal, pos_type).report()
+}
+
+/// net profit of `f_backtest` without printing the report, e.g. as a tuning fitness
+pub fn f_net_profit(price: &[f64], signal: &[f64], pos_type: bool) -> f64 {
+    f_positions(price, signal, pos_type).net_profit()
+}
+
+fn f_positions(price: &[f64], signal: &[f64], pos_type: bool) -> Summary {
     let mut capital = 1000.0;
     let mut pos_list: Vec<Position> = vec![];
 
@@ -83,7 +111,7 @@ pub fn f_backtest(price: &Vec<f64>, signal: &Vec<f64>, pos_type: bool) {
         }
     }
 
-    realizing_pos(capital, price, &mut pos_list, pos_type)
+    realizing_pos(price, &mut pos_list, pos_type)
 }
 
 pub fn c_backtest(price: &Vec<f64>, rsi: &Vec<f64>, bb: &Vec<(f64, f64)>, pos_type: bool) {
@@ -119,5 +147,5 @@ pub fn c_backtest(price: &Vec<f64>, rsi: &Vec<f64>, bb: &Vec<(f64, f64)>, pos_ty
         }
     }
 
-    realizing_pos(capital, price, &mut pos_list, pos_type)
+    realizing_pos(price, &mut pos_list, pos_type).report()
 }
@@ -0,0 +1,272 @@
+// evaluating an engine over many input rows, e.g. a whole indicator time series.
+// with the `parallel` feature the par_* functions split the rows across threads using rayon
+
+use crate::error::{or_panic, FuzzyError};
+use crate::rule::{DynFuzzyEngine, FuzzyEngine};
+use crate::set::FuzzySet;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// buffers reused between calls of `defuzzify_with`, made by `DynFuzzyEngine::scratch`
+pub struct Scratch {
+    strengths: Vec<f64>,
+    aggregated: Vec<FuzzySet>,
+    row: Vec<f64>,
+}
+
+impl DynFuzzyEngine {
+    pub fn scratch(&self) -> Scratch {
+        Scratch {
+            strengths: Vec::with_capacity(self.rules().len()),
+            aggregated: self.empty_outputs(),
+            row: Vec::with_capacity(self.inputs().len()),
+        }
+    }
+
+    /// `defuzzify` without allocating the output sets, results are written to `out`
+    pub fn defuzzify_with(&self, inputs: &[f64], scratch: &mut Scratch, out: &mut Vec<f64>) {
+        or_panic(self.try_defuzzify_with(inputs, scratch, out))
+    }
+
+    pub fn try_defuzzify_with(
+        &self,
+        inputs: &[f64],
+        scratch: &mut Scratch,
+        out: &mut Vec<f64>,
+    ) -> Result<(), FuzzyError> {
+        // a scratch made for another engine is rebuilt. universes are evenly spaced, so their
+        // length and end points tell them apart without comparing every point on every row
+        let fits = scratch.aggregated.len() == self.outputs().len()
+            && scratch
+                .aggregated
+                .iter()
+                .zip(self.outputs().iter())
+                .all(|(set, var)| {
+                    set.universe.len() == var.universe.len()
+                        && set.universe.first() == var.universe.first()
+                        && set.universe.last() == var.universe.last()
+                });
+        if !fits {
+            *scratch = self.scratch();
+        }
+        self.strengths_into(inputs, &mut scratch.strengths)?;
+        self.aggregate_into(&scratch.strengths, &mut scratch.aggregated);
+        self.crisp_into(&scratch.strengths, &scratch.aggregated, out);
+        Ok(())
+    }
+
+    /// crisp outputs for every row of inputs
+    pub fn defuzzify_batch<R: AsRef<[f64]>>(&self, rows: &[R]) -> Vec<Vec<f64>> {
+        or_panic(self.try_defuzzify_batch(rows))
+    }
+
+    pub fn try_defuzzify_batch<R: AsRef<[f64]>>(
+        &self,
+        rows: &[R],
+    ) -> Result<Vec<Vec<f64>>, FuzzyError> {
+        let mut scratch = self.scratch();
+        rows.iter()
+            .map(|row| {
+                let mut out = Vec::with_capacity(self.outputs().len());
+                self.try_defuzzify_with(row.as_ref(), &mut scratch, &mut out)?;
+                Ok(out)
+            })
+            .collect()
+    }
+
+    /// one column of values per input variable, returns one column per output variable
+    pub fn defuzzify_columns(&self, columns: &[&[f64]]) -> Vec<Vec<f64>> {
+        or_panic(self.try_defuzzify_columns(columns))
+    }
+
+    pub fn try_defuzzify_columns(&self, columns: &[&[f64]]) -> Result<Vec<Vec<f64>>, FuzzyError> {
+        let len = self.column_len(columns)?;
+        let mut scratch = self.scratch();
+        let mut res = vec![Vec::with_capacity(len); self.outputs().len()];
+        let mut out = Vec::with_capacity(self.outputs().len());
+        let mut row = std::mem::take(&mut scratch.row);
+        for k in 0..len {
+            row.clear();
+            row.extend(columns.iter().map(|c| c[k]));
+            self.try_defuzzify_with(&row, &mut scratch, &mut out)?;
+            for (column, x) in res.iter_mut().zip(out.iter()) {
+                column.push(*x);
+            }
+        }
+        Ok(res)
+    }
+
+    /// `defuzzify_batch` with the rows split across threads
+    #[cfg(feature = "parallel")]
+    pub fn par_defuzzify_batch<R: AsRef<[f64]> + Sync>(&self, rows: &[R]) -> Vec<Vec<f64>> {
+        or_panic(self.try_par_defuzzify_batch(rows))
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn try_par_defuzzify_batch<R: AsRef<[f64]> + Sync>(
+        &self,
+        rows: &[R],
+    ) -> Result<Vec<Vec<f64>>, FuzzyError> {
+        rows.par_iter()
+            .map_init(
+                || self.scratch(),
+                |scratch, row| {
+                    let mut out = Vec::with_capacity(self.outputs().len());
+                    self.try_defuzzify_with(row.as_ref(), scratch, &mut out)?;
+                    Ok(out)
+                },
+            )
+            .collect()
+    }
+
+    /// `defuzzify_columns` with the rows split across threads
+    #[cfg(feature = "parallel")]
+    pub fn par_defuzzify_columns(&self, columns: &[&[f64]]) -> Vec<Vec<f64>> {
+        or_panic(self.try_par_defuzzify_columns(columns))
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn try_par_defuzzify_columns(
+        &self,
+        columns: &[&[f64]],
+    ) -> Result<Vec<Vec<f64>>, FuzzyError> {
+        let len = self.column_len(columns)?;
+        let rows: Vec<Vec<f64>> = (0..len)
+            .into_par_iter()
+            .map_init(
+                || self.scratch(),
+                |scratch, k| {
+                    let mut row = std::mem::take(&mut scratch.row);
+                    row.clear();
+                    row.extend(columns.iter().map(|c| c[k]));
+                    let mut out = Vec::with_capacity(self.outputs().len());
+                    let res = self.try_defuzzify_with(&row, scratch, &mut out);
+                    scratch.row = row;
+                    res.map(|_| out)
+                },
+            )
+            .collect::<Result<_, FuzzyError>>()?;
+        let mut res = vec![Vec::with_capacity(len); self.outputs().len()];
+        for out in rows {
+            for (column, x) in res.iter_mut().zip(out) {
+                column.push(x);
+            }
+        }
+        Ok(res)
+    }
+
+    /// length shared by all columns, one column per input variable
+    fn column_len(&self, columns: &[&[f64]]) -> Result<usize, FuzzyError> {
+        if columns.len() != self.inputs().len() {
+            return Err(FuzzyError::Arity(
+                "input columns".into(),
+                self.inputs().len(),
+                columns.len(),
+            ));
+        }
+        let len = columns.first().map_or(0, |c| c.len());
+        for column in columns.iter() {
+            if column.len() != len {
+                return Err(FuzzyError::Arity("rows".into(), len, column.len()));
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// crisp outputs for every row of inputs
+    pub fn defuzzify_batch(&self, rows: &[[f64; N]]) -> Vec<Vec<f64>> {
+        self.as_dyn().defuzzify_batch(rows)
+    }
+
+    /// one column of values per input variable, returns one column per output variable.
+    /// panics if the columns have different lengths
+    pub fn defuzzify_columns(&self, columns: [&[f64]; N]) -> Vec<Vec<f64>> {
+        self.as_dyn().defuzzify_columns(&columns)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn par_defuzzify_batch(&self, rows: &[[f64; N]]) -> Vec<Vec<f64>> {
+        self.as_dyn().par_defuzzify_batch(rows)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn par_defuzzify_columns(&self, columns: [&[f64]; N]) -> Vec<Vec<f64>> {
+        self.as_dyn().par_defuzzify_columns(&columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defuzz::Defuzzifier;
+    use crate::set::*;
+    use crate::shape::*;
+
+    fn engine() -> FuzzyEngine<2, 1> {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.1),
+        );
+        let mut engine = FuzzyEngine::new([x.clone(), x.clone()], [x]);
+        engine.add_rule(["low", "any"], ["high"]);
+        engine.add_rule(["high", "low"], ["low"]);
+        engine.add_rule_weighted(["any", "high"], ["low"], 0.4);
+        engine
+    }
+
+    #[test]
+    fn same_as_defuzzify() {
+        let mut engine = engine();
+        let a: Vec<f64> = (0..50).map(|i| i as f64 * 2.0).collect();
+        let b: Vec<f64> = (0..50).map(|i| 100.0 - i as f64 * 1.7).collect();
+        let rows: Vec<[f64; 2]> = a.iter().zip(b.iter()).map(|(x, y)| [*x, *y]).collect();
+
+        for defuzzifier in [Defuzzifier::Centroid, Defuzzifier::WeightedCentroid] {
+            engine.set_defuzzifier(defuzzifier);
+            let expected: Vec<Vec<f64>> = rows.iter().map(|r| engine.defuzzify(*r)).collect();
+            assert_eq!(engine.defuzzify_batch(&rows), expected);
+            let columns = engine.defuzzify_columns([&a, &b]);
+            assert_eq!(columns.len(), 1);
+            assert!(columns[0]
+                .iter()
+                .zip(expected.iter())
+                .all(|(x, e)| *x == e[0]));
+
+            #[cfg(feature = "parallel")]
+            {
+                assert_eq!(engine.par_defuzzify_batch(&rows), expected);
+                assert_eq!(engine.par_defuzzify_columns([&a, &b]), columns);
+            }
+        }
+
+        // a scratch from another engine is replaced rather than misused
+        let other = FuzzyEngine::new(
+            [LinguisticVar::new(vec![], arange(0.0, 1.0, 0.5))],
+            [LinguisticVar::new(vec![], arange(0.0, 1.0, 0.5))],
+        );
+        let mut scratch = other.as_dyn().scratch();
+        let mut out = vec![];
+        engine
+            .as_dyn()
+            .defuzzify_with(&rows[3], &mut scratch, &mut out);
+        assert_eq!(out, engine.defuzzify(rows[3]));
+    }
+
+    #[test]
+    fn column_errors() {
+        let engine = engine().into_dyn();
+        let a = [1.0, 2.0];
+        assert!(matches!(
+            engine.try_defuzzify_columns(&[&a]),
+            Err(FuzzyError::Arity(_, 2, 1))
+        ));
+        assert!(engine.try_defuzzify_columns(&[&a, &a[..1]]).is_err());
+        assert!(engine.try_defuzzify_batch(&[vec![1.0]]).is_err());
+        assert!(engine.try_defuzzify_columns(&[&[], &[]]).unwrap()[0].is_empty());
+    }
+}
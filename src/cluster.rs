@@ -0,0 +1,462 @@
+// fuzzy c-means and gustafson-kessel clustering of indicator samples, with the clusters
+// projected onto one dimension as the terms of a linguistic variable
+
+use crate::error::{or_panic, FuzzyError};
+use crate::partition::{auto_names, in_universe};
+use crate::set::{FuzzySet, LinguisticVar};
+use crate::shape::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    FuzzyCMeans,     // euclidean distance, round clusters
+    GustafsonKessel, // distance adapted to each cluster's covariance, elongated clusters
+}
+
+/// settings of the clustering, memberships start random from `seed`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CMeans {
+    pub algorithm: Algorithm,
+    pub clusters: usize,
+    pub fuzziness: f64, // exponent m > 1 of the memberships, larger gives softer clusters
+    pub max_iterations: usize,
+    pub tolerance: f64, // stop once no membership changes by more than this
+    pub seed: u64,
+}
+
+impl Default for CMeans {
+    fn default() -> Self {
+        CMeans {
+            algorithm: Algorithm::FuzzyCMeans,
+            clusters: 3,
+            fuzziness: 2.0,
+            max_iterations: 300,
+            tolerance: 1e-6,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clusters {
+    pub centres: Vec<Vec<f64>>,
+    pub covariances: Vec<Vec<Vec<f64>>>, // fuzzy covariance matrix of each cluster
+    pub memberships: Vec<Vec<f64>>, // [sample][cluster], NaN for samples with non-finite values
+    pub objective: f64,             // sum of memberships^m times squared distances
+    pub iterations: usize,
+}
+
+/// shape of the terms built by `Clusters::project`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Gaussian,   // standard deviation of the cluster
+    Triangular, // same variance as the cluster, half width sqrt(6) standard deviations
+}
+
+/// inverse and determinant of a square matrix, Gauss-Jordan with partial pivoting.
+/// None if it's singular
+fn invert(m: &[Vec<f64>]) -> Option<(Vec<Vec<f64>>, f64)> {
+    let n = m.len();
+    let mut a: Vec<Vec<f64>> = m.to_vec();
+    let mut inv: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| (i == j) as u8 as f64).collect())
+        .collect();
+    let mut det = 1.0;
+    for col in 0..n {
+        let pivot = (col..n).max_by(|x, y| a[*x][col].abs().total_cmp(&a[*y][col].abs()))?;
+        if a[pivot][col].abs() < 1e-300 {
+            return None;
+        }
+        if pivot != col {
+            a.swap(pivot, col);
+            inv.swap(pivot, col);
+            det = -det;
+        }
+        let p = a[col][col];
+        det *= p;
+        for j in 0..n {
+            a[col][j] /= p;
+            inv[col][j] /= p;
+        }
+        for row in 0..n {
+            let f = a[row][col];
+            if row != col && f != 0.0 {
+                for j in 0..n {
+                    a[row][j] -= f * a[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+    }
+    Some((inv, det))
+}
+
+impl CMeans {
+    /// cluster `samples`, each a point with one value per dimension like `[rsi, bb]`.
+    /// samples with a NaN or infinite value are left out. `data::rsi` and `data::bb` fill their
+    /// warm-up with -1.0 instead, slice it off first, e.g. `&rsi[15..]` for a 14 period rsi
+    pub fn fit<R: AsRef<[f64]>>(&self, samples: &[R]) -> Clusters {
+        or_panic(self.try_fit(samples))
+    }
+
+    /// fails for gustafson-kessel if a cluster's covariance can't be inverted
+    pub fn try_fit<R: AsRef<[f64]>>(&self, samples: &[R]) -> Result<Clusters, FuzzyError> {
+        if self.fuzziness.is_nan() || self.fuzziness <= 1.0 || self.fuzziness.is_infinite() {
+            return Err(FuzzyError::InvalidRange(
+                "fuzziness must be finite and > 1".into(),
+            ));
+        }
+        if self.tolerance.is_nan() || self.tolerance < 0.0 {
+            return Err(FuzzyError::InvalidRange("tolerance must be >= 0".into()));
+        }
+        let dims = samples.first().map_or(0, |x| x.as_ref().len());
+        for x in samples.iter() {
+            if x.as_ref().len() != dims {
+                return Err(FuzzyError::Arity(
+                    "dimensions".into(),
+                    dims,
+                    x.as_ref().len(),
+                ));
+            }
+        }
+        let kept: Vec<usize> = (0..samples.len())
+            .filter(|k| samples[*k].as_ref().iter().all(|x| x.is_finite()))
+            .collect();
+        let c = self.clusters;
+        if dims == 0 || c == 0 || kept.len() < c {
+            return Err(FuzzyError::InvalidRange(format!(
+                "{} clusters need at least as many finite samples, got {}",
+                c,
+                kept.len()
+            )));
+        }
+        let points: Vec<&[f64]> = kept.iter().map(|k| samples[*k].as_ref()).collect();
+        let m = self.fuzziness;
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut u: Vec<Vec<f64>> = points
+            .iter()
+            .map(|_| {
+                let row: Vec<f64> = (0..c).map(|_| rng.gen::<f64>() + 1e-3).collect();
+                let total: f64 = row.iter().sum();
+                row.into_iter().map(|x| x / total).collect()
+            })
+            .collect();
+
+        let mut centres = vec![vec![0.0; dims]; c];
+        let mut covariances = vec![vec![vec![0.0; dims]; dims]; c];
+        let mut distances = vec![vec![0.0; c]; points.len()];
+        let mut iterations = 0;
+        while iterations < self.max_iterations {
+            iterations += 1;
+            for i in 0..c {
+                let weights: Vec<f64> = u.iter().map(|row| row[i].powf(m)).collect();
+                let total: f64 = weights.iter().sum();
+                for (d, centre) in centres[i].iter_mut().enumerate() {
+                    *centre = weights
+                        .iter()
+                        .zip(points.iter())
+                        .map(|(w, x)| w * x[d])
+                        .sum::<f64>()
+                        / total;
+                }
+                for (a, row) in covariances[i].iter_mut().enumerate() {
+                    for (b, cov) in row.iter_mut().enumerate() {
+                        *cov = weights
+                            .iter()
+                            .zip(points.iter())
+                            .map(|(w, x)| w * (x[a] - centres[i][a]) * (x[b] - centres[i][b]))
+                            .sum::<f64>()
+                            / total;
+                    }
+                }
+                // norm inducing matrix, identity for fuzzy c-means. gustafson-kessel scales
+                // the inverse covariance to a determinant of 1 so every cluster has the same volume
+                let norm = match self.algorithm {
+                    Algorithm::FuzzyCMeans => None,
+                    Algorithm::GustafsonKessel => {
+                        // a small ridge keeps clusters collapsed onto a line invertible
+                        let trace: f64 = (0..dims).map(|d| covariances[i][d][d]).sum();
+                        let ridge = 1e-9 * (trace / dims as f64).max(f64::MIN_POSITIVE);
+                        let mut f = covariances[i].clone();
+                        for (d, row) in f.iter_mut().enumerate() {
+                            row[d] += ridge;
+                        }
+                        let (inv, det) = match invert(&f) {
+                            Some(x) => x,
+                            None => {
+                                return Err(FuzzyError::InvalidRange(format!(
+                                    "cluster {} has a singular covariance",
+                                    i
+                                )))
+                            }
+                        };
+                        let scale = det.abs().powf(1.0 / dims as f64);
+                        Some(
+                            inv.into_iter()
+                                .map(|row| row.into_iter().map(|x| x * scale).collect::<Vec<_>>())
+                                .collect::<Vec<_>>(),
+                        )
+                    }
+                };
+                for (x, row) in points.iter().zip(distances.iter_mut()) {
+                    let diff: Vec<f64> = x
+                        .iter()
+                        .zip(centres[i].iter())
+                        .map(|(x, v)| x - v)
+                        .collect();
+                    row[i] = match &norm {
+                        None => diff.iter().map(|d| d * d).sum(),
+                        Some(a) => a
+                            .iter()
+                            .zip(diff.iter())
+                            .map(|(row, da)| {
+                                da * row
+                                    .iter()
+                                    .zip(diff.iter())
+                                    .map(|(x, db)| x * db)
+                                    .sum::<f64>()
+                            })
+                            .sum::<f64>()
+                            .max(0.0),
+                    };
+                }
+            }
+
+            let mut change: f64 = 0.0;
+            for (row, dist) in u.iter_mut().zip(distances.iter()) {
+                let zeros = dist.iter().filter(|d| **d == 0.0).count();
+                for i in 0..c {
+                    let new = match zeros {
+                        // the sample sits on a centre, it's shared by the centres it sits on
+                        0 => {
+                            1.0 / dist
+                                .iter()
+                                .map(|dj| (dist[i] / dj).powf(1.0 / (m - 1.0)))
+                                .sum::<f64>()
+                        }
+                        _ => (dist[i] == 0.0) as u8 as f64 / zeros as f64,
+                    };
+                    change = change.max((new - row[i]).abs());
+                    row[i] = new;
+                }
+            }
+            if change <= self.tolerance {
+                break;
+            }
+        }
+
+        let objective = u
+            .iter()
+            .zip(distances.iter())
+            .map(|(row, dist)| {
+                row.iter()
+                    .zip(dist.iter())
+                    .map(|(u, d)| u.powf(m) * d)
+                    .sum::<f64>()
+            })
+            .sum();
+        let mut memberships = vec![vec![f64::NAN; c]; samples.len()];
+        for (k, row) in kept.into_iter().zip(u) {
+            memberships[k] = row;
+        }
+        Ok(Clusters {
+            centres,
+            covariances,
+            memberships,
+            objective,
+            iterations,
+        })
+    }
+}
+
+impl Clusters {
+    /// standard deviation of cluster `i` along dimension `dim`
+    pub fn spread(&self, i: usize, dim: usize) -> f64 {
+        self.covariances[i][dim][dim].sqrt()
+    }
+
+    /// one term per cluster over `universe`, centred on the cluster along dimension `dim`
+    /// with its spread. terms are sorted by centre and named low/medium/high style like
+    /// `LinguisticVar::uniform`, the first and last are shoulders staying at 1 outwards
+    pub fn project(&self, dim: usize, kind: Projection, universe: Vec<f64>) -> LinguisticVar {
+        or_panic(self.try_project(dim, kind, universe))
+    }
+
+    pub fn try_project(
+        &self,
+        dim: usize,
+        kind: Projection,
+        universe: Vec<f64>,
+    ) -> Result<LinguisticVar, FuzzyError> {
+        let dims = self.centres.first().map_or(0, |v| v.len());
+        if dim >= dims {
+            return Err(FuzzyError::InvalidRange(format!(
+                "dimension {} is out of the {} clustered",
+                dim, dims
+            )));
+        }
+        let mut order: Vec<usize> = (0..self.centres.len()).collect();
+        order.sort_by(|a, b| self.centres[*a][dim].total_cmp(&self.centres[*b][dim]));
+        let names = auto_names(order.len());
+        let k = order.len();
+        let mut sets = vec![];
+        for (j, i) in order.into_iter().enumerate() {
+            let (c, sigma) = (self.centres[i][dim], self.spread(i, dim));
+            if sigma.is_nan() || sigma <= 0.0 {
+                return Err(FuzzyError::InvalidRange(format!(
+                    "cluster {} has no spread along dimension {}",
+                    i, dim
+                )));
+            }
+            let w = 6f64.sqrt() * sigma;
+            let shape: Box<dyn Shape> = match (kind, j == 0 && k > 1, j + 1 == k && k > 1) {
+                (Projection::Gaussian, true, _) => Box::new(gauss2(f64::MIN, sigma, c, sigma)),
+                (Projection::Gaussian, _, true) => Box::new(gauss2(c, sigma, f64::MAX, sigma)),
+                (Projection::Gaussian, _, _) => Box::new(gaussian(c, sigma)),
+                (Projection::Triangular, true, _) => Box::new(points(vec![(c, 1.0), (c + w, 0.0)])),
+                (Projection::Triangular, _, true) => Box::new(points(vec![(c - w, 0.0), (c, 1.0)])),
+                (Projection::Triangular, _, _) => Box::new(triangular(c, 1.0, w)),
+            };
+            sets.push(FuzzySet::analytic(
+                &universe,
+                shape.as_ref(),
+                names[j].clone(),
+            ));
+        }
+        Ok(LinguisticVar {
+            name: String::new(),
+            sets,
+            universe,
+        })
+    }
+}
+
+impl LinguisticVar {
+    /// `k` terms from fuzzy c-means clusters of a single series like `data::rsi`,
+    /// see `Clusters::project`. values outside the universe are ignored, see
+    /// `partition::in_universe`
+    pub fn clustered(
+        kind: Projection,
+        k: usize,
+        data: &[f64],
+        universe: Vec<f64>,
+    ) -> LinguisticVar {
+        or_panic(LinguisticVar::try_clustered(kind, k, data, universe))
+    }
+
+    pub fn try_clustered(
+        kind: Projection,
+        k: usize,
+        data: &[f64],
+        universe: Vec<f64>,
+    ) -> Result<LinguisticVar, FuzzyError> {
+        if universe.is_empty() {
+            return Err(FuzzyError::InvalidRange("empty universe".into()));
+        }
+        let samples: Vec<[f64; 1]> = data
+            .iter()
+            .filter(|x| in_universe(&universe, **x))
+            .map(|x| [*x])
+            .collect();
+        let cmeans = CMeans {
+            clusters: k,
+            ..CMeans::default()
+        };
+        cmeans.try_fit(&samples)?.try_project(0, kind, universe)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::arange;
+
+    #[test]
+    fn clustered_terms() {
+        // an rsi-like series hovering around 25, 50 and 75, after the warm-up
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut rsi = vec![-1.0; 15];
+        for i in 0..300 {
+            rsi.push([25.0, 50.0, 75.0][i % 3] + rng.gen_range(-5.0..5.0));
+        }
+
+        let var =
+            LinguisticVar::clustered(Projection::Gaussian, 3, &rsi, arange(0f64, 100f64, 0.5));
+        let names: Vec<&str> = var.sets.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["low", "medium", "high"]);
+        assert!((var.term("medium").mom_defuzz() - 50.0).abs() < 1.0);
+        assert_eq!(var.term("low").degree_of(0.0), 1.0);
+        assert_eq!(var.term("high").degree_of(100.0), 1.0);
+        let q = var.quality();
+        assert!(q.ordered);
+        assert!(q.uncovered.is_empty());
+
+        let var =
+            LinguisticVar::clustered(Projection::Triangular, 3, &rsi, arange(0f64, 100f64, 0.5));
+        assert_eq!(var.term("medium").degree_of(50.0).round(), 1.0);
+        assert_eq!(var.term("medium").degree_of(35.0), 0.0);
+        assert_eq!(var.sets[1].shape.as_ref().unwrap().kind(), "triangular");
+    }
+
+    #[test]
+    fn elongated_clusters() {
+        // two long thin clusters, one along each axis, crossing near the origin
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut samples = vec![[f64::NAN, 0.0]];
+        for _ in 0..200 {
+            samples.push([rng.gen_range(-10.0..10.0), 5.0 + rng.gen_range(-0.5..0.5)]);
+            samples.push([15.0 + rng.gen_range(-0.5..0.5), rng.gen_range(-10.0..10.0)]);
+        }
+
+        let gk = CMeans {
+            algorithm: Algorithm::GustafsonKessel,
+            clusters: 2,
+            ..CMeans::default()
+        }
+        .fit(&samples);
+        assert!(gk.memberships[0][0].is_nan());
+        let horizontal = (gk.centres[0][1] - 5.0).abs() > 1.0;
+        let (h, v) = match horizontal {
+            true => (1, 0),
+            false => (0, 1),
+        };
+        assert!((gk.centres[h][1] - 5.0).abs() < 1.0, "{:?}", gk.centres);
+        assert!((gk.centres[v][0] - 15.0).abs() < 1.0, "{:?}", gk.centres);
+        assert!(gk.spread(h, 0) > 5.0 * gk.spread(h, 1));
+        assert!(gk.spread(v, 1) > 5.0 * gk.spread(v, 0));
+        // every sample is assigned to the line it was drawn from
+        for (k, row) in gk.memberships.iter().enumerate().skip(1) {
+            let line = match k % 2 {
+                1 => h,
+                _ => v,
+            };
+            assert!(row[line] > 0.5, "{} {:?} {:?}", k, samples[k], row);
+        }
+
+        let bb = gk.project(1, Projection::Gaussian, arange(-20f64, 20f64, 0.1));
+        assert_eq!(bb.sets.len(), 2);
+        assert!(gk
+            .try_project(2, Projection::Gaussian, arange(0.0, 1.0, 0.1))
+            .is_err());
+        assert!(CMeans::default().try_fit(&samples[..2]).is_err());
+        assert!(CMeans {
+            fuzziness: 1.0,
+            ..CMeans::default()
+        }
+        .try_fit(&samples)
+        .is_err());
+        assert!(CMeans::default()
+            .try_fit(&[vec![1.0], vec![1.0, 2.0]])
+            .is_err());
+        // identical samples leave gustafson-kessel no covariance to invert
+        let gk = CMeans {
+            algorithm: Algorithm::GustafsonKessel,
+            clusters: 2,
+            ..CMeans::default()
+        };
+        assert!(gk.try_fit(&[[1.0, 2.0]; 4]).is_err());
+        assert!(gk.try_fit(&samples).is_ok());
+    }
+}
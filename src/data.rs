@@ -1,3 +1,4 @@
+use crate::error::{or_panic, FuzzyError};
 use serde::Deserialize;
 use std::fs;
 
@@ -83,13 +84,18 @@ pub fn bb(data: &Vec<Record>, n: usize) -> Vec<(f64, f64)> {
 
 /// read coingecko csv data
 pub fn read_csv(path: &str) -> Vec<Record> {
-    let contents = fs::read_to_string(path).unwrap();
+    or_panic(try_read_csv(path))
+}
+
+/// fails on a missing file or on the first malformed row
+pub fn try_read_csv(path: &str) -> Result<Vec<Record>, FuzzyError> {
+    let contents = fs::read_to_string(path)?;
 
     let mut rdr = csv::Reader::from_reader(contents.as_bytes());
     let mut data: Vec<Record> = vec![];
     for result in rdr.deserialize() {
-        let record: Record = result.unwrap();
+        let record: Record = result?;
         data.push(record);
     }
-    data
+    Ok(data)
 }
@@ -0,0 +1,105 @@
+use crate::set::FuzzySet;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Defuzzifier {
+    Centroid,
+    Bisector,
+    MeanOfMax,
+    SmallestOfMax,
+    LargestOfMax,
+    WeightedAverage, // peak of each consequent weighted by its firing strength, the height method
+    WeightedCentroid, // centroid of each consequent weighted by its firing strength
+}
+
+impl Defuzzifier {
+    /// `aggregated` is the output set of the engine, `fired` holds each rule's
+    /// (unclipped consequent term, firing strength) which the last two methods work on
+    pub fn defuzzify(&self, aggregated: &FuzzySet, fired: &[(&FuzzySet, f64)]) -> f64 {
+        self.defuzzify_fired(aggregated, fired.iter().copied())
+    }
+
+    /// `defuzzify` reading the fired terms as they're produced, without collecting them
+    pub(crate) fn defuzzify_fired<'a>(
+        &self,
+        aggregated: &FuzzySet,
+        fired: impl Iterator<Item = (&'a FuzzySet, f64)>,
+    ) -> f64 {
+        match self {
+            Defuzzifier::Centroid => aggregated.centroid_defuzz(),
+            Defuzzifier::Bisector => aggregated.bisector_defuzz(),
+            Defuzzifier::MeanOfMax => aggregated.mom_defuzz(),
+            Defuzzifier::SmallestOfMax => aggregated.som_defuzz(),
+            Defuzzifier::LargestOfMax => aggregated.lom_defuzz(),
+            Defuzzifier::WeightedAverage => weighted(fired, |set| set.mom_defuzz()),
+            Defuzzifier::WeightedCentroid => weighted(fired, |set| set.centroid_defuzz()),
+        }
+    }
+}
+
+fn weighted<'a>(
+    fired: impl Iterator<Item = (&'a FuzzySet, f64)>,
+    point: impl Fn(&FuzzySet) -> f64,
+) -> f64 {
+    let (top_sum, bot_sum) = fired.fold((0.0, 0.0), |(top, bot), (set, w)| {
+        (top + point(set) * w, bot + w)
+    });
+    if bot_sum == 0.0 {
+        return 0.0;
+    }
+    top_sum / bot_sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::FuzzyEngine;
+    use crate::set::{arange, LinguisticVar};
+    use crate::shape::*;
+
+    #[test]
+    fn rule_based_methods() {
+        let universe = arange(0.0, 100.0, 0.01);
+        let weak = FuzzySet::new(&universe, &triangular(20.0, 1.0, 20.0), "weak".into());
+        let strong = FuzzySet::new(
+            &universe,
+            &trapezoidal(40.0, 60.0, 80.0, 100.0, 1.0),
+            "strong".into(),
+        );
+        let fired = [(&weak, 0.5), (&strong, 0.5), (&strong, 0.0)];
+        let aggregated = weak
+            .min(0.5, "".into())
+            .std_union(&strong.min(0.5, "".into()), "".into());
+
+        let d = Defuzzifier::WeightedAverage.defuzzify(&aggregated, &fired);
+        assert!((d - 45.0).abs() < 1e-6);
+        let d = Defuzzifier::WeightedCentroid.defuzzify(&aggregated, &fired);
+        assert!((d - (20.0 + strong.centroid_defuzz()) / 2.0).abs() < 1e-6);
+        assert_eq!(
+            Defuzzifier::WeightedCentroid.defuzzify(&aggregated, &[]),
+            0.0
+        );
+    }
+
+    #[test]
+    fn engine_defuzzifier() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(20f64, 1.0, 20f64), "low"),
+                (&triangular(80f64, 1.0, 20f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let trend = LinguisticVar::new(
+            vec![(&triangular(30f64, 1f64, 30f64), "weak")],
+            arange(0f64, 100f64, 0.01),
+        );
+        let mut engine = FuzzyEngine::new([rsi], [trend]);
+        engine.add_rule(["low"], ["weak"]);
+
+        let res = engine.calculate([15f64]);
+        assert_eq!(engine.defuzzify([15f64])[0], res[0].centroid_defuzz());
+        engine.set_defuzzifier(Defuzzifier::MeanOfMax);
+        assert_eq!(engine.defuzzify([15f64])[0], res[0].mom_defuzz());
+    }
+}
@@ -0,0 +1,103 @@
+use crate::parse::ParseError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// error returned by the `try_*` variants of the panicking api
+#[derive(Debug)]
+pub enum FuzzyError {
+    UnknownVariable(String),
+    UnknownTerm(String, String),     // (variable, term)
+    UnknownModifier(String, String), // (word, condition)
+    Arity(String, usize, usize),     // (what, expected, found)
+    UniverseMismatch,
+    InvalidRange(String),
+    InvalidShape(String, usize), // (kind, number of parameters)
+    InvalidWeight(f64),
+    UnknownRule(usize),
+    Incomplete(String, f64, f64), // (variable, epsilon reached, epsilon needed)
+    Unordered(String),
+    Unsupported(String, String), // (what, file format)
+    Parse(ParseError),
+    Io(io::Error),
+    Csv(csv::Error),
+}
+
+impl fmt::Display for FuzzyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FuzzyError::UnknownVariable(var) => write!(f, "there're no variable name {}", var),
+            FuzzyError::UnknownTerm(var, term) => {
+                write!(f, "there're no term name {} in variable \"{}\"", term, var)
+            }
+            FuzzyError::UnknownModifier(word, cond) => {
+                write!(f, "unknown modifier {} in \"{}\"", word, cond)
+            }
+            FuzzyError::Arity(what, expected, found) => {
+                write!(f, "needs {} {}, got {}", expected, what, found)
+            }
+            FuzzyError::UniverseMismatch => write!(f, "domain needs to be equal"),
+            FuzzyError::InvalidRange(msg) => write!(f, "{}", msg),
+            FuzzyError::InvalidShape(kind, n) => {
+                write!(f, "there're no shape {} taking {} parameters", kind, n)
+            }
+            FuzzyError::InvalidWeight(w) => write!(f, "rule weight {} is not in [0, 1]", w),
+            FuzzyError::UnknownRule(i) => write!(f, "there're no rule {}", i),
+            FuzzyError::Incomplete(var, found, needed) => write!(
+                f,
+                "variable \"{}\" is only {}-complete, needs {}",
+                var, found, needed
+            ),
+            FuzzyError::Unordered(var) => {
+                write!(
+                    f,
+                    "terms of variable \"{}\" are not in increasing order",
+                    var
+                )
+            }
+            FuzzyError::Unsupported(what, format) => {
+                write!(f, "{} can't be written as {}", what, format)
+            }
+            FuzzyError::Parse(e) => write!(f, "{}", e),
+            FuzzyError::Io(e) => write!(f, "{}", e),
+            FuzzyError::Csv(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for FuzzyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FuzzyError::Parse(e) => Some(e),
+            FuzzyError::Io(e) => Some(e),
+            FuzzyError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for FuzzyError {
+    fn from(e: ParseError) -> Self {
+        FuzzyError::Parse(e)
+    }
+}
+
+impl From<io::Error> for FuzzyError {
+    fn from(e: io::Error) -> Self {
+        FuzzyError::Io(e)
+    }
+}
+
+impl From<csv::Error> for FuzzyError {
+    fn from(e: csv::Error) -> Self {
+        FuzzyError::Csv(e)
+    }
+}
+
+/// unwrap for the panicking wrappers around `try_*` functions
+pub(crate) fn or_panic<T>(res: Result<T, FuzzyError>) -> T {
+    match res {
+        Ok(v) => v,
+        Err(e) => panic!("{}", e),
+    }
+}
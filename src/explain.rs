@@ -0,0 +1,226 @@
+// step by step trace of a mamdani inference, for auditing a single decision
+
+use crate::error::{or_panic, FuzzyError};
+use crate::parse::{format_rule, label, Terms};
+use crate::rule::{DynFuzzyEngine, FuzzyEngine};
+use crate::set::*;
+use std::fmt;
+
+/// membership of a crisp input in every term of its variable
+#[derive(Debug, Clone)]
+pub struct InputTrace {
+    pub name: String,
+    pub value: f64,
+    pub degrees: Vec<(String, f64)>, // (term, degree)
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleTrace {
+    pub text: String,                      // the rule as `IF ... THEN ...`
+    pub strength: f64,                     // firing strength, weight included
+    pub consequent: Vec<Option<FuzzySet>>, // implicated term for each output the rule sets
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputTrace {
+    pub name: String,
+    pub aggregated: FuzzySet,
+    pub crisp: f64,
+}
+
+/// everything `calculate` and `defuzzify` compute for one input vector
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub inputs: Vec<InputTrace>,
+    pub rules: Vec<RuleTrace>, // in the order rules were added
+    pub outputs: Vec<OutputTrace>,
+}
+
+impl Explanation {
+    /// rules sorted by firing strength, strongest first, skipping the ones that didn't fire
+    pub fn fired(&self) -> Vec<&RuleTrace> {
+        let mut fired: Vec<&RuleTrace> = self.rules.iter().filter(|r| r.strength > 0.0).collect();
+        fired.sort_by(|a, b| b.strength.total_cmp(&a.strength));
+        fired
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "inputs")?;
+        for input in self.inputs.iter() {
+            let degrees: Vec<String> = input
+                .degrees
+                .iter()
+                .map(|(term, d)| format!("{} {:.3}", term, d))
+                .collect();
+            writeln!(
+                f,
+                "  {} = {}: {}",
+                input.name,
+                input.value,
+                degrees.join(", ")
+            )?;
+        }
+
+        writeln!(f, "rules fired")?;
+        let fired = self.fired();
+        if fired.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for rule in fired {
+            writeln!(f, "  {:.3}  {}", rule.strength, rule.text)?;
+        }
+
+        writeln!(f, "outputs")?;
+        for output in self.outputs.iter() {
+            writeln!(
+                f,
+                "  {} = {} (height {:.3})",
+                output.name,
+                output.crisp,
+                output.aggregated.height()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// variable name used in the report, see `parse::label`
+pub(crate) struct Label(pub(crate) String);
+
+impl Terms for Label {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    fn term_index(&self, _: &str) -> Option<usize> {
+        None // only used to print rules that were already checked
+    }
+
+    fn has_term(&self, _: &str) -> bool {
+        true
+    }
+}
+
+pub(crate) fn labels(vars: &[LinguisticVar], prefix: &str) -> Vec<Label> {
+    vars.iter()
+        .enumerate()
+        .map(|(i, v)| Label(label(v, i, prefix)))
+        .collect()
+}
+
+impl DynFuzzyEngine {
+    /// trace of how `defuzzify` reaches its result for these inputs
+    pub fn explain(&self, inputs: &[f64]) -> Explanation {
+        or_panic(self.try_explain(inputs))
+    }
+
+    pub fn try_explain(&self, inputs: &[f64]) -> Result<Explanation, FuzzyError> {
+        let crisp = self.try_defuzzify(inputs)?;
+        let aggregated = self.try_calculate(inputs)?;
+        let ops = self.operators();
+        let input_labels = labels(self.inputs(), "input");
+        let output_labels = labels(self.outputs(), "output");
+
+        let input_traces = self
+            .inputs()
+            .iter()
+            .zip(inputs.iter())
+            .zip(input_labels.iter())
+            .map(|((var, x), label)| InputTrace {
+                name: label.0.clone(),
+                value: *x,
+                degrees: var
+                    .sets
+                    .iter()
+                    .map(|set| (set.name.clone(), set.degree_of(*x)))
+                    .collect(),
+            })
+            .collect();
+
+        let rule_traces = self
+            .rules()
+            .iter()
+            .enumerate()
+            .map(|(j, rule)| {
+                let strength = rule.weight * rule.antecedent.eval(self.inputs(), inputs, &ops);
+                let consequent = rule
+                    .consequent
+                    .iter()
+                    .enumerate()
+                    .map(|(i, term)| {
+                        let set = self.outputs()[i].term(term.as_ref()?);
+                        Some(set.implicate(strength, ops.implication, format!("f{}", j)))
+                    })
+                    .collect();
+                RuleTrace {
+                    text: format_rule(rule, &input_labels, &output_labels),
+                    strength,
+                    consequent,
+                }
+            })
+            .collect();
+
+        let output_traces = aggregated
+            .into_iter()
+            .zip(crisp)
+            .zip(output_labels)
+            .map(|((aggregated, crisp), label)| OutputTrace {
+                name: label.0,
+                aggregated,
+                crisp,
+            })
+            .collect();
+
+        Ok(Explanation {
+            inputs: input_traces,
+            rules: rule_traces,
+            outputs: output_traces,
+        })
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// trace of how `defuzzify` reaches its result for these inputs
+    pub fn explain(&self, inputs: [f64; N]) -> Explanation {
+        self.as_dyn().explain(&inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn report() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let mut engine = FuzzyEngine::new([rsi.clone().named("rsi")], [rsi]);
+        engine.add_rule(["low"], ["high"]);
+        engine.add_rule_weighted(["high"], ["low"], 0.5);
+
+        let e = engine.explain([25.0]);
+        assert_eq!(
+            e.inputs[0].degrees,
+            vec![("low".into(), 0.5), ("high".into(), 0.0)]
+        );
+        assert_eq!(e.rules[0].strength, 0.5);
+        assert_eq!(e.rules[1].strength, 0.0);
+        assert_eq!(e.rules[0].text, "IF rsi IS low THEN output0 IS high");
+        assert_eq!(e.rules[0].consequent[0].as_ref().unwrap().height(), 0.5);
+        assert_eq!(e.outputs[0].crisp, engine.defuzzify([25.0])[0]);
+        assert_eq!(e.fired().len(), 1);
+
+        let report = e.to_string();
+        assert!(report.contains("rsi = 25: low 0.500, high 0.000"));
+        assert!(report.contains("0.500  IF rsi IS low THEN output0 IS high"));
+        assert!(!report.contains("WITH 0.5"));
+    }
+}
@@ -0,0 +1,816 @@
+// Fuzzy Control Language (IEC 61131-7) import and export.
+// terms can be points `(x, y) ...` or the jFuzzyLogic functions
+// `trian`, `trape`, `gauss`, `gbell` and `sigm`. singleton terms (COGS) aren't supported.
+// export is lossy for other shapes, which are written as at most ~100 sampled points
+
+use crate::defuzz::Defuzzifier;
+use crate::error::FuzzyError;
+use crate::ops::*;
+use crate::parse::{format_rule, label, parse_rule, ParseError};
+use crate::rule::{DynFuzzyEngine, FuzzyEngine, Rule};
+use crate::set::*;
+use crate::shape::*;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+pub struct Fcl {
+    pub name: String,
+    pub inputs: Vec<LinguisticVar>,
+    pub outputs: Vec<LinguisticVar>,
+    pub rules: Vec<Rule>,
+    pub ops: Operators,
+    pub defuzzifier: Defuzzifier,
+}
+
+impl Fcl {
+    pub fn parse(text: &str) -> Result<Fcl, ParseError> {
+        let tokens = tokenize(text);
+        let mut p = Reader {
+            tokens: &tokens,
+            pos: 0,
+        };
+        p.parse().map_err(|e| match e {
+            ParseError::Line(..) => e,
+            _ => ParseError::Line(p.line(), Box::new(e)),
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Fcl, Box<dyn Error>> {
+        Ok(Fcl::parse(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// variables are written with their `name`, unnamed ones become input{i}/output{i}.
+    /// fails for the WeightedAverage and WeightedCentroid defuzzifiers, which FCL has no method for.
+    /// terms other than points, gaussian, bell and sigmoid are only approximated, see `term_def`
+    pub fn from_engine<const N: usize, const M: usize>(
+        name: &str,
+        engine: &FuzzyEngine<N, M>,
+    ) -> Result<Fcl, FuzzyError> {
+        Fcl::from_dyn_engine(name, engine.as_dyn())
+    }
+
+    pub fn from_dyn_engine(name: &str, engine: &DynFuzzyEngine) -> Result<Fcl, FuzzyError> {
+        if let Defuzzifier::WeightedAverage | Defuzzifier::WeightedCentroid = engine.defuzzifier() {
+            return Err(FuzzyError::Unsupported(
+                format!("defuzzifier {:?}", engine.defuzzifier()),
+                "FCL".into(),
+            ));
+        }
+        let named = |vars: &[LinguisticVar], prefix: &str| -> Vec<LinguisticVar> {
+            vars.iter()
+                .enumerate()
+                .map(|(i, v)| v.clone().named(&label(v, i, prefix)))
+                .collect()
+        };
+        Ok(Fcl {
+            name: name.to_string(),
+            inputs: named(engine.inputs(), "input"),
+            outputs: named(engine.outputs(), "output"),
+            rules: engine.rules().to_vec(),
+            ops: engine.operators(),
+            defuzzifier: engine.defuzzifier(),
+        })
+    }
+
+    pub fn into_engine<const N: usize, const M: usize>(
+        self,
+    ) -> Result<FuzzyEngine<N, M>, ParseError> {
+        if self.inputs.len() != N || self.outputs.len() != M {
+            let found = format!(
+                "{} inputs and {} outputs",
+                self.inputs.len(),
+                self.outputs.len()
+            );
+            let expected = format!("{} inputs and {} outputs", N, M);
+            return Err(ParseError::Unexpected(found, expected));
+        }
+        Ok(FuzzyEngine::from_dyn(self.into_dyn_engine()).unwrap())
+    }
+
+    pub fn into_dyn_engine(self) -> DynFuzzyEngine {
+        let mut engine = DynFuzzyEngine::new(self.inputs, self.outputs);
+        engine.set_operators(self.ops);
+        engine.set_defuzzifier(self.defuzzifier);
+        for rule in self.rules {
+            engine.push_rule(rule);
+        }
+        engine
+    }
+}
+
+impl fmt::Display for Fcl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "FUNCTION_BLOCK {}\n", self.name)?;
+        for (block, vars) in [("VAR_INPUT", &self.inputs), ("VAR_OUTPUT", &self.outputs)] {
+            writeln!(f, "{}", block)?;
+            for var in vars.iter() {
+                writeln!(f, "    {} : REAL;", var.name)?;
+            }
+            writeln!(f, "END_VAR\n")?;
+        }
+
+        for (block, vars) in [("FUZZIFY", &self.inputs), ("DEFUZZIFY", &self.outputs)] {
+            for var in vars.iter() {
+                writeln!(f, "{} {}", block, var.name)?;
+                for set in var.sets.iter() {
+                    writeln!(f, "    TERM {} := {};", set.name, term_def(set))?;
+                }
+                if block == "DEFUZZIFY" {
+                    writeln!(f, "    METHOD : {};", method_name(self.defuzzifier))?;
+                    writeln!(f, "    DEFAULT := 0;")?;
+                }
+                let (lo, hi) = (var.universe[0], var.universe[var.universe.len() - 1]);
+                writeln!(f, "    RANGE := ({} .. {});", lo, hi)?;
+                writeln!(f, "END_{}\n", block)?;
+            }
+        }
+
+        writeln!(f, "RULEBLOCK No1")?;
+        writeln!(f, "    AND : {};", tnorm_name(self.ops.and))?;
+        writeln!(f, "    OR : {};", snorm_name(self.ops.or))?;
+        let act = match self.ops.implication {
+            Implication::Min => "MIN",
+            Implication::Product => "PROD",
+        };
+        writeln!(f, "    ACT : {};", act)?;
+        writeln!(f, "    ACCU : {};", snorm_name(self.ops.aggregation))?;
+        for (i, rule) in self.rules.iter().enumerate() {
+            let text = format_rule(rule, &self.inputs, &self.outputs);
+            writeln!(f, "    RULE {} : {};", i + 1, text)?;
+        }
+        writeln!(f, "END_RULEBLOCK\n")?;
+        writeln!(f, "END_FUNCTION_BLOCK")
+    }
+}
+
+/// FCL text of a term. triangles, trapezoids and points are exact, gaussian, bell and sigmoid
+/// use the jFuzzyLogic functions, anything else is resampled by `breakpoints`
+fn term_def(set: &FuzzySet) -> String {
+    let fmt_points = |points: &[(f64, f64)]| {
+        points
+            .iter()
+            .map(|(x, y)| format!("({}, {})", x, y))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let shape = match &set.shape {
+        Some(shape) => shape,
+        None => return fmt_points(&breakpoints(set)),
+    };
+    let p = shape.params();
+    match shape.kind() {
+        "triangular" => fmt_points(&[(p[0] - p[2], 0.0), (p[0], p[1]), (p[0] + p[2], 0.0)]),
+        "trapezoidal" => fmt_points(&[(p[0], 0.0), (p[1], p[4]), (p[2], p[4]), (p[3], 0.0)]),
+        "points" => fmt_points(&p.chunks(2).map(|c| (c[0], c[1])).collect::<Vec<_>>()),
+        "gaussian" => format!("gauss {} {}", p[0], p[1]),
+        "bell" => format!("gbell {} {} {}", p[0], p[1], p[2]),
+        "sigmoid" => format!("sigm {} {}", p[0], p[1]),
+        _ => fmt_points(&breakpoints(set)),
+    }
+}
+
+/// sampled points where the slope of the set changes. curves with more than 100 of those
+/// are evenly resampled to 100 points, so their FCL copy is only an approximation
+fn breakpoints(set: &FuzzySet) -> Vec<(f64, f64)> {
+    let (x, m) = (&set.universe, &set.membership);
+    let mut points: Vec<(f64, f64)> = vec![(x[0], m[0])];
+    for i in 1..x.len() - 1 {
+        let slope = (m[i] - m[i - 1]) / (x[i] - x[i - 1]);
+        let next = (m[i + 1] - m[i]) / (x[i + 1] - x[i]);
+        if (slope - next).abs() > 1e-9 {
+            points.push((x[i], m[i]));
+        }
+    }
+    points.push((x[x.len() - 1], m[m.len() - 1]));
+
+    if points.len() > 100 {
+        let step = (x.len() - 1) as f64 / 99.0;
+        points = (0..100)
+            .map(|i| {
+                let j = ((i as f64) * step).round() as usize;
+                (x[j], m[j])
+            })
+            .collect();
+    }
+    points
+}
+
+fn method_name(d: Defuzzifier) -> &'static str {
+    match d {
+        Defuzzifier::Centroid => "COG",
+        Defuzzifier::Bisector => "COA",
+        Defuzzifier::MeanOfMax => "MM",
+        Defuzzifier::SmallestOfMax => "LM",
+        Defuzzifier::LargestOfMax => "RM",
+        // no FCL equivalent, `from_engine` refuses them but a hand built Fcl is written as COG
+        Defuzzifier::WeightedAverage | Defuzzifier::WeightedCentroid => "COG",
+    }
+}
+
+fn tnorm_name(t: TNorm) -> String {
+    match t {
+        TNorm::Min => "MIN".into(),
+        TNorm::Product => "PROD".into(),
+        TNorm::Lukasiewicz => "BDIF".into(),
+        TNorm::Drastic => "DPROD".into(),
+        TNorm::Einstein => "EPROD".into(),
+        TNorm::Hamacher => "HPROD".into(),
+        TNorm::Yager(p) => format!("YAGER {}", p),
+    }
+}
+
+fn snorm_name(s: SNorm) -> String {
+    match s {
+        SNorm::Max => "MAX".into(),
+        SNorm::ProbSum => "ASUM".into(),
+        SNorm::BoundedSum => "BSUM".into(),
+        SNorm::Drastic => "DSUM".into(),
+        SNorm::Einstein => "ESUM".into(),
+        SNorm::Hamacher => "HSUM".into(),
+        SNorm::Yager(p) => format!("YAGER {}", p),
+    }
+}
+
+/// tokens with their line number, comments (`//`, `(* *)`) removed
+fn tokenize(text: &str) -> Vec<(String, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens: Vec<(String, usize)> = vec![];
+    let mut word = String::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let two: String = [Some(c), next].iter().flatten().collect();
+        let separator = c.is_whitespace() || "(),;:".contains(c) || two == "//" || two == "..";
+        if separator && !word.is_empty() {
+            tokens.push((word.clone(), line));
+            word.clear();
+        }
+
+        if two == "//" {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        } else if two == "(*" {
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&')')) {
+                line += (chars[i] == '\n') as usize;
+                i += 1;
+            }
+            i += 2;
+            continue;
+        } else if two == ":=" || two == ".." {
+            tokens.push((two, line));
+            i += 2;
+            continue;
+        }
+
+        if c == '\n' {
+            line += 1;
+        } else if "(),;:".contains(c) {
+            tokens.push((c.to_string(), line));
+        } else if !c.is_whitespace() {
+            word.push(c);
+        }
+        i += 1;
+    }
+    if !word.is_empty() {
+        tokens.push((word, line));
+    }
+    tokens
+}
+
+// variable being read from a FUZZIFY or DEFUZZIFY block
+struct VarDef {
+    name: String,
+    terms: Vec<(String, Box<dyn Shape>)>,
+    range: Option<(f64, f64)>,
+    hint: Vec<f64>, // x positions used for the universe when there's no RANGE
+}
+
+struct Reader<'a> {
+    tokens: &'a [(String, usize)],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn line(&self) -> usize {
+        match self.tokens.get(self.pos.min(self.tokens.len().max(1) - 1)) {
+            Some(t) => t.1,
+            None => 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|x| x.0.as_str())
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'a str, ParseError> {
+        match self.peek() {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t)
+            }
+            None => Err(ParseError::Unexpected(
+                "end of file".into(),
+                expected.into(),
+            )),
+        }
+    }
+
+    fn eat(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(t) if t.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(), ParseError> {
+        let t = self.next(keyword)?;
+        if !t.eq_ignore_ascii_case(keyword) {
+            return Err(ParseError::Unexpected(t.into(), keyword.into()));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<f64, ParseError> {
+        let t = self.next("a number")?;
+        t.parse::<f64>()
+            .map_err(|_| ParseError::Unexpected(t.into(), "a number".into()))
+    }
+
+    fn yager(&mut self) -> Result<f64, ParseError> {
+        let p = self.number()?;
+        if p.is_nan() || p <= 0.0 {
+            return Err(ParseError::Unexpected(
+                p.to_string(),
+                "a yager p > 0".into(),
+            ));
+        }
+        Ok(p)
+    }
+
+    fn parse(&mut self) -> Result<Fcl, ParseError> {
+        self.expect("FUNCTION_BLOCK")?;
+        let mut name = String::new();
+        if let Some(t) = self.peek() {
+            if !t.to_uppercase().starts_with("VAR_")
+                && !t.eq_ignore_ascii_case("END_FUNCTION_BLOCK")
+            {
+                name = t.to_string();
+                self.pos += 1;
+            }
+        }
+
+        let mut input_names: Vec<String> = vec![];
+        let mut output_names: Vec<String> = vec![];
+        let mut defs: Vec<VarDef> = vec![];
+        let mut rules: Vec<(String, usize)> = vec![];
+        let mut and: Option<TNorm> = None;
+        let mut or: Option<SNorm> = None;
+        let mut act: Option<Implication> = None;
+        let mut accu: Option<SNorm> = None;
+        let mut method: Option<Defuzzifier> = None;
+
+        loop {
+            let t = self.next("a block")?.to_uppercase();
+            match t.as_str() {
+                "END_FUNCTION_BLOCK" => break,
+                "VAR_INPUT" | "VAR_OUTPUT" => {
+                    while !self.eat("END_VAR") {
+                        let var = self.next("a variable")?.to_string();
+                        // skip the type and anything else up to ;
+                        while self.next(";")? != ";" {}
+                        match t.as_str() {
+                            "VAR_INPUT" => input_names.push(var),
+                            _ => output_names.push(var),
+                        }
+                    }
+                }
+                "FUZZIFY" | "DEFUZZIFY" => {
+                    let end = format!("END_{}", t);
+                    let mut def = VarDef {
+                        name: self.next("a variable")?.to_string(),
+                        terms: vec![],
+                        range: None,
+                        hint: vec![],
+                    };
+                    while !self.eat(&end) {
+                        let t = self.next(&end)?.to_uppercase();
+                        match t.as_str() {
+                            "TERM" => {
+                                let term = self.next("a term")?.to_string();
+                                self.expect(":=")?;
+                                let shape = self.term_shape(&mut def.hint)?;
+                                self.expect(";")?;
+                                def.terms.push((term, shape));
+                            }
+                            "RANGE" => {
+                                self.expect(":=")?;
+                                self.expect("(")?;
+                                let lo = self.number()?;
+                                self.expect("..")?;
+                                let hi = self.number()?;
+                                self.expect(")")?;
+                                self.expect(";")?;
+                                def.range = Some((lo, hi));
+                            }
+                            "METHOD" => {
+                                self.expect(":")?;
+                                let m = self.next("a method")?;
+                                let d = match m.to_uppercase().as_str() {
+                                    "COG" => Defuzzifier::Centroid,
+                                    "COA" => Defuzzifier::Bisector,
+                                    "MM" => Defuzzifier::MeanOfMax,
+                                    "LM" => Defuzzifier::SmallestOfMax,
+                                    "RM" => Defuzzifier::LargestOfMax,
+                                    _ => {
+                                        return Err(ParseError::Unexpected(
+                                            m.into(),
+                                            "COG, COA, MM, LM or RM".into(),
+                                        ))
+                                    }
+                                };
+                                set_once(&mut method, d, m)?;
+                                self.expect(";")?;
+                            }
+                            // no equivalent in the engine, when no rule fires the output is 0
+                            "DEFAULT" | "LOCK" => while self.next(";")? != ";" {},
+                            _ => {
+                                return Err(ParseError::Unexpected(
+                                    t,
+                                    "TERM, RANGE or METHOD".into(),
+                                ))
+                            }
+                        }
+                    }
+                    defs.push(def);
+                }
+                "RULEBLOCK" => {
+                    self.next("a rule block name")?;
+                    while !self.eat("END_RULEBLOCK") {
+                        let t = self.next("END_RULEBLOCK")?.to_uppercase();
+                        match t.as_str() {
+                            "AND" => {
+                                self.expect(":")?;
+                                let n = self.tnorm()?;
+                                set_once(&mut and, n, &t)?;
+                            }
+                            "OR" => {
+                                self.expect(":")?;
+                                let n = self.snorm()?;
+                                set_once(&mut or, n, &t)?;
+                            }
+                            "ACT" => {
+                                self.expect(":")?;
+                                let m = self.next("MIN or PROD")?;
+                                let i = match m.to_uppercase().as_str() {
+                                    "MIN" => Implication::Min,
+                                    "PROD" => Implication::Product,
+                                    _ => {
+                                        return Err(ParseError::Unexpected(
+                                            m.into(),
+                                            "MIN or PROD".into(),
+                                        ))
+                                    }
+                                };
+                                set_once(&mut act, i, m)?;
+                            }
+                            "ACCU" | "ACCUM" => {
+                                self.expect(":")?;
+                                let n = self.snorm()?;
+                                set_once(&mut accu, n, &t)?;
+                            }
+                            "RULE" => {
+                                let line = self.line();
+                                // rule number or name
+                                self.next("a rule number")?;
+                                self.expect(":")?;
+                                let mut text: Vec<&str> = vec![];
+                                loop {
+                                    let t = self.next(";")?;
+                                    if t == ";" {
+                                        break;
+                                    }
+                                    text.push(t);
+                                }
+                                rules.push((text.join(" "), line));
+                                continue;
+                            }
+                            _ => return Err(ParseError::Unexpected(t, "RULE".into())),
+                        }
+                        self.expect(";")?;
+                    }
+                }
+                _ => return Err(ParseError::Unexpected(t, "a block".into())),
+            }
+        }
+
+        let mut take = |names: &Vec<String>| -> Result<Vec<LinguisticVar>, ParseError> {
+            names
+                .iter()
+                .map(|name| match defs.iter().position(|d| d.name == *name) {
+                    Some(i) => build_var(defs.remove(i)),
+                    None => Err(ParseError::UnknownVariable(name.clone())),
+                })
+                .collect()
+        };
+        let inputs = take(&input_names)?;
+        let outputs = take(&output_names)?;
+        if let Some(d) = defs.first() {
+            return Err(ParseError::UnknownVariable(d.name.clone()));
+        }
+
+        let mut parsed: Vec<Rule> = vec![];
+        for (text, line) in rules {
+            match parse_rule(&format!("{};", text), &inputs, &outputs) {
+                Ok(rule) => parsed.push(rule),
+                Err(e) => return Err(ParseError::Line(line, Box::new(e))),
+            }
+        }
+
+        let default = Operators::default();
+        Ok(Fcl {
+            name,
+            inputs,
+            outputs,
+            rules: parsed,
+            ops: Operators {
+                and: and.unwrap_or(default.and),
+                or: or.unwrap_or(default.or),
+                implication: act.unwrap_or(default.implication),
+                aggregation: accu.unwrap_or(default.aggregation),
+            },
+            defuzzifier: method.unwrap_or(Defuzzifier::Centroid),
+        })
+    }
+
+    fn term_shape(&mut self, hint: &mut Vec<f64>) -> Result<Box<dyn Shape>, ParseError> {
+        let t = self.next("a term definition")?;
+        let mut args =
+            |n: usize| -> Result<Vec<f64>, ParseError> { (0..n).map(|_| self.number()).collect() };
+        match t.to_lowercase().as_str() {
+            "(" => {
+                self.pos -= 1;
+                let mut p: Vec<(f64, f64)> = vec![];
+                while self.eat("(") {
+                    let x = self.number()?;
+                    self.expect(",")?;
+                    let y = self.number()?;
+                    self.expect(")")?;
+                    hint.push(x);
+                    p.push((x, y));
+                }
+                Ok(Box::new(points(p)))
+            }
+            "trian" => {
+                let p = args(3)?;
+                hint.extend([p[0], p[2]]);
+                // a trapezoid keeps shoulder triangles like `trian 0 0 5` at 1 on their peak
+                Ok(Box::new(trapezoidal(p[0], p[1], p[1], p[2], 1.0)))
+            }
+            "trape" => {
+                let p = args(4)?;
+                hint.extend([p[0], p[3]]);
+                Ok(Box::new(trapezoidal(p[0], p[1], p[2], p[3], 1.0)))
+            }
+            "gauss" => {
+                let p = args(2)?;
+                Ok(Box::new(gaussian(p[0], p[1])))
+            }
+            "gbell" => {
+                let p = args(3)?;
+                Ok(Box::new(bell(p[0], p[1], p[2])))
+            }
+            "sigm" => {
+                let p = args(2)?;
+                Ok(Box::new(sigmoid(p[0], p[1])))
+            }
+            _ => Err(ParseError::Unexpected(
+                t.into(),
+                "points, trian, trape, gauss, gbell or sigm".into(),
+            )),
+        }
+    }
+
+    fn tnorm(&mut self) -> Result<TNorm, ParseError> {
+        let t = self.next("an AND operator")?;
+        Ok(match t.to_uppercase().as_str() {
+            "MIN" => TNorm::Min,
+            "PROD" => TNorm::Product,
+            "BDIF" => TNorm::Lukasiewicz,
+            "DPROD" => TNorm::Drastic,
+            "EPROD" => TNorm::Einstein,
+            "HPROD" => TNorm::Hamacher,
+            "YAGER" => TNorm::Yager(self.yager()?),
+            _ => {
+                return Err(ParseError::Unexpected(
+                    t.into(),
+                    "MIN, PROD, BDIF, DPROD, EPROD, HPROD or YAGER <p>".into(),
+                ))
+            }
+        })
+    }
+
+    fn snorm(&mut self) -> Result<SNorm, ParseError> {
+        let t = self.next("an OR operator")?;
+        Ok(match t.to_uppercase().as_str() {
+            "MAX" => SNorm::Max,
+            "ASUM" | "PROBOR" => SNorm::ProbSum,
+            "BSUM" => SNorm::BoundedSum,
+            "DSUM" => SNorm::Drastic,
+            "ESUM" => SNorm::Einstein,
+            "HSUM" => SNorm::Hamacher,
+            "YAGER" => SNorm::Yager(self.yager()?),
+            _ => {
+                return Err(ParseError::Unexpected(
+                    t.into(),
+                    "MAX, ASUM, PROBOR, BSUM, DSUM, ESUM, HSUM or YAGER <p>".into(),
+                ))
+            }
+        })
+    }
+}
+
+/// operators can be given by more than one block as long as they agree
+fn set_once<T: PartialEq>(slot: &mut Option<T>, value: T, name: &str) -> Result<(), ParseError> {
+    match slot {
+        Some(v) if *v != value => Err(ParseError::Unexpected(
+            name.into(),
+            "the same setting as the previous block".into(),
+        )),
+        _ => {
+            *slot = Some(value);
+            Ok(())
+        }
+    }
+}
+
+fn build_var(def: VarDef) -> Result<LinguisticVar, ParseError> {
+    let (lo, hi) = match def.range {
+        Some(r) => r,
+        None if !def.hint.is_empty() => (
+            def.hint.iter().fold(f64::MAX, |m, x| x.min(m)),
+            def.hint.iter().fold(f64::MIN, |m, x| x.max(m)),
+        ),
+        None => {
+            return Err(ParseError::Unexpected(
+                def.name,
+                "a RANGE for this variable".into(),
+            ))
+        }
+    };
+    if def.terms.is_empty() || hi <= lo {
+        return Err(ParseError::Unexpected(
+            def.name,
+            "terms and a valid range".into(),
+        ));
+    }
+
+    let terms: Vec<(&dyn Shape, &str)> = def
+        .terms
+        .iter()
+        .map(|(name, shape)| (shape.as_ref(), name.as_str()))
+        .collect();
+    Ok(LinguisticVar::new(terms, default_universe(lo, hi)).named(&def.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIPPER: &str = "
+        FUNCTION_BLOCK tipper   // comment
+
+        VAR_INPUT
+            service : REAL;
+            food : REAL;
+        END_VAR
+
+        VAR_OUTPUT
+            tip : REAL;
+        END_VAR
+
+        FUZZIFY service
+            TERM poor := gauss 0 1.5;
+            TERM good := gauss 5 1.5;
+            TERM excellent := gauss 10 1.5;
+            RANGE := (0 .. 10);
+        END_FUZZIFY
+
+        FUZZIFY food
+            TERM rancid := (0, 1) (1, 1) (3,0);
+            TERM delicious := (7,0) (9,1) (10,1);
+        END_FUZZIFY
+
+        (* output *)
+        DEFUZZIFY tip
+            TERM cheap := (0,0) (5,1) (10,0);
+            TERM average := trian 10 15 20;
+            TERM generous := (20,0) (25,1) (30,0);
+            METHOD : COG;
+            DEFAULT := 0;
+            RANGE := (0 .. 30);
+        END_DEFUZZIFY
+
+        RULEBLOCK No1
+            AND : MIN;
+            ACT : MIN;
+            ACCU : MAX;
+
+            RULE 1 : IF service IS poor OR food IS rancid THEN tip IS cheap;
+            RULE 2 : IF service IS good THEN tip IS average;
+            RULE 3 : IF service IS excellent AND food IS delicious THEN tip IS generous WITH 0.5;
+        END_RULEBLOCK
+
+        END_FUNCTION_BLOCK
+    ";
+
+    #[test]
+    fn load_and_save() {
+        let fcl = Fcl::parse(TIPPER).unwrap();
+        assert_eq!(fcl.name, "tipper");
+        assert_eq!(fcl.inputs[1].universe[0], 0.0);
+        assert_eq!(
+            fcl.inputs[1].universe[fcl.inputs[1].universe.len() - 1],
+            10.0
+        );
+        assert_eq!(fcl.rules[2].weight, 0.5);
+
+        let engine = fcl.into_engine::<2, 1>().unwrap();
+        let tip = engine.defuzzify([0.0, 0.0])[0];
+        assert!((tip - 5.0).abs() < 0.1);
+
+        let dynamic = Fcl::parse(TIPPER).unwrap().into_dyn_engine();
+        let named = dynamic.defuzzify_named(&[("food", 0.0), ("service", 0.0)]);
+        assert_eq!(named[0], tip);
+
+        let text = Fcl::from_engine("tipper", &engine).unwrap().to_string();
+        let copy = Fcl::parse(&text).unwrap().into_engine::<2, 1>().unwrap();
+        for inputs in [[0.0, 0.0], [3.0, 8.0], [9.0, 9.5]] {
+            let a = engine.defuzzify(inputs)[0];
+            let b = copy.defuzzify(inputs)[0];
+            assert!((a - b).abs() < 1e-6, "{} {}", a, b);
+        }
+    }
+
+    #[test]
+    fn shoulders() {
+        let text = TIPPER
+            .replace("(0,0) (5,1) (10,0)", "trian 0 0 10")
+            .replace("(20,0) (25,1) (30,0)", "(20,0) (30,1) (30,0)");
+        let engine = Fcl::parse(&text).unwrap().into_engine::<2, 1>().unwrap();
+        let text = Fcl::from_engine("tipper", &engine).unwrap().to_string();
+        let copy = Fcl::parse(&text).unwrap().into_engine::<2, 1>().unwrap();
+        for engine in [engine, copy] {
+            let tip = &engine.outputs()[0];
+            assert_eq!(tip.term("cheap").degree_of(0.0), 1.0);
+            assert_eq!(tip.term("generous").degree_of(30.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let e = Fcl::parse(&TIPPER.replace("food IS rancid", "food IS old"));
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "line 40: variable \"food\" has no term \"old\""
+        );
+        let e = Fcl::parse(&TIPPER.replace("gauss 5 1.5", "circle 5"));
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "line 15: expected points, trian, trape, gauss, gbell or sigm but found \"circle\""
+        );
+        assert!(Fcl::parse(TIPPER).unwrap().into_engine::<1, 1>().is_err());
+        let e = Fcl::parse(&TIPPER.replace("AND : MIN", "AND : TMIN"));
+        assert!(e
+            .err()
+            .unwrap()
+            .to_string()
+            .ends_with("MIN, PROD, BDIF, DPROD, EPROD, HPROD or YAGER <p> but found \"TMIN\""));
+
+        let e = Fcl::parse(&TIPPER.replace("AND : MIN", "AND : YAGER 0"));
+        assert!(e
+            .err()
+            .unwrap()
+            .to_string()
+            .ends_with("expected a yager p > 0 but found \"0\""));
+
+        let mut dynamic = Fcl::parse(TIPPER).unwrap().into_dyn_engine();
+        dynamic.set_defuzzifier(Defuzzifier::WeightedCentroid);
+        assert!(matches!(
+            Fcl::from_dyn_engine("tipper", &dynamic),
+            Err(FuzzyError::Unsupported(..))
+        ));
+    }
+}
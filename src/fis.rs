@@ -0,0 +1,952 @@
+// MATLAB Fuzzy Logic Toolbox .fis import and export, for both mamdani and sugeno systems.
+// membership functions map onto shape.rs: trimf, trapmf, gaussmf, gauss2mf, gbellmf,
+// sigmf, dsigmf, psigmf, smf, zmf and pimf
+
+use crate::antecedent::Antecedent;
+use crate::defuzz::Defuzzifier;
+use crate::error::FuzzyError;
+use crate::ops::*;
+use crate::parse::{label, ParseError};
+use crate::rule::{DynFuzzyEngine, FuzzyEngine, Rule};
+use crate::set::*;
+use crate::shape::*;
+use crate::sugeno::{Consequent, DynSugenoEngine, SugenoEngine, SugenoMethod, SugenoVar};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+pub enum FisOutputs {
+    Mamdani(Vec<LinguisticVar>),
+    Sugeno(Vec<SugenoVar>),
+}
+
+/// only built by `parse` and the `from_*` functions, which check that Display can write all of it
+pub struct Fis {
+    name: String,
+    inputs: Vec<LinguisticVar>,
+    outputs: FisOutputs,
+    rules: Vec<Rule>,
+    ops: Operators,
+    defuzzifier: Defuzzifier, // unused by sugeno systems
+    method: SugenoMethod,     // only used by sugeno systems
+}
+
+impl Fis {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inputs(&self) -> &[LinguisticVar] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &FisOutputs {
+        &self.outputs
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn operators(&self) -> Operators {
+        self.ops
+    }
+
+    pub fn defuzzifier(&self) -> Defuzzifier {
+        self.defuzzifier
+    }
+
+    pub fn method(&self) -> SugenoMethod {
+        self.method
+    }
+
+    pub fn parse(text: &str) -> Result<Fis, ParseError> {
+        let mut sections: Vec<Section> = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('%') || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                sections.push(Section {
+                    name: line[1..line.len() - 1].to_string(),
+                    line: i + 1,
+                    entries: vec![],
+                });
+                continue;
+            }
+            match sections.last_mut() {
+                Some(s) => s.entries.push((line.to_string(), i + 1)),
+                None => return Err(at(i + 1, unexpected(line, "a [System] section"))),
+            }
+        }
+        read(&sections)
+    }
+
+    pub fn load(path: &str) -> Result<Fis, Box<dyn Error>> {
+        Ok(Fis::parse(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// fails if a term or rule has no .fis equivalent (e.g. hedges, nested conditions)
+    pub fn from_engine<const N: usize, const M: usize>(
+        name: &str,
+        engine: &FuzzyEngine<N, M>,
+    ) -> Result<Fis, FuzzyError> {
+        Fis::from_dyn_engine(name, engine.as_dyn())
+    }
+
+    pub fn from_dyn_engine(name: &str, engine: &DynFuzzyEngine) -> Result<Fis, FuzzyError> {
+        let fis = Fis {
+            name: name.to_string(),
+            inputs: named(engine.inputs(), "input"),
+            outputs: FisOutputs::Mamdani(named(engine.outputs(), "output")),
+            rules: engine.rules().to_vec(),
+            ops: engine.operators(),
+            defuzzifier: engine.defuzzifier(),
+            method: SugenoMethod::default(),
+        };
+        fis.check()?;
+        Ok(fis)
+    }
+
+    pub fn from_sugeno<const N: usize, const M: usize>(
+        name: &str,
+        engine: &SugenoEngine<N, M>,
+    ) -> Result<Fis, FuzzyError> {
+        Fis::from_dyn_sugeno(name, engine.as_dyn())
+    }
+
+    pub fn from_dyn_sugeno(name: &str, engine: &DynSugenoEngine) -> Result<Fis, FuzzyError> {
+        let outputs = engine
+            .outputs()
+            .iter()
+            .enumerate()
+            .map(|(i, v)| v.clone().named(&label(v, i, "output")))
+            .collect();
+        let fis = Fis {
+            name: name.to_string(),
+            inputs: named(engine.inputs(), "input"),
+            outputs: FisOutputs::Sugeno(outputs),
+            rules: engine.rules().to_vec(),
+            ops: engine.operators(),
+            defuzzifier: Defuzzifier::WeightedAverage,
+            method: engine.method(),
+        };
+        fis.check()?;
+        Ok(fis)
+    }
+
+    pub fn into_engine<const N: usize, const M: usize>(
+        self,
+    ) -> Result<FuzzyEngine<N, M>, ParseError> {
+        let engine = self.into_dyn_engine()?;
+        arity::<N, M>(engine.inputs().len(), engine.outputs().len())?;
+        Ok(FuzzyEngine::from_dyn(engine).unwrap())
+    }
+
+    pub fn into_dyn_engine(self) -> Result<DynFuzzyEngine, ParseError> {
+        let outputs = match self.outputs {
+            FisOutputs::Mamdani(v) => v,
+            FisOutputs::Sugeno(_) => return Err(unexpected("sugeno", "a mamdani system")),
+        };
+        let mut engine = DynFuzzyEngine::new(self.inputs, outputs);
+        engine.set_operators(self.ops);
+        engine.set_defuzzifier(self.defuzzifier);
+        for rule in self.rules {
+            engine.push_rule(rule);
+        }
+        Ok(engine)
+    }
+
+    pub fn into_sugeno<const N: usize, const M: usize>(
+        self,
+    ) -> Result<SugenoEngine<N, M>, ParseError> {
+        let engine = self.into_dyn_sugeno()?;
+        arity::<N, M>(engine.inputs().len(), engine.outputs().len())?;
+        Ok(SugenoEngine::from_dyn(engine).unwrap())
+    }
+
+    pub fn into_dyn_sugeno(self) -> Result<DynSugenoEngine, ParseError> {
+        let outputs = match self.outputs {
+            FisOutputs::Sugeno(v) => v,
+            FisOutputs::Mamdani(_) => return Err(unexpected("mamdani", "a sugeno system")),
+        };
+        let mut engine = DynSugenoEngine::new(self.inputs, outputs);
+        engine.set_operators(self.ops);
+        engine.set_method(self.method);
+        for rule in self.rules {
+            engine.push_rule(rule);
+        }
+        Ok(engine)
+    }
+
+    fn output_term_index(&self, i: usize, term: &str) -> usize {
+        match &self.outputs {
+            FisOutputs::Mamdani(v) => v[i].sets.iter().position(|s| s.name == term),
+            FisOutputs::Sugeno(v) => v[i].terms.iter().position(|t| t.0 == term),
+        }
+        .unwrap_or(0)
+    }
+
+    /// everything Display writes has to be expressible in the format
+    fn check(&self) -> Result<(), FuzzyError> {
+        and_name(self.ops.and)?;
+        or_name(self.ops.or)?;
+        if let FisOutputs::Mamdani(outputs) = &self.outputs {
+            agg_name(self.ops.aggregation)?;
+            defuzz_name(self.defuzzifier)?;
+            for var in outputs.iter() {
+                for set in var.sets.iter() {
+                    mf_def(set)?;
+                }
+            }
+        }
+        for var in self.inputs.iter() {
+            for set in var.sets.iter() {
+                mf_def(set)?;
+            }
+        }
+        for rule in self.rules.iter() {
+            rule_literals(rule, &self.inputs)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Fis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, num_outputs) = match &self.outputs {
+            FisOutputs::Mamdani(v) => ("mamdani", v.len()),
+            FisOutputs::Sugeno(v) => ("sugeno", v.len()),
+        };
+        // `check` already made sure these exist
+        let defuzz = match &self.outputs {
+            FisOutputs::Mamdani(_) => defuzz_name(self.defuzzifier).unwrap(),
+            FisOutputs::Sugeno(_) => match self.method {
+                SugenoMethod::WeightedAverage => "wtaver",
+                SugenoMethod::WeightedSum => "wtsum",
+            },
+        };
+        // sugeno outputs aren't aggregated, MATLAB writes sum for them
+        let agg = match &self.outputs {
+            FisOutputs::Mamdani(_) => agg_name(self.ops.aggregation).unwrap(),
+            FisOutputs::Sugeno(_) => "sum",
+        };
+        writeln!(f, "[System]")?;
+        writeln!(f, "Name='{}'", self.name)?;
+        writeln!(f, "Type='{}'", kind)?;
+        writeln!(f, "Version=2.0")?;
+        writeln!(f, "NumInputs={}", self.inputs.len())?;
+        writeln!(f, "NumOutputs={}", num_outputs)?;
+        writeln!(f, "NumRules={}", self.rules.len())?;
+        writeln!(f, "AndMethod='{}'", and_name(self.ops.and).unwrap())?;
+        writeln!(f, "OrMethod='{}'", or_name(self.ops.or).unwrap())?;
+        let imp = match self.ops.implication {
+            Implication::Min => "min",
+            Implication::Product => "prod",
+        };
+        writeln!(f, "ImpMethod='{}'", imp)?;
+        writeln!(f, "AggMethod='{}'", agg)?;
+        writeln!(f, "DefuzzMethod='{}'", defuzz)?;
+
+        for (i, var) in self.inputs.iter().enumerate() {
+            write_var(f, &format!("Input{}", i + 1), var)?;
+        }
+        match &self.outputs {
+            FisOutputs::Mamdani(outputs) => {
+                for (i, var) in outputs.iter().enumerate() {
+                    write_var(f, &format!("Output{}", i + 1), var)?;
+                }
+            }
+            FisOutputs::Sugeno(outputs) => {
+                for (i, var) in outputs.iter().enumerate() {
+                    writeln!(f, "\n[Output{}]", i + 1)?;
+                    writeln!(f, "Name='{}'", var.name)?;
+                    let (lo, hi) = output_range(var, &self.inputs);
+                    writeln!(f, "Range=[{} {}]", lo, hi)?;
+                    writeln!(f, "NumMFs={}", var.terms.len())?;
+                    for (j, (name, c)) in var.terms.iter().enumerate() {
+                        let (kind, p) = match c {
+                            Consequent::Constant(c) => ("constant", vec![*c]),
+                            Consequent::Linear(coef, c) => {
+                                ("linear", coef.iter().chain([c]).copied().collect())
+                            }
+                        };
+                        writeln!(f, "MF{}='{}':'{}',[{}]", j + 1, name, kind, join(&p))?;
+                    }
+                }
+            }
+        }
+
+        writeln!(f, "\n[Rules]")?;
+        for rule in self.rules.iter() {
+            let (ins, connection) = rule_literals(rule, &self.inputs).unwrap();
+            let outs: Vec<String> = rule
+                .consequent
+                .iter()
+                .enumerate()
+                .map(|(i, term)| match term {
+                    Some(term) => (self.output_term_index(i, term) + 1).to_string(),
+                    None => "0".into(),
+                })
+                .collect();
+            let ins: Vec<String> = ins.iter().map(|x| x.to_string()).collect();
+            writeln!(
+                f,
+                "{}, {} ({}) : {}",
+                ins.join(" "),
+                outs.join(" "),
+                rule.weight,
+                connection
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn write_var(f: &mut fmt::Formatter, section: &str, var: &LinguisticVar) -> fmt::Result {
+    writeln!(f, "\n[{}]", section)?;
+    writeln!(f, "Name='{}'", var.name)?;
+    writeln!(
+        f,
+        "Range=[{} {}]",
+        var.universe[0],
+        var.universe[var.universe.len() - 1]
+    )?;
+    writeln!(f, "NumMFs={}", var.sets.len())?;
+    for (j, set) in var.sets.iter().enumerate() {
+        let (kind, p) = mf_def(set).unwrap();
+        writeln!(f, "MF{}='{}':'{}',[{}]", j + 1, set.name, kind, join(&p))?;
+    }
+    Ok(())
+}
+
+fn join(p: &[f64]) -> String {
+    p.iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn named(vars: &[LinguisticVar], prefix: &str) -> Vec<LinguisticVar> {
+    vars.iter()
+        .enumerate()
+        .map(|(i, v)| v.clone().named(&label(v, i, prefix)))
+        .collect()
+}
+
+fn arity<const N: usize, const M: usize>(inputs: usize, outputs: usize) -> Result<(), ParseError> {
+    if inputs != N || outputs != M {
+        let found = format!("{} inputs and {} outputs", inputs, outputs);
+        let expected = format!("{} inputs and {} outputs", N, M);
+        return Err(ParseError::Unexpected(found, expected));
+    }
+    Ok(())
+}
+
+fn unexpected(found: &str, expected: &str) -> ParseError {
+    ParseError::Unexpected(found.into(), expected.into())
+}
+
+fn unsupported(what: &str) -> FuzzyError {
+    FuzzyError::Unsupported(what.into(), ".fis".into())
+}
+
+fn at(line: usize, e: ParseError) -> ParseError {
+    ParseError::Line(line, Box::new(e))
+}
+
+/// MATLAB membership function name and parameters of a set
+fn mf_def(set: &FuzzySet) -> Result<(&'static str, Vec<f64>), FuzzyError> {
+    let shape = match &set.shape {
+        Some(shape) => shape,
+        None => return Err(unsupported(&format!("term {} without a shape", set.name))),
+    };
+    let p = shape.params();
+    let res = match shape.kind() {
+        "triangular" if p[1] == 1.0 => ("trimf", vec![p[0] - p[2], p[0], p[0] + p[2]]),
+        "trapezoidal" if p[4] == 1.0 && p[1] == p[2] => ("trimf", vec![p[0], p[1], p[3]]),
+        "trapezoidal" if p[4] == 1.0 => ("trapmf", p[..4].to_vec()),
+        "points" if p.len() == 6 && [p[1], p[3], p[5]] == [0.0, 1.0, 0.0] => {
+            ("trimf", vec![p[0], p[2], p[4]])
+        }
+        "points" if p.len() == 8 && [p[1], p[3], p[5], p[7]] == [0.0, 1.0, 1.0, 0.0] => {
+            ("trapmf", vec![p[0], p[2], p[4], p[6]])
+        }
+        "gaussian" => ("gaussmf", vec![p[1], p[0]]),
+        "gauss2" => ("gauss2mf", vec![p[1], p[0], p[3], p[2]]),
+        "bell" => ("gbellmf", p),
+        "sigmoid" => ("sigmf", p),
+        "dsigmoid" => ("dsigmf", p),
+        "psigmoid" => ("psigmf", p),
+        "s_shape" => ("smf", p),
+        "z_shape" => ("zmf", p),
+        "pi_shape" => ("pimf", p),
+        _ => {
+            return Err(unsupported(&format!(
+                "term {} of shape {}",
+                set.name,
+                shape.kind()
+            )))
+        }
+    };
+    Ok(res)
+}
+
+fn mf_shape(kind: &str, p: &[f64]) -> Option<Box<dyn Shape>> {
+    let n = match kind {
+        "gaussmf" | "sigmf" | "smf" | "zmf" => 2,
+        "trimf" | "gbellmf" => 3,
+        "trapmf" | "gauss2mf" | "dsigmf" | "psigmf" | "pimf" => 4,
+        _ => return None,
+    };
+    if p.len() != n {
+        return None;
+    }
+    match kind {
+        // a trapezoid keeps shoulder triangles like [0 0 5] at 1 on their peak
+        "trimf" => Some(Box::new(trapezoidal(p[0], p[1], p[1], p[2], 1.0))),
+        "trapmf" => Some(Box::new(trapezoidal(p[0], p[1], p[2], p[3], 1.0))),
+        "gaussmf" => Some(Box::new(gaussian(p[1], p[0]))),
+        "gauss2mf" => Some(Box::new(gauss2(p[1], p[0], p[3], p[2]))),
+        "gbellmf" => from_params("bell", p),
+        "sigmf" => from_params("sigmoid", p),
+        "dsigmf" => from_params("dsigmoid", p),
+        "psigmf" => from_params("psigmoid", p),
+        "smf" => from_params("s_shape", p),
+        "zmf" => from_params("z_shape", p),
+        _ => from_params("pi_shape", p),
+    }
+}
+
+/// MF index for every input (0 don't care, negative for NOT) and the
+/// connection, 1 for AND and 2 for OR
+fn rule_literals(rule: &Rule, inputs: &[LinguisticVar]) -> Result<(Vec<i64>, u8), FuzzyError> {
+    let err = || unsupported("a nested condition or hedge");
+    let (items, connection) = match &rule.antecedent {
+        Antecedent::And(v) => (v.clone(), 1),
+        Antecedent::Or(v) => (v.clone(), 2),
+        a => (vec![a.clone()], 1),
+    };
+    let mut res: Vec<i64> = vec![0; inputs.len()];
+    for item in items {
+        let (i, term, sign) = match item {
+            Antecedent::Any if connection == 1 => continue,
+            Antecedent::Is(i, term) => (i, term, 1),
+            Antecedent::Not(a) => match *a {
+                Antecedent::Is(i, term) => (i, term, -1),
+                _ => return Err(err()),
+            },
+            _ => return Err(err()),
+        };
+        let j = inputs[i]
+            .sets
+            .iter()
+            .position(|s| s.name == term)
+            .ok_or_else(|| FuzzyError::UnknownTerm(inputs[i].name.clone(), term.clone()))?;
+        if res[i] != 0 {
+            return Err(err());
+        }
+        res[i] = sign * (j as i64 + 1);
+    }
+    Ok((res, connection))
+}
+
+struct Section {
+    name: String,
+    line: usize,
+    entries: Vec<(String, usize)>,
+}
+
+impl Section {
+    fn get(&self, key: &str) -> Result<(&str, usize), ParseError> {
+        for (entry, line) in self.entries.iter() {
+            if let Some((k, v)) = entry.split_once('=') {
+                if k.trim() == key {
+                    return Ok((v.trim(), *line));
+                }
+            }
+        }
+        Err(at(
+            self.line,
+            unexpected(&self.name, &format!("a {} entry", key)),
+        ))
+    }
+
+    fn string(&self, key: &str) -> Result<String, ParseError> {
+        let (v, _) = self.get(key)?;
+        Ok(v.trim_matches('\'').to_string())
+    }
+
+    fn number(&self, key: &str) -> Result<f64, ParseError> {
+        let (v, line) = self.get(key)?;
+        v.parse::<f64>()
+            .map_err(|_| at(line, unexpected(v, "a number")))
+    }
+}
+
+fn numbers(v: &str, line: usize) -> Result<Vec<f64>, ParseError> {
+    v.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            x.parse::<f64>()
+                .map_err(|_| at(line, unexpected(x, "a number")))
+        })
+        .collect()
+}
+
+/// `'name':'type',[p1 p2 ...]`
+fn parse_mf(v: &str, line: usize) -> Result<(String, String, Vec<f64>), ParseError> {
+    let err = || at(line, unexpected(v, "'name':'type',[params]"));
+    let parts: Vec<&str> = v.splitn(5, '\'').collect();
+    if parts.len() != 5 || parts[2] != ":" {
+        return Err(err());
+    }
+    let p = parts[4].trim().strip_prefix(',').ok_or_else(err)?;
+    Ok((
+        parts[1].to_string(),
+        parts[3].to_string(),
+        numbers(p, line)?,
+    ))
+}
+
+fn read(sections: &[Section]) -> Result<Fis, ParseError> {
+    let find = |name: &str| sections.iter().find(|s| s.name == name);
+    let system = find("System").ok_or_else(|| unexpected("no [System]", "a [System] section"))?;
+    let kind = system.string("Type")?;
+    let num_inputs = system.number("NumInputs")? as usize;
+    let num_outputs = system.number("NumOutputs")? as usize;
+
+    let method = |key: &str, default: &str| match system.get(key) {
+        Ok((v, line)) => Ok((v.trim_matches('\'').to_string(), line)),
+        Err(_) => Ok((default.to_string(), system.line)),
+    };
+    let (and, line) = method("AndMethod", "min")?;
+    let and = match and.as_str() {
+        "min" => TNorm::Min,
+        "prod" => TNorm::Product,
+        _ => return Err(at(line, unexpected(&and, "min or prod"))),
+    };
+    let (or, line) = method("OrMethod", "max")?;
+    let or = match or.as_str() {
+        "max" => SNorm::Max,
+        "probor" => SNorm::ProbSum,
+        _ => return Err(at(line, unexpected(&or, "max or probor"))),
+    };
+    let (imp, line) = method("ImpMethod", "min")?;
+    let implication = match imp.as_str() {
+        "min" => Implication::Min,
+        "prod" => Implication::Product,
+        _ => return Err(at(line, unexpected(&imp, "min or prod"))),
+    };
+    let sugeno = kind == "sugeno";
+    let (agg, line) = method("AggMethod", if sugeno { "sum" } else { "max" })?;
+    let aggregation = match agg.as_str() {
+        "max" => SNorm::Max,
+        "probor" => SNorm::ProbSum,
+        // sugeno outputs aren't aggregated, whatever MATLAB wrote for them
+        "sum" if sugeno => SNorm::Max,
+        _ => return Err(at(line, unexpected(&agg, "max or probor"))),
+    };
+    let (defuzz, line) = method("DefuzzMethod", if sugeno { "wtaver" } else { "centroid" })?;
+    let (defuzzifier, method) = match (sugeno, defuzz.as_str()) {
+        (true, "wtaver") => (Defuzzifier::WeightedAverage, SugenoMethod::WeightedAverage),
+        (true, "wtsum") => (Defuzzifier::WeightedAverage, SugenoMethod::WeightedSum),
+        (true, _) => return Err(at(line, unexpected(&defuzz, "wtaver or wtsum"))),
+        (false, "centroid") => (Defuzzifier::Centroid, SugenoMethod::default()),
+        (false, "bisector") => (Defuzzifier::Bisector, SugenoMethod::default()),
+        (false, "mom") => (Defuzzifier::MeanOfMax, SugenoMethod::default()),
+        (false, "som") => (Defuzzifier::SmallestOfMax, SugenoMethod::default()),
+        (false, "lom") => (Defuzzifier::LargestOfMax, SugenoMethod::default()),
+        _ => return Err(at(line, unexpected(&defuzz, "a supported DefuzzMethod"))),
+    };
+
+    let mut inputs: Vec<LinguisticVar> = vec![];
+    for i in 1..=num_inputs {
+        let s = find(&format!("Input{}", i))
+            .ok_or_else(|| unexpected(&format!("no [Input{}]", i), "a section per input"))?;
+        inputs.push(read_var(s)?);
+    }
+
+    let outputs = match kind.as_str() {
+        "mamdani" => {
+            let mut outputs: Vec<LinguisticVar> = vec![];
+            for i in 1..=num_outputs {
+                let s = find(&format!("Output{}", i)).ok_or_else(|| {
+                    unexpected(&format!("no [Output{}]", i), "a section per output")
+                })?;
+                outputs.push(read_var(s)?);
+            }
+            FisOutputs::Mamdani(outputs)
+        }
+        "sugeno" => {
+            let mut outputs: Vec<SugenoVar> = vec![];
+            for i in 1..=num_outputs {
+                let s = find(&format!("Output{}", i)).ok_or_else(|| {
+                    unexpected(&format!("no [Output{}]", i), "a section per output")
+                })?;
+                outputs.push(read_sugeno_var(s, num_inputs)?);
+            }
+            FisOutputs::Sugeno(outputs)
+        }
+        _ => return Err(at(system.line, unexpected(&kind, "mamdani or sugeno"))),
+    };
+
+    let mut fis = Fis {
+        name: system.string("Name")?,
+        inputs,
+        outputs,
+        rules: vec![],
+        ops: Operators {
+            and,
+            or,
+            implication,
+            aggregation,
+        },
+        defuzzifier,
+        method,
+    };
+    if let Some(s) = find("Rules") {
+        for (entry, line) in s.entries.iter() {
+            let rule = read_rule(&fis, entry, *line)?;
+            fis.rules.push(rule);
+        }
+    }
+    Ok(fis)
+}
+
+fn read_var(s: &Section) -> Result<LinguisticVar, ParseError> {
+    let (range, line) = s.get("Range")?;
+    let range = numbers(range, line)?;
+    if range.len() != 2 || range[1] <= range[0] {
+        return Err(at(line, unexpected(&format!("{:?}", range), "[min max]")));
+    }
+    let mut shapes: Vec<(String, Box<dyn Shape>)> = vec![];
+    for i in 1..=s.number("NumMFs")? as usize {
+        let (v, line) = s.get(&format!("MF{}", i))?;
+        let (name, kind, p) = parse_mf(v, line)?;
+        match mf_shape(&kind, &p) {
+            Some(shape) => shapes.push((name, shape)),
+            None => {
+                return Err(at(
+                    line,
+                    unexpected(&kind, "a supported membership function"),
+                ))
+            }
+        }
+    }
+    let terms: Vec<(&dyn Shape, &str)> = shapes
+        .iter()
+        .map(|(n, s)| (s.as_ref(), n.as_str()))
+        .collect();
+    Ok(LinguisticVar::new(terms, default_universe(range[0], range[1])).named(&s.string("Name")?))
+}
+
+/// lowest and highest value the terms of `var` reach over the input universes
+fn output_range(var: &SugenoVar, inputs: &[LinguisticVar]) -> (f64, f64) {
+    let mut range = (f64::INFINITY, f64::NEG_INFINITY);
+    for (_, f) in var.terms.iter() {
+        let (lo, hi) = match f {
+            Consequent::Constant(c) => (*c, *c),
+            Consequent::Linear(coef, c) => {
+                coef.iter()
+                    .zip(inputs.iter())
+                    .fold((*c, *c), |(lo, hi), (a, x)| {
+                        let ends = (a * x.universe[0], a * x.universe[x.universe.len() - 1]);
+                        (lo + ends.0.min(ends.1), hi + ends.0.max(ends.1))
+                    })
+            }
+        };
+        range = (range.0.min(lo), range.1.max(hi));
+    }
+    if var.terms.is_empty() {
+        return (0.0, 1.0);
+    }
+    range
+}
+
+fn read_sugeno_var(s: &Section, num_inputs: usize) -> Result<SugenoVar, ParseError> {
+    let mut terms: Vec<(Consequent, String)> = vec![];
+    for i in 1..=s.number("NumMFs")? as usize {
+        let (v, line) = s.get(&format!("MF{}", i))?;
+        let (name, kind, p) = parse_mf(v, line)?;
+        let c = match kind.as_str() {
+            "constant" if p.len() == 1 => Consequent::Constant(p[0]),
+            "linear" if p.len() == num_inputs + 1 => {
+                Consequent::Linear(p[..num_inputs].to_vec(), p[num_inputs])
+            }
+            _ => return Err(at(line, unexpected(v, "a constant or linear output"))),
+        };
+        terms.push((c, name));
+    }
+    let terms = terms.iter().map(|(c, n)| (c.clone(), n.as_str())).collect();
+    Ok(SugenoVar::new(terms).named(&s.string("Name")?))
+}
+
+/// `1 -2, 1 (0.5) : 1`
+fn read_rule(fis: &Fis, entry: &str, line: usize) -> Result<Rule, ParseError> {
+    let err = || {
+        at(
+            line,
+            unexpected(entry, "in1 in2 ..., out1 ... (weight) : connection"),
+        )
+    };
+    let (ins, rest) = entry.split_once(',').ok_or_else(err)?;
+    let (outs, rest) = rest.split_once('(').ok_or_else(err)?;
+    let (weight, connection) = rest.split_once(')').ok_or_else(err)?;
+    let ins = numbers(ins, line)?;
+    let outs = numbers(outs, line)?;
+    let weight = weight.trim().parse::<f64>().map_err(|_| err())?;
+    if !(0.0..=1.0).contains(&weight) {
+        return Err(at(
+            line,
+            unexpected(&weight.to_string(), "a weight in [0, 1]"),
+        ));
+    }
+    let connection = connection.trim().trim_start_matches(':').trim();
+
+    if ins.len() != fis.inputs.len() {
+        return Err(err());
+    }
+    let mut items: Vec<Antecedent> = vec![];
+    for (i, x) in ins.iter().enumerate() {
+        let j = x.abs() as usize;
+        if j == 0 {
+            continue;
+        }
+        let set = fis.inputs[i].sets.get(j - 1).ok_or_else(|| {
+            at(
+                line,
+                ParseError::UnknownTerm(fis.inputs[i].name.clone(), x.to_string()),
+            )
+        })?;
+        let item = Antecedent::Is(i, set.name.clone());
+        items.push(if *x < 0.0 { !item } else { item });
+    }
+    let antecedent = match connection {
+        "1" => Antecedent::And(items),
+        "2" => Antecedent::Or(items),
+        _ => return Err(err()),
+    };
+
+    let terms: Vec<Vec<String>> = match &fis.outputs {
+        FisOutputs::Mamdani(v) => v
+            .iter()
+            .map(|x| x.sets.iter().map(|s| s.name.clone()).collect())
+            .collect(),
+        FisOutputs::Sugeno(v) => v
+            .iter()
+            .map(|x| x.terms.iter().map(|t| t.0.clone()).collect())
+            .collect(),
+    };
+    if outs.len() != terms.len() {
+        return Err(err());
+    }
+    let mut consequent: Vec<Option<String>> = vec![];
+    for (i, x) in outs.iter().enumerate() {
+        if *x < 0.0 {
+            return Err(at(line, unexpected(entry, "no negated outputs")));
+        }
+        consequent.push(match *x as usize {
+            0 => None,
+            j => Some(terms[i].get(j - 1).cloned().ok_or_else(err)?),
+        });
+    }
+    Ok(Rule {
+        antecedent,
+        consequent,
+        weight,
+    })
+}
+
+fn and_name(t: TNorm) -> Result<&'static str, FuzzyError> {
+    match t {
+        TNorm::Min => Ok("min"),
+        TNorm::Product => Ok("prod"),
+        _ => Err(unsupported(&format!("AND {:?}", t))),
+    }
+}
+
+fn or_name(s: SNorm) -> Result<&'static str, FuzzyError> {
+    match s {
+        SNorm::Max => Ok("max"),
+        SNorm::ProbSum => Ok("probor"),
+        _ => Err(unsupported(&format!("OR {:?}", s))),
+    }
+}
+
+fn agg_name(s: SNorm) -> Result<&'static str, FuzzyError> {
+    or_name(s)
+}
+
+fn defuzz_name(d: Defuzzifier) -> Result<&'static str, FuzzyError> {
+    match d {
+        Defuzzifier::Centroid => Ok("centroid"),
+        Defuzzifier::Bisector => Ok("bisector"),
+        Defuzzifier::MeanOfMax => Ok("mom"),
+        Defuzzifier::SmallestOfMax => Ok("som"),
+        Defuzzifier::LargestOfMax => Ok("lom"),
+        _ => Err(unsupported(&format!("defuzzifier {:?}", d))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIPPER: &str = "[System]
+Name='tipper'
+Type='mamdani'
+Version=2.0
+NumInputs=2
+NumOutputs=1
+NumRules=3
+AndMethod='min'
+OrMethod='max'
+ImpMethod='min'
+AggMethod='max'
+DefuzzMethod='centroid'
+
+[Input1]
+Name='service'
+Range=[0 10]
+NumMFs=3
+MF1='poor':'gaussmf',[1.5 0]
+MF2='good':'gaussmf',[1.5 5]
+MF3='excellent':'gaussmf',[1.5 10]
+
+[Input2]
+Name='food'
+Range=[0 10]
+NumMFs=2
+MF1='rancid':'trapmf',[-2 0 1 3]
+MF2='delicious':'trapmf',[7 9 10 12]
+
+[Output1]
+Name='tip'
+Range=[0 30]
+NumMFs=3
+MF1='cheap':'trimf',[0 5 10]
+MF2='average':'trimf',[10 15 20]
+MF3='generous':'trimf',[20 25 30]
+
+[Rules]
+1 1, 1 (1) : 2
+2 0, 2 (1) : 1
+3 2, 3 (0.5) : 1
+";
+
+    #[test]
+    fn mamdani() {
+        let fis = Fis::parse(TIPPER).unwrap();
+        assert_eq!(fis.inputs[0].name, "service");
+        assert_eq!(
+            fis.rules[0].antecedent,
+            Antecedent::Or(vec![
+                Antecedent::Is(0, "poor".into()),
+                Antecedent::Is(1, "rancid".into())
+            ])
+        );
+        assert_eq!(fis.rules[2].weight, 0.5);
+
+        let engine = fis.into_engine::<2, 1>().unwrap();
+        assert!((engine.defuzzify([0.0, 0.0])[0] - 5.0).abs() < 0.1);
+
+        let text = Fis::from_engine("tipper", &engine).unwrap().to_string();
+        let copy = Fis::parse(&text).unwrap().into_engine::<2, 1>().unwrap();
+        for inputs in [[0.0, 0.0], [3.0, 8.0], [9.0, 9.5]] {
+            assert!((engine.defuzzify(inputs)[0] - copy.defuzzify(inputs)[0]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn shoulders() {
+        let text = TIPPER
+            .replace("'cheap':'trimf',[0 5 10]", "'cheap':'trimf',[0 0 10]")
+            .replace(
+                "'generous':'trimf',[20 25 30]",
+                "'generous':'trimf',[20 30 30]",
+            );
+        let engine = Fis::parse(&text).unwrap().into_engine::<2, 1>().unwrap();
+        let tip = &engine.outputs()[0];
+        assert_eq!(tip.term("cheap").degree_of(0.0), 1.0);
+        assert_eq!(tip.term("generous").degree_of(30.0), 1.0);
+        assert_eq!(tip.term("average").degree_of(15.0), 1.0);
+
+        let copy = Fis::from_engine("tipper", &engine).unwrap().to_string();
+        assert!(copy.contains("MF1='cheap':'trimf',[0 0 10]"), "{}", copy);
+    }
+
+    #[test]
+    fn sugeno() {
+        let text = TIPPER
+            .replace("Type='mamdani'", "Type='sugeno'")
+            .replace("AggMethod='max'", "AggMethod='sum'")
+            .replace("DefuzzMethod='centroid'", "DefuzzMethod='wtaver'")
+            .replace("'cheap':'trimf',[0 5 10]", "'cheap':'constant',[5]")
+            .replace(
+                "'average':'trimf',[10 15 20]",
+                "'average':'linear',[1 0 10]",
+            )
+            .replace(
+                "'generous':'trimf',[20 25 30]",
+                "'generous':'constant',[25]",
+            );
+        let engine = Fis::parse(&text).unwrap().into_sugeno::<2, 1>().unwrap();
+        // only "poor or rancid" fires fully at (0, 0), "good" fires slightly
+        let res = engine.calculate([0.0, 0.0])[0];
+        assert!(res > 5.0 && res < 5.1);
+
+        let copy = Fis::from_sugeno("tipper", &engine).unwrap().to_string();
+        assert!(copy.contains("AggMethod='sum'"), "{}", copy);
+        // "average" is service + 10 over service in [0, 10]
+        assert!(copy.contains("Range=[5 25]"), "{}", copy);
+        let copy = Fis::parse(&copy).unwrap().into_sugeno::<2, 1>().unwrap();
+        assert!((copy.calculate([4.0, 6.0])[0] - engine.calculate([4.0, 6.0])[0]).abs() < 1e-9);
+
+        let text = text.replace("DefuzzMethod='wtaver'", "DefuzzMethod='wtsum'");
+        let mut sum = Fis::parse(&text).unwrap().into_sugeno::<2, 1>().unwrap();
+        assert_eq!(sum.method(), SugenoMethod::WeightedSum);
+        let copy = Fis::from_sugeno("tipper", &sum).unwrap().to_string();
+        assert!(copy.contains("DefuzzMethod='wtsum'"), "{}", copy);
+        sum.set_method(SugenoMethod::WeightedAverage);
+        assert_eq!(sum.calculate([4.0, 6.0]), engine.calculate([4.0, 6.0]));
+    }
+
+    #[test]
+    fn errors() {
+        let e = Fis::parse(&TIPPER.replace("'gaussmf',[1.5 5]", "'circlemf',[1.5 5]"));
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "line 19: expected a supported membership function but found \"circlemf\""
+        );
+        let e = Fis::parse(&TIPPER.replace("3 2, 3 (0.5) : 1", "3 4, 3 (0.5) : 1"));
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "line 40: variable \"food\" has no term \"4\""
+        );
+        let e = Fis::parse(&TIPPER.replace("3 2, 3 (0.5) : 1", "3 x, 3 (0.5) : 1"));
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "line 40: expected a number but found \"x\""
+        );
+        assert!(Fis::parse(TIPPER).unwrap().into_sugeno::<2, 1>().is_err());
+
+        let mut engine = Fis::parse(TIPPER).unwrap().into_dyn_engine().unwrap();
+        engine.set_defuzzifier(Defuzzifier::WeightedCentroid);
+        let e = Fis::from_dyn_engine("tipper", &engine);
+        assert!(matches!(e, Err(FuzzyError::Unsupported(..))));
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "defuzzifier WeightedCentroid can't be written as .fis"
+        );
+    }
+}
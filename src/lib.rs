@@ -0,0 +1,25 @@
+pub mod analysis;
+pub mod anfis;
+pub mod antecedent;
+pub mod backtest;
+pub mod batch;
+pub mod cluster;
+pub mod data;
+pub mod defuzz;
+pub mod error;
+pub mod explain;
+pub mod fcl;
+pub mod fis;
+pub mod ops;
+pub mod parse;
+pub mod partition;
+pub mod quality;
+pub mod rule;
+pub mod set;
+pub mod shape;
+pub mod sugeno;
+pub mod surface;
+pub mod table;
+pub mod tune;
+pub mod type2;
+pub mod wang_mendel;
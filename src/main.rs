@@ -1,16 +1,11 @@
-pub mod backtest;
-pub mod data;
-pub mod rule;
-pub mod set;
-pub mod shape;
-
 use chrono::offset::{Local, TimeZone};
 use chrono::Date;
-use data::read_csv;
+use fuzzy::backtest;
+use fuzzy::data::{self, read_csv};
+use fuzzy::rule::FuzzyEngine;
+use fuzzy::set::{arange, LinguisticVar};
+use fuzzy::shape::{trapezoidal, triangular};
 use plotters::prelude::*;
-use rule::FuzzyEngine;
-use set::{arange, LinguisticVar};
-use shape::{trapezoidal, triangular};
 use std::error::Error;
 
 fn parse_time(t: &str) -> Date<Local> {
@@ -104,19 +99,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     //bb_var.plot("bollinger bands".into(), "img/bb.svg".into())?;
     //long.plot("long, short".into(), "img/ls.svg".into())?;
 
-    let mut f_engine = FuzzyEngine::new([rsi_var, bb_var], [long, short]);
+    let mut f_engine = FuzzyEngine::new(
+        [rsi_var.named("rsi"), bb_var.named("bb")],
+        [long.named("long"), short.named("short")],
+    );
 
-    f_engine.add_rule(["high", "long"], ["weak", "weak"]);
-    f_engine.add_rule(["high", "wait"], ["weak", "strong"]);
-    f_engine.add_rule(["high", "short"], ["weak", "verystrong"]);
+    f_engine.add_rules_str(
+        "
+        IF rsi IS high AND bb IS long THEN long IS weak AND short IS weak
+        IF rsi IS high AND bb IS wait THEN long IS weak AND short IS strong
+        IF rsi IS high AND bb IS short THEN long IS weak AND short IS verystrong
 
-    f_engine.add_rule(["medium", "long"], ["weak", "strong"]);
-    f_engine.add_rule(["medium", "wait"], ["weak", "weak"]);
-    f_engine.add_rule(["medium", "short"], ["strong", "weak"]);
+        IF rsi IS medium AND bb IS long THEN long IS weak AND short IS strong
+        IF rsi IS medium AND bb IS wait THEN long IS weak AND short IS weak
+        IF rsi IS medium AND bb IS short THEN long IS strong AND short IS weak
 
-    f_engine.add_rule(["low", "long"], ["verystrong", "weak"]);
-    f_engine.add_rule(["low", "wait"], ["strong", "weak"]);
-    f_engine.add_rule(["low", "short"], ["weak", "weak"]);
+        IF rsi IS low AND bb IS long THEN long IS verystrong AND short IS weak
+        IF rsi IS low AND bb IS wait THEN long IS strong AND short IS weak
+        IF rsi IS low AND bb IS short THEN long IS weak AND short IS weak
+        ",
+    )?;
 
     let data = read_csv("eth.csv");
     let rsi = data::rsi(&data, 14)[2256..].to_vec();
@@ -133,13 +135,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map(|x| parse_time(x.snapped_at.as_str()))
         .collect();
 
-    let mut long_singal: Vec<f64> = vec![];
-    let mut short_singal: Vec<f64> = vec![];
-    for i in 0..price.len() {
-        let result = f_engine.calculate([rsi[i], bb_inputs[i]]);
-        long_singal.push(result[0].centroid_defuzz());
-        short_singal.push(result[1].centroid_defuzz());
-    }
+    let signals = f_engine.defuzzify_columns([&rsi, &bb_inputs]);
+    let (long_singal, short_singal) = (&signals[0], &signals[1]);
     /*
     plot(
         &date,
@@ -148,8 +145,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     )?;
     */
 
-    backtest::f_backtest(&price, &long_singal, false);
-    backtest::f_backtest(&price, &short_singal, true);
+    backtest::f_backtest(&price, long_singal, false);
+    backtest::f_backtest(&price, short_singal, true);
     backtest::c_backtest(&price, &rsi, &bb, false);
     backtest::c_backtest(&price, &rsi, &bb, true);
 
@@ -0,0 +1,237 @@
+use crate::error::FuzzyError;
+use serde::{Deserialize, Serialize};
+
+/// operators for AND (t-norm) used in rule conditions
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TNorm {
+    Min,
+    Product,
+    Lukasiewicz,
+    Drastic,
+    Einstein,
+    Hamacher,
+    Yager(f64),
+}
+
+impl TNorm {
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            TNorm::Min => a.min(b),
+            TNorm::Product => a * b,
+            TNorm::Lukasiewicz => (a + b - 1.0).max(0.0),
+            TNorm::Drastic => {
+                if a == 1.0 {
+                    b
+                } else if b == 1.0 {
+                    a
+                } else {
+                    0.0
+                }
+            }
+            TNorm::Einstein => (a * b) / (2.0 - (a + b - a * b)),
+            TNorm::Hamacher => {
+                if a == 0.0 && b == 0.0 {
+                    0.0
+                } else {
+                    (a * b) / (a + b - a * b)
+                }
+            }
+            TNorm::Yager(p) => {
+                (1.0 - ((1.0 - a).powf(*p) + (1.0 - b).powf(*p)).powf(1.0 / p)).max(0.0)
+            }
+        }
+    }
+}
+
+/// operators for OR (s-norm) used in rule conditions and for aggregating rule outputs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SNorm {
+    Max,
+    ProbSum,
+    BoundedSum,
+    Drastic,
+    Einstein,
+    Hamacher,
+    Yager(f64),
+}
+
+impl SNorm {
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            SNorm::Max => a.max(b),
+            SNorm::ProbSum => a + b - a * b,
+            SNorm::BoundedSum => (a + b).min(1.0),
+            SNorm::Drastic => {
+                if a == 0.0 {
+                    b
+                } else if b == 0.0 {
+                    a
+                } else {
+                    1.0
+                }
+            }
+            SNorm::Einstein => (a + b) / (1.0 + a * b),
+            SNorm::Hamacher => {
+                if a * b == 1.0 {
+                    1.0
+                } else {
+                    (a + b - 2.0 * a * b) / (1.0 - a * b)
+                }
+            }
+            SNorm::Yager(p) => (a.powf(*p) + b.powf(*p)).powf(1.0 / p).min(1.0),
+        }
+    }
+}
+
+/// how a rule's firing strength shapes its consequent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Implication {
+    Min,     // clip the consequent at the firing strength
+    Product, // scale the consequent by the firing strength
+}
+
+impl Implication {
+    /// consequent membership `degree` after firing with `strength`
+    pub fn apply(&self, strength: f64, degree: f64) -> f64 {
+        match self {
+            Implication::Min => degree.min(strength),
+            Implication::Product => degree * strength,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Operators {
+    pub and: TNorm,
+    pub or: SNorm,
+    pub implication: Implication,
+    pub aggregation: SNorm,
+}
+
+impl Operators {
+    /// fails for a Yager norm whose p isn't > 0, its degrees would be NaN or infinite
+    pub fn check(&self) -> Result<(), FuzzyError> {
+        let yager = [
+            match self.and {
+                TNorm::Yager(p) => Some(p),
+                _ => None,
+            },
+            match self.or {
+                SNorm::Yager(p) => Some(p),
+                _ => None,
+            },
+            match self.aggregation {
+                SNorm::Yager(p) => Some(p),
+                _ => None,
+            },
+        ];
+        match yager.iter().flatten().find(|p| p.is_nan() || **p <= 0.0) {
+            Some(p) => Err(FuzzyError::InvalidRange(format!(
+                "yager p must be > 0, got {}",
+                p
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for Operators {
+    /// classic Mamdani min/max
+    fn default() -> Self {
+        Operators {
+            and: TNorm::Min,
+            or: SNorm::Max,
+            implication: Implication::Min,
+            aggregation: SNorm::Max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::FuzzyEngine;
+    use crate::set::{arange, LinguisticVar};
+    use crate::shape::triangular;
+
+    #[test]
+    fn norms() {
+        let t = [
+            TNorm::Min,
+            TNorm::Product,
+            TNorm::Lukasiewicz,
+            TNorm::Drastic,
+            TNorm::Einstein,
+            TNorm::Hamacher,
+            TNorm::Yager(2.0),
+        ];
+        let s = [
+            SNorm::Max,
+            SNorm::ProbSum,
+            SNorm::BoundedSum,
+            SNorm::Drastic,
+            SNorm::Einstein,
+            SNorm::Hamacher,
+            SNorm::Yager(2.0),
+        ];
+        // 1 is the identity of every t-norm, 0 of every s-norm
+        for (t, s) in t.iter().zip(s.iter()) {
+            assert!((t.apply(0.3, 1.0) - 0.3).abs() < 1e-12);
+            assert!((s.apply(0.3, 0.0) - 0.3).abs() < 1e-12);
+            assert!(t.apply(0.6, 0.7) <= 0.6);
+            assert!(s.apply(0.6, 0.7) >= 0.7);
+        }
+        assert!((TNorm::Einstein.apply(0.5, 0.5) - 0.2).abs() < 1e-12);
+        assert!((SNorm::ProbSum.apply(0.5, 0.5) - 0.75).abs() < 1e-12);
+        assert_eq!(TNorm::Lukasiewicz.apply(0.4, 0.5), 0.0);
+    }
+
+    #[test]
+    fn yager_p() {
+        let ops = |p: f64| Operators {
+            aggregation: SNorm::Yager(p),
+            ..Operators::default()
+        };
+        assert!(ops(2.0).check().is_ok());
+        for p in [0.0, -1.0, f64::NAN] {
+            assert!(ops(p).check().is_err());
+        }
+        let and = Operators {
+            and: TNorm::Yager(-2.0),
+            ..Operators::default()
+        };
+        assert!(and.check().is_err());
+    }
+
+    #[test]
+    fn engine_operators() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(20f64, 1.0, 20f64), "low"),
+                (&triangular(80f64, 1.0, 20f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let ma = LinguisticVar::new(
+            vec![(&triangular(30f64, 0.8, 20f64), "low")],
+            arange(0f64, 100f64, 0.01),
+        );
+        let trend = LinguisticVar::new(
+            vec![(&triangular(30f64, 1f64, 30f64), "weak")],
+            arange(0f64, 100f64, 0.01),
+        );
+        let mut engine = FuzzyEngine::new([rsi, ma], [trend]);
+        engine.add_rule(["low", "low"], ["weak"]);
+        engine.add_rule(["high", "low"], ["weak"]);
+
+        engine.set_operators(Operators {
+            and: TNorm::Product,
+            implication: Implication::Product,
+            aggregation: SNorm::ProbSum,
+            ..Operators::default()
+        });
+        let res = engine.calculate([15f64, 25f64]);
+        // (low, low): 0.75 * 0.6, (high, low): 0 -> weak scaled by 0.45
+        assert!((res[0].height() - 0.45).abs() < 1e-3);
+    }
+}
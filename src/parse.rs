@@ -0,0 +1,484 @@
+use crate::antecedent::{is, Antecedent, Hedge};
+use crate::rule::Rule;
+use crate::set::LinguisticVar;
+use crate::sugeno::SugenoVar;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnknownVariable(String),
+    UnknownTerm(String, String), // (variable, term)
+    Unexpected(String, String),  // (found, expected)
+    HedgeTerm(String, String),   // (variable, term) named like a hedge or NOT
+    Repeated(String),            // output set twice in one consequent
+    Line(usize, Box<ParseError>),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownVariable(var) => write!(f, "unknown variable \"{}\"", var),
+            ParseError::UnknownTerm(var, term) => {
+                write!(f, "variable \"{}\" has no term \"{}\"", var, term)
+            }
+            ParseError::Unexpected(found, expected) => {
+                write!(f, "expected {} but found \"{}\"", expected, found)
+            }
+            ParseError::HedgeTerm(var, term) => write!(
+                f,
+                "term \"{}\" of variable \"{}\" reads as a modifier, rename it",
+                term, var
+            ),
+            ParseError::Repeated(var) => write!(f, "output \"{}\" is set twice", var),
+            ParseError::Line(line, e) => write!(f, "line {}: {}", line, e),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// anything rules can refer to by variable and term name
+pub trait Terms {
+    fn name(&self) -> &str;
+    fn term_index(&self, term: &str) -> Option<usize>;
+
+    fn has_term(&self, term: &str) -> bool {
+        self.term_index(term).is_some()
+    }
+}
+
+/// name rules and reports use for variable `i`, unnamed ones become `{prefix}{i}` counting from 0
+pub(crate) fn label<T: Terms>(var: &T, i: usize, prefix: &str) -> String {
+    match var.name().is_empty() {
+        true => format!("{}{}", prefix, i),
+        false => var.name().to_string(),
+    }
+}
+
+impl Terms for LinguisticVar {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn term_index(&self, term: &str) -> Option<usize> {
+        self.sets.iter().position(|x| x.name == term)
+    }
+}
+
+impl Terms for SugenoVar {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn term_index(&self, term: &str) -> Option<usize> {
+        self.terms.iter().position(|x| x.0 == term)
+    }
+}
+
+/// parse `IF <cond> THEN <var> IS <term> [AND <var> IS <term> ...] [WITH <weight>]`
+/// where cond is made of `<var> IS [NOT] [hedge ...] <term>` joined with
+/// AND, OR, NOT and parentheses. keywords and hedges are case insensitive, so a condition
+/// can't refer to an input term named like one of them
+pub fn parse_rule<I: Terms, O: Terms>(
+    text: &str,
+    inputs: &[I],
+    outputs: &[O],
+) -> Result<Rule, ParseError> {
+    let tokens = tokenize(text);
+    let mut p = Parser {
+        tokens: &tokens,
+        pos: 0,
+    };
+
+    p.keyword("IF")?;
+    let antecedent = p.or_expr(inputs)?;
+    p.keyword("THEN")?;
+
+    let mut consequent: Vec<Option<String>> = vec![None; outputs.len()];
+    loop {
+        let (i, term) = p.clause(outputs)?;
+        if consequent[i].is_some() {
+            return Err(ParseError::Repeated(outputs[i].name().into()));
+        }
+        consequent[i] = Some(term);
+        if !(p.eat("AND") || p.eat(",")) {
+            break;
+        }
+    }
+
+    let mut weight = 1.0;
+    if p.eat("WITH") {
+        let w = p.next("a weight")?;
+        weight = match w.parse::<f64>() {
+            Ok(w) if (0.0..=1.0).contains(&w) => w,
+            _ => {
+                return Err(ParseError::Unexpected(
+                    w.into(),
+                    "a weight in [0, 1]".into(),
+                ))
+            }
+        };
+    }
+    p.eat(";");
+    if let Some(t) = p.peek() {
+        return Err(ParseError::Unexpected(t.into(), "end of rule".into()));
+    }
+
+    Ok(Rule {
+        antecedent,
+        consequent,
+        weight,
+    })
+}
+
+/// one rule per line, empty lines and lines starting with `#` are skipped
+pub fn parse_rules<I: Terms, O: Terms>(
+    text: &str,
+    inputs: &[I],
+    outputs: &[O],
+) -> Result<Vec<Rule>, ParseError> {
+    let mut rules: Vec<Rule> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_rule(line, inputs, outputs) {
+            Ok(rule) => rules.push(rule),
+            Err(e) => return Err(ParseError::Line(i + 1, Box::new(e))),
+        }
+    }
+    Ok(rules)
+}
+
+/// write a rule back in the syntax read by `parse_rule`
+pub fn format_rule<I: Terms, O: Terms>(rule: &Rule, inputs: &[I], outputs: &[O]) -> String {
+    let mut res = format!("IF {}", format_cond(&rule.antecedent, inputs, true));
+    let consequent: Vec<String> = rule
+        .consequent
+        .iter()
+        .enumerate()
+        .filter_map(|(i, term)| Some(format!("{} IS {}", outputs[i].name(), term.as_ref()?)))
+        .collect();
+    res.push_str(&format!(" THEN {}", consequent.join(" AND ")));
+    if rule.weight != 1.0 {
+        res.push_str(&format!(" WITH {}", rule.weight));
+    }
+    res
+}
+
+fn format_cond<I: Terms>(cond: &Antecedent, inputs: &[I], top: bool) -> String {
+    match cond {
+        Antecedent::Any => format!("{} IS any", inputs[0].name()),
+        Antecedent::Is(i, term) => format!("{} IS {}", inputs[*i].name(), term),
+        Antecedent::Not(a) | Antecedent::Hedge(_, a) => {
+            // `var IS NOT very term` when the modifiers end at a single term
+            let mut modifiers: Vec<&str> = vec![];
+            let mut c = cond;
+            while let Antecedent::Not(a) | Antecedent::Hedge(_, a) = c {
+                modifiers.push(match c {
+                    Antecedent::Hedge(h, _) => h.name(),
+                    _ => "NOT",
+                });
+                c = a;
+            }
+            if let Antecedent::Is(i, term) = c {
+                return format!("{} IS {} {}", inputs[*i].name(), modifiers.join(" "), term);
+            }
+            let word = match cond {
+                Antecedent::Hedge(h, _) => h.name(),
+                _ => "NOT",
+            };
+            format!("{} {}", word, format_cond(a, inputs, false))
+        }
+        Antecedent::And(v) | Antecedent::Or(v) => {
+            let op = match cond {
+                Antecedent::And(_) => " AND ",
+                _ => " OR ",
+            };
+            let parts: Vec<String> = v
+                .iter()
+                .filter(|a| !(op == " AND " && **a == Antecedent::Any))
+                .map(|a| format_cond(a, inputs, false))
+                .collect();
+            match parts.len() {
+                0 => format_cond(&Antecedent::Any, inputs, top),
+                1 => parts[0].clone(),
+                _ if top => parts.join(op),
+                _ => format!("({})", parts.join(op)),
+            }
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == ',' || c == ';' {
+            if !word.is_empty() {
+                tokens.push(word.clone());
+                word.clear();
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|x| x.as_str())
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'a str, ParseError> {
+        match self.peek() {
+            Some(t) => {
+                self.pos += 1;
+                Ok(t)
+            }
+            None => Err(ParseError::Unexpected(
+                "end of rule".into(),
+                expected.into(),
+            )),
+        }
+    }
+
+    /// consume the next token if it's the given keyword
+    fn eat(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(t) if t.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        let t = self.next(keyword)?;
+        if !t.eq_ignore_ascii_case(keyword) {
+            return Err(ParseError::Unexpected(t.into(), keyword.into()));
+        }
+        Ok(())
+    }
+
+    fn or_expr<I: Terms>(&mut self, inputs: &[I]) -> Result<Antecedent, ParseError> {
+        let mut res = self.and_expr(inputs)?;
+        while self.eat("OR") {
+            res = res.or(self.and_expr(inputs)?);
+        }
+        Ok(res)
+    }
+
+    fn and_expr<I: Terms>(&mut self, inputs: &[I]) -> Result<Antecedent, ParseError> {
+        let mut res = self.unary(inputs)?;
+        while self.eat("AND") {
+            res = res.and(self.unary(inputs)?);
+        }
+        Ok(res)
+    }
+
+    fn unary<I: Terms>(&mut self, inputs: &[I]) -> Result<Antecedent, ParseError> {
+        if self.eat("NOT") {
+            return Ok(!self.unary(inputs)?);
+        }
+        // hedge in front of a parenthesized condition
+        if let (Some(t), Some("(")) = (
+            self.peek(),
+            self.tokens.get(self.pos + 1).map(|x| x.as_str()),
+        ) {
+            if let Some(h) = Hedge::from_name(&t.to_lowercase()) {
+                self.pos += 1;
+                return Ok(self.unary(inputs)?.hedge(h));
+            }
+        }
+        if self.eat("(") {
+            let res = self.or_expr(inputs)?;
+            self.keyword(")")?;
+            return Ok(res);
+        }
+
+        let name = self.next("a variable")?;
+        let i = find_var(inputs, name)?;
+        self.keyword("IS")?;
+
+        let mut modifiers: Vec<&str> = vec![];
+        while let Some(t) = self.peek() {
+            if t.eq_ignore_ascii_case("NOT") || Hedge::from_name(&t.to_lowercase()).is_some() {
+                modifiers.push(t);
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if let Some(m) = modifiers.iter().find(|m| inputs[i].has_term(m)) {
+            return Err(ParseError::HedgeTerm(name.into(), m.to_string()));
+        }
+        let term = self.next("a term")?;
+        if term.eq_ignore_ascii_case("any") && modifiers.is_empty() {
+            return Ok(Antecedent::Any);
+        }
+        if !inputs[i].has_term(term) {
+            return Err(ParseError::UnknownTerm(name.into(), term.into()));
+        }
+
+        let mut res = is(i, term);
+        for m in modifiers.iter().rev() {
+            res = match Hedge::from_name(&m.to_lowercase()) {
+                Some(h) => res.hedge(h),
+                None => !res,
+            };
+        }
+        Ok(res)
+    }
+
+    /// `<var> IS <term>` of the consequent
+    fn clause<O: Terms>(&mut self, outputs: &[O]) -> Result<(usize, String), ParseError> {
+        let name = self.next("a variable")?;
+        let i = find_var(outputs, name)?;
+        self.keyword("IS")?;
+        let term = self.next("a term")?;
+        if !outputs[i].has_term(term) {
+            return Err(ParseError::UnknownTerm(name.into(), term.into()));
+        }
+        Ok((i, term.to_string()))
+    }
+}
+
+fn find_var<T: Terms>(vars: &[T], name: &str) -> Result<usize, ParseError> {
+    vars.iter()
+        .position(|v| v.name() == name)
+        .ok_or_else(|| ParseError::UnknownVariable(name.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::arange;
+    use crate::shape::*;
+
+    fn vars() -> (Vec<LinguisticVar>, Vec<LinguisticVar>) {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let signal = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "weak"),
+                (&triangular(100f64, 1.0, 50f64), "strong"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        (
+            vec![rsi.clone().named("rsi"), rsi.named("bb")],
+            vec![signal.clone().named("long"), signal.named("short")],
+        )
+    }
+
+    #[test]
+    fn parse() {
+        let (inputs, outputs) = vars();
+        let rule = parse_rule(
+            "IF rsi IS low AND bb IS NOT very high THEN long IS strong WITH 0.8",
+            &inputs,
+            &outputs,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.antecedent,
+            is(0, "low").and(!is(1, "high").hedge(Hedge::Very))
+        );
+        assert_eq!(rule.consequent, vec![Some("strong".into()), None]);
+        assert_eq!(rule.weight, 0.8);
+
+        let rule = parse_rule(
+            "if (rsi is high or bb is any) and not rsi is low then short is weak, long is weak",
+            &inputs,
+            &outputs,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.antecedent,
+            is(0, "high").or(Antecedent::Any).and(!is(0, "low"))
+        );
+        assert_eq!(
+            rule.consequent,
+            vec![Some("weak".into()), Some("weak".into())]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (inputs, outputs) = vars();
+        let e = parse_rule("IF rsi IS lo THEN long IS weak", &inputs, &outputs);
+        assert_eq!(e, Err(ParseError::UnknownTerm("rsi".into(), "lo".into())));
+        let e = parse_rule("IF macd IS low THEN long IS weak", &inputs, &outputs);
+        assert_eq!(e, Err(ParseError::UnknownVariable("macd".into())));
+        let e = parse_rule("IF rsi IS low THEN long IS weak WITH 2", &inputs, &outputs);
+        assert!(matches!(e, Err(ParseError::Unexpected(..))));
+        let e = parse_rule(
+            "IF rsi IS low THEN long IS weak AND long IS strong",
+            &inputs,
+            &outputs,
+        );
+        assert_eq!(e, Err(ParseError::Repeated("long".into())));
+
+        // hedges are read greedily, a term named like one can't be reached
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "very"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        )
+        .named("rsi");
+        for text in [
+            "IF rsi IS very THEN long IS weak",
+            "IF rsi IS very high THEN long IS weak",
+        ] {
+            let e = parse_rule(text, std::slice::from_ref(&rsi), &outputs);
+            assert_eq!(e, Err(ParseError::HedgeTerm("rsi".into(), "very".into())));
+        }
+
+        let e = parse_rules(
+            "# comment\nIF rsi IS low THEN long IS weak\n\nIF rsi IS low THEN short IS big",
+            &inputs,
+            &outputs,
+        );
+        assert_eq!(
+            e.unwrap_err().to_string(),
+            "line 4: variable \"short\" has no term \"big\""
+        );
+    }
+
+    #[test]
+    fn format() {
+        let (inputs, outputs) = vars();
+        for text in [
+            "IF rsi IS low AND bb IS NOT very high THEN long IS strong WITH 0.8",
+            "IF (rsi IS high OR bb IS low) AND NOT (rsi IS low OR bb IS somewhat low) THEN short IS weak",
+            "IF very (rsi IS high AND bb IS high) THEN long IS weak AND short IS strong",
+            "IF rsi IS any THEN long IS weak",
+        ] {
+            let rule = parse_rule(text, &inputs, &outputs).unwrap();
+            assert_eq!(format_rule(&rule, &inputs, &outputs), text);
+        }
+    }
+}
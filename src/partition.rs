@@ -0,0 +1,289 @@
+// linguistic variables with terms placed automatically, evenly over the universe or at quantiles of data
+
+use crate::error::{or_panic, FuzzyError};
+use crate::set::{FuzzySet, LinguisticVar};
+use crate::shape::*;
+
+/// shape of the terms built by `LinguisticVar::uniform` and `LinguisticVar::quantiles`.
+/// neighbouring terms cross at 0.5, the triangular and trapezoidal ones sum to 1 everywhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    Triangular,
+    Trapezoidal, // core over the middle half of the space to each neighbour
+    Gaussian,
+}
+
+/// whether `x` lies in `universe`. samples outside it are treated as missing rather than
+/// extreme by `LinguisticVar::quantiles`, `LinguisticVar::clustered` and `wang_mendel`:
+/// `data::rsi` and `data::bb` fill their warm-up with -1.0, so the raw series can be passed
+/// in as is. NaN is never inside
+pub fn in_universe(universe: &[f64], x: f64) -> bool {
+    match (universe.first(), universe.last()) {
+        (Some(lo), Some(hi)) => (*lo..=*hi).contains(&x),
+        _ => false,
+    }
+}
+
+/// low/medium/high style names for 2, 3 and 5 terms, term0, term1, ... otherwise
+pub(crate) fn auto_names(k: usize) -> Vec<String> {
+    let names: &[&str] = match k {
+        2 => &["low", "high"],
+        3 => &["low", "medium", "high"],
+        5 => &["verylow", "low", "medium", "high", "veryhigh"],
+        _ => return (0..k).map(|i| format!("term{}", i)).collect(),
+    };
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+/// standard deviation of a gaussian that is 0.5 at distance `gap` / 2 from its centre
+fn half_at(gap: f64) -> f64 {
+    gap / (2.0 * (2.0 * 2f64.ln()).sqrt())
+}
+
+/// gaps that only differ by rounding, evenly spaced peaks get the symmetric shapes
+fn even(l: f64, r: f64) -> bool {
+    (l - r).abs() <= 1e-9 * l.max(r)
+}
+
+/// shape of term i of a partition with peaks at `centres`. the first and last terms are
+/// shoulders, staying at 1 from their peak outwards, even outside the universe
+fn term_shape(kind: Partition, centres: &[f64], i: usize) -> Box<dyn Shape> {
+    let k = centres.len();
+    let c = centres[i];
+    let left = (i > 0).then(|| c - centres[i - 1]);
+    let right = (i + 1 < k).then(|| centres[i + 1] - c);
+    match (kind, left, right) {
+        (Partition::Triangular, None, Some(r)) => Box::new(points(vec![(c, 1.0), (c + r, 0.0)])),
+        (Partition::Triangular, Some(l), None) => Box::new(points(vec![(c - l, 0.0), (c, 1.0)])),
+        (Partition::Triangular, Some(l), Some(r)) if even(l, r) => Box::new(triangular(c, 1.0, l)),
+        (Partition::Triangular, Some(l), Some(r)) => {
+            Box::new(points(vec![(c - l, 0.0), (c, 1.0), (c + r, 0.0)]))
+        }
+        (Partition::Trapezoidal, None, Some(r)) => {
+            Box::new(points(vec![(c + r / 4.0, 1.0), (c + r * 3.0 / 4.0, 0.0)]))
+        }
+        (Partition::Trapezoidal, Some(l), None) => {
+            Box::new(points(vec![(c - l * 3.0 / 4.0, 0.0), (c - l / 4.0, 1.0)]))
+        }
+        (Partition::Trapezoidal, Some(l), Some(r)) => Box::new(trapezoidal(
+            c - l * 3.0 / 4.0,
+            c - l / 4.0,
+            c + r / 4.0,
+            c + r * 3.0 / 4.0,
+            1.0,
+        )),
+        // gauss2 is flat between its two centres, f64::MIN/MAX leave a shoulder open-ended
+        (Partition::Gaussian, None, Some(r)) => {
+            Box::new(gauss2(f64::MIN, half_at(r), c, half_at(r)))
+        }
+        (Partition::Gaussian, Some(l), None) => {
+            Box::new(gauss2(c, half_at(l), f64::MAX, half_at(l)))
+        }
+        (Partition::Gaussian, Some(l), Some(r)) if even(l, r) => Box::new(gaussian(c, half_at(l))),
+        (Partition::Gaussian, Some(l), Some(r)) => Box::new(gauss2(c, half_at(l), c, half_at(r))),
+        (_, None, None) => unreachable!("a partition has at least two terms"),
+    }
+}
+
+fn build(kind: Partition, centres: &[f64], universe: Vec<f64>) -> LinguisticVar {
+    let names = auto_names(centres.len());
+    let sets = (0..centres.len())
+        .map(|i| {
+            let shape = term_shape(kind, centres, i);
+            FuzzySet::analytic(&universe, shape.as_ref(), names[i].clone())
+        })
+        .collect();
+    LinguisticVar {
+        name: String::new(),
+        sets,
+        universe,
+    }
+}
+
+impl LinguisticVar {
+    /// `k` terms peaking at evenly spaced points from the start to the end of the universe.
+    /// terms are named low/medium/high style, see `term_names` to supply names
+    pub fn uniform(kind: Partition, k: usize, universe: Vec<f64>) -> LinguisticVar {
+        or_panic(LinguisticVar::try_uniform(kind, k, universe))
+    }
+
+    pub fn try_uniform(
+        kind: Partition,
+        k: usize,
+        universe: Vec<f64>,
+    ) -> Result<LinguisticVar, FuzzyError> {
+        if k < 2 {
+            return Err(FuzzyError::InvalidRange(
+                "a partition needs at least two terms".into(),
+            ));
+        }
+        let (lo, hi) = match (universe.first(), universe.last()) {
+            (Some(lo), Some(hi)) if lo < hi => (*lo, *hi),
+            _ => {
+                return Err(FuzzyError::InvalidRange(
+                    "a partition needs a universe with at least two points".into(),
+                ))
+            }
+        };
+        let step = (hi - lo) / (k - 1) as f64;
+        let centres: Vec<f64> = (0..k).map(|i| lo + step * i as f64).collect();
+        Ok(build(kind, &centres, universe))
+    }
+
+    /// `k` terms peaking at the 0, 1/(k-1), ..., 1 quantiles of `data`, so each term
+    /// covers about as many samples. values outside the universe are ignored, see `in_universe`
+    pub fn quantiles(kind: Partition, k: usize, data: &[f64], universe: Vec<f64>) -> LinguisticVar {
+        or_panic(LinguisticVar::try_quantiles(kind, k, data, universe))
+    }
+
+    pub fn try_quantiles(
+        kind: Partition,
+        k: usize,
+        data: &[f64],
+        universe: Vec<f64>,
+    ) -> Result<LinguisticVar, FuzzyError> {
+        if k < 2 {
+            return Err(FuzzyError::InvalidRange(
+                "a partition needs at least two terms".into(),
+            ));
+        }
+        if universe.is_empty() {
+            return Err(FuzzyError::InvalidRange("empty universe".into()));
+        }
+        let mut sorted: Vec<f64> = data
+            .iter()
+            .copied()
+            .filter(|x| in_universe(&universe, *x))
+            .collect();
+        if sorted.is_empty() {
+            return Err(FuzzyError::InvalidRange(
+                "quantiles need at least one value inside the universe".into(),
+            ));
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let centres: Vec<f64> = (0..k)
+            .map(|i| {
+                let pos = (sorted.len() - 1) as f64 * i as f64 / (k - 1) as f64;
+                let (j, t) = (pos.floor() as usize, pos.fract());
+                match sorted.get(j + 1) {
+                    Some(next) => sorted[j] + (next - sorted[j]) * t,
+                    None => sorted[j],
+                }
+            })
+            .collect();
+        if !centres.windows(2).all(|w| w[0] < w[1]) {
+            return Err(FuzzyError::InvalidRange(
+                "too many terms for the distinct values in the data".into(),
+            ));
+        }
+        Ok(build(kind, &centres, universe))
+    }
+
+    /// rename the terms in order, e.g. after `uniform`
+    pub fn term_names(self, names: &[&str]) -> LinguisticVar {
+        or_panic(self.try_term_names(names))
+    }
+
+    pub fn try_term_names(mut self, names: &[&str]) -> Result<LinguisticVar, FuzzyError> {
+        if names.len() != self.sets.len() {
+            return Err(FuzzyError::Arity(
+                "term names".into(),
+                self.sets.len(),
+                names.len(),
+            ));
+        }
+        for (set, name) in self.sets.iter_mut().zip(names.iter()) {
+            set.name = name.to_string();
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::set::arange;
+
+    #[test]
+    fn uniform_partitions() {
+        for kind in [
+            Partition::Triangular,
+            Partition::Trapezoidal,
+            Partition::Gaussian,
+        ] {
+            let var = LinguisticVar::uniform(kind, 3, arange(0f64, 100f64, 0.5));
+            let names: Vec<&str> = var.sets.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(names, ["low", "medium", "high"]);
+
+            assert_eq!(var.term("low").degree_of(0.0), 1.0);
+            assert_eq!(var.term("medium").degree_of(50.0), 1.0);
+            // shoulders stay at 1 past the universe
+            assert_eq!(var.term("low").degree_of(-20.0), 1.0);
+            assert_eq!(var.term("high").degree_of(130.0), 1.0);
+            // neighbours cross half way
+            assert!((var.term("low").degree_of(25.0) - 0.5).abs() < 1e-9);
+            assert!((var.term("medium").degree_of(75.0) - 0.5).abs() < 1e-9);
+
+            let q = var.quality();
+            assert!(q.ordered);
+            assert!(q.uncovered.is_empty());
+            assert_eq!(q.ruspini, kind != Partition::Gaussian, "{:?}", kind);
+        }
+
+        let var = LinguisticVar::uniform(Partition::Triangular, 4, arange(0f64, 30f64, 1.0))
+            .term_names(&["a", "b", "c", "d"]);
+        assert_eq!(var.term("c").degree_of(20.0), 1.0);
+        assert_eq!(var.sets[1].shape.as_ref().unwrap().kind(), "triangular");
+        assert!(LinguisticVar::try_uniform(Partition::Gaussian, 1, arange(0.0, 1.0, 0.1)).is_err());
+        assert!(var.try_term_names(&["a"]).is_err());
+    }
+
+    #[test]
+    fn quantile_partitions() {
+        // an rsi-like series that spends most of its time between 40 and 60, after a warm-up
+        let mut rsi = vec![-1.0; 15];
+        rsi.push(f64::NAN);
+        rsi.extend((0..=100).map(|i| 50.0 + 30.0 * ((i as f64 - 50.0) / 50.0).powi(3)));
+
+        let var =
+            LinguisticVar::quantiles(Partition::Triangular, 3, &rsi, arange(0f64, 100f64, 0.5));
+        assert_eq!(var.term("low").degree_of(20.0), 1.0);
+        assert_eq!(var.term("medium").degree_of(50.0), 1.0);
+        assert_eq!(var.term("high").degree_of(80.0), 1.0);
+        assert!(var.quality().ruspini);
+
+        // the middle quartiles are much closer to the median than the extremes
+        let var = LinguisticVar::quantiles(Partition::Gaussian, 5, &rsi, arange(0f64, 100f64, 0.5));
+        assert!((var.term("low").mom_defuzz() - 50.0).abs() < 5.0);
+        assert!(var.quality().ordered);
+
+        assert!(LinguisticVar::try_quantiles(
+            Partition::Triangular,
+            3,
+            &[1.0, 1.0, 1.0],
+            arange(0.0, 2.0, 0.1)
+        )
+        .is_err());
+        assert!(
+            LinguisticVar::try_quantiles(Partition::Triangular, 3, &[], arange(0.0, 2.0, 0.1))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn warm_up() {
+        let rsi = data::rsi(&data::read_csv("eth.csv"), 14);
+        let universe = arange(0f64, 100f64, 0.5);
+        assert_eq!(rsi[..15], [-1.0; 15]);
+        assert!(rsi[..15].iter().all(|x| !in_universe(&universe, *x)));
+        assert!(rsi[15..].iter().all(|x| in_universe(&universe, *x)));
+        assert!(!in_universe(&universe, f64::NAN));
+        assert!(!in_universe(&[], 0.0));
+
+        // the warm-up doesn't drag the lowest term out of the universe
+        let lowest = rsi[15..].iter().copied().fold(f64::INFINITY, f64::min);
+        let var = LinguisticVar::quantiles(Partition::Triangular, 3, &rsi, universe);
+        assert_eq!(var.term("low").degree_of(lowest), 1.0);
+    }
+}
@@ -0,0 +1,191 @@
+// how well the terms of a linguistic variable cover its universe, measured on the sampled points
+
+use crate::error::FuzzyError;
+use crate::set::LinguisticVar;
+use std::fmt;
+
+/// tolerance for membership sums to count as 1 in a ruspini partition
+const RUSPINI_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone)]
+pub struct Quality {
+    pub uncovered: Vec<(f64, f64)>, // intervals of the universe where every term is 0
+    pub min_total: f64,             // smallest sum of memberships over the terms
+    pub max_total: f64,             // largest sum of memberships over the terms
+    pub epsilon: f64,               // every point belongs to some term with at least this degree
+    pub peaks: Vec<(String, f64)>,  // (term, mean of its maxima), in declaration order
+    pub ordered: bool,              // peaks strictly increase in declaration order
+    pub ruspini: bool,              // memberships sum to 1 everywhere
+}
+
+impl Quality {
+    /// true if every point belongs to some term with a degree of at least `epsilon`
+    pub fn is_complete(&self, epsilon: f64) -> bool {
+        self.epsilon >= epsilon
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "uncovered")?;
+        if self.uncovered.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for (lo, hi) in self.uncovered.iter() {
+            writeln!(f, "  [{}, {}]", lo, hi)?;
+        }
+        writeln!(
+            f,
+            "total membership in [{:.3}, {:.3}]",
+            self.min_total, self.max_total
+        )?;
+        writeln!(f, "{:.3}-complete", self.epsilon)?;
+        let peaks: Vec<String> = self
+            .peaks
+            .iter()
+            .map(|(term, peak)| format!("{} {}", term, peak))
+            .collect();
+        writeln!(
+            f,
+            "peaks {}: {}",
+            match self.ordered {
+                true => "ordered",
+                false => "out of order",
+            },
+            peaks.join(", ")
+        )?;
+        writeln!(
+            f,
+            "{}a ruspini partition",
+            match self.ruspini {
+                true => "",
+                false => "not ",
+            }
+        )
+    }
+}
+
+impl LinguisticVar {
+    /// coverage diagnostics of the terms over the universe
+    pub fn quality(&self) -> Quality {
+        let mut uncovered = vec![];
+        let mut gap_start: Option<f64> = None;
+        let (mut min_total, mut max_total, mut epsilon) = (f64::INFINITY, 0f64, f64::INFINITY);
+        for (k, x) in self.universe.iter().enumerate() {
+            let degrees = self.sets.iter().map(|set| set.membership[k]);
+            let total: f64 = degrees.clone().sum();
+            let best = degrees.fold(0f64, f64::max);
+            min_total = min_total.min(total);
+            max_total = max_total.max(total);
+            epsilon = epsilon.min(best);
+
+            match (best == 0.0, gap_start) {
+                (true, None) => gap_start = Some(*x),
+                (false, Some(lo)) => {
+                    uncovered.push((lo, self.universe[k - 1]));
+                    gap_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(lo) = gap_start {
+            uncovered.push((lo, self.universe[self.universe.len() - 1]));
+        }
+        if self.universe.is_empty() {
+            (min_total, epsilon) = (0.0, 0.0);
+        }
+
+        let peaks: Vec<(String, f64)> = self
+            .sets
+            .iter()
+            .map(|set| (set.name.clone(), set.mom_defuzz()))
+            .collect();
+        Quality {
+            uncovered,
+            min_total,
+            max_total,
+            epsilon,
+            ordered: peaks.windows(2).all(|w| w[0].1 < w[1].1),
+            peaks,
+            ruspini: (min_total - 1.0).abs() <= RUSPINI_TOLERANCE
+                && (max_total - 1.0).abs() <= RUSPINI_TOLERANCE,
+        }
+    }
+
+    /// fails unless every point belongs to some term with a degree of at least `epsilon`
+    /// and the terms are declared in increasing order, e.g.
+    /// `LinguisticVar::new(terms, universe).validated(0.5)?`
+    pub fn validated(self, epsilon: f64) -> Result<LinguisticVar, FuzzyError> {
+        let quality = self.quality();
+        if !quality.is_complete(epsilon) {
+            return Err(FuzzyError::Incomplete(
+                self.name.clone(),
+                quality.epsilon,
+                epsilon,
+            ));
+        }
+        if !quality.ordered {
+            return Err(FuzzyError::Unordered(self.name.clone()));
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::arange;
+    use crate::shape::*;
+
+    #[test]
+    fn diagnostics() {
+        // the rsi variable of main.rs covers everything, but around 25 and 75
+        // no term goes above 1/6
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 30f64), "low"),
+                (&triangular(50f64, 1.0, 30f64), "medium"),
+                (&triangular(100f64, 1.0, 30f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.5),
+        );
+        let q = rsi.quality();
+        assert!(q.uncovered.is_empty());
+        assert!((q.epsilon - 1.0 / 6.0).abs() < 1e-9, "{}", q.epsilon);
+        assert!((q.min_total - 1.0 / 3.0).abs() < 1e-9, "{}", q.min_total);
+        assert!(q.ordered);
+        assert!(!q.ruspini);
+        assert!(rsi.clone().validated(0.1).is_ok());
+        assert!(matches!(
+            rsi.validated(0.2),
+            Err(FuzzyError::Incomplete(_, _, _))
+        ));
+
+        let narrow = LinguisticVar::new(
+            vec![
+                (&triangular(50f64, 1.0, 20f64), "medium"),
+                (&triangular(0f64, 1.0, 20f64), "low"),
+            ],
+            arange(0f64, 100f64, 1.0),
+        )
+        .named("rsi");
+        let q = narrow.quality();
+        assert_eq!(q.uncovered, vec![(20.0, 30.0), (70.0, 100.0)]);
+        assert_eq!(q.epsilon, 0.0);
+        assert!(!q.ordered);
+        assert!(q.to_string().contains("[70, 100]"));
+        assert!(narrow.validated(0.0).is_err());
+
+        let ruspini = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(50f64, 1.0, 50f64), "medium"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.5),
+        );
+        let q = ruspini.quality();
+        assert!(q.ruspini);
+        assert!(q.to_string().contains("\na ruspini partition"));
+    }
+}
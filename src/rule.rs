@@ -1,9 +1,533 @@
+use crate::antecedent::Antecedent;
+use crate::defuzz::Defuzzifier;
+use crate::error::{or_panic, FuzzyError};
+use crate::ops::*;
+use crate::parse::{parse_rule, parse_rules, ParseError, Terms};
 use crate::set::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub antecedent: Antecedent,
+    #[serde(with = "slots")]
+    pub consequent: Vec<Option<String>>, // one term for each output variable, None if the rule doesn't set it
+    #[serde(default = "full_weight")]
+    pub weight: f64, // scales the firing strength, in [0, 1]
+}
+
+fn full_weight() -> f64 {
+    1.0
+}
+
+/// consequents are written like the slots of `add_rule`, "any" for an output the rule doesn't
+/// set, since formats like TOML have no null
+mod slots {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        consequent: &[Option<String>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        consequent
+            .iter()
+            .map(|term| term.as_deref().unwrap_or("any"))
+            .collect::<Vec<&str>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Option<String>>, D::Error> {
+        Ok(Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|term| match term.as_str() {
+                "any" | "-" => None,
+                _ => Some(term),
+            })
+            .collect())
+    }
+}
+
+/// consequent of `add_rule`, "any" or "-" leaves that output out of the rule
+fn consequent_slots(res: &[&str]) -> Vec<Option<String>> {
+    res.iter()
+        .map(|term| match *term {
+            "any" | "-" => None,
+            _ => Some(term.to_string()),
+        })
+        .collect()
+}
+
+/// rule weights scale the firing strength and must be in [0, 1]
+fn check_weight(weight: f64) -> Result<(), FuzzyError> {
+    if !(0.0..=1.0).contains(&weight) {
+        return Err(FuzzyError::InvalidWeight(weight));
+    }
+    Ok(())
+}
+
+/// rules of an engine, checked against its input and output variables which the engine passes
+/// in. every engine stores its rules in one so they're validated the same way
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RuleBase {
+    rules: Vec<Rule>,
+    terms: Vec<Vec<Option<usize>>>, // index of each rule's consequent terms in their outputs
+}
+
+impl RuleBase {
+    pub(crate) fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// position of every rule's consequent terms in their output variable, None if left out
+    pub(crate) fn terms(&self) -> &[Vec<Option<usize>>] {
+        &self.terms
+    }
+
+    /// fails if the weight isn't in [0, 1] or the rule refers to a missing variable or term
+    pub(crate) fn try_push<I: Terms, O: Terms>(
+        &mut self,
+        inputs: &[I],
+        outputs: &[O],
+        rule: Rule,
+    ) -> Result<(), FuzzyError> {
+        check_weight(rule.weight)?;
+        rule.antecedent.try_check(inputs)?;
+        if rule.consequent.len() != outputs.len() {
+            return Err(FuzzyError::Arity(
+                "consequent".into(),
+                outputs.len(),
+                rule.consequent.len(),
+            ));
+        }
+        for (var, term) in outputs.iter().zip(rule.consequent.iter()) {
+            match term {
+                Some(term) if !var.has_term(term) => {
+                    return Err(FuzzyError::UnknownTerm(var.name().into(), term.clone()))
+                }
+                _ => (),
+            }
+        }
+        self.insert(outputs, rule);
+        Ok(())
+    }
+
+    /// append a checked rule, resolving its consequent terms once
+    fn insert<O: Terms>(&mut self, outputs: &[O], rule: Rule) {
+        let terms = outputs
+            .iter()
+            .zip(rule.consequent.iter())
+            .map(|(var, term)| var.term_index(term.as_ref()?))
+            .collect();
+        self.terms.push(terms);
+        self.rules.push(rule);
+    }
+
+    /// one condition per input joined by AND and one slot per output
+    pub(crate) fn try_add<I: Terms, O: Terms>(
+        &mut self,
+        inputs: &[I],
+        outputs: &[O],
+        cond: &[&str],
+        res: &[&str],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        if cond.len() != inputs.len() {
+            return Err(FuzzyError::Arity(
+                "conditions".into(),
+                inputs.len(),
+                cond.len(),
+            ));
+        }
+        let cond = Antecedent::try_from_slots(cond)?;
+        self.try_add_expr(inputs, outputs, cond, res, weight)
+    }
+
+    pub(crate) fn try_add_expr<I: Terms, O: Terms>(
+        &mut self,
+        inputs: &[I],
+        outputs: &[O],
+        cond: Antecedent,
+        res: &[&str],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        let rule = Rule {
+            antecedent: cond,
+            consequent: consequent_slots(res),
+            weight,
+        };
+        self.try_push(inputs, outputs, rule)
+    }
+
+    /// conditions and results as (variable name, term), unlisted variables are "any"
+    pub(crate) fn try_add_named<I: Terms, O: Terms>(
+        &mut self,
+        inputs: &[I],
+        outputs: &[O],
+        cond: &[(&str, &str)],
+        res: &[(&str, &str)],
+    ) -> Result<(), FuzzyError> {
+        fn slots<'a, V: Terms>(
+            vars: &[V],
+            named: &[(&str, &'a str)],
+        ) -> Result<Vec<&'a str>, FuzzyError> {
+            let mut slots = vec!["any"; vars.len()];
+            for (name, term) in named {
+                match vars.iter().position(|var| var.name() == *name) {
+                    Some(i) => slots[i] = term,
+                    None => return Err(FuzzyError::UnknownVariable(name.to_string())),
+                }
+            }
+            Ok(slots)
+        }
+        let (cond, res) = (slots(inputs, cond)?, slots(outputs, res)?);
+        self.try_add(inputs, outputs, &cond, &res, 1.0)
+    }
+
+    pub(crate) fn try_set_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        check_weight(weight)?;
+        match self.rules.get_mut(index) {
+            Some(rule) => rule.weight = weight,
+            None => return Err(FuzzyError::UnknownRule(index)),
+        }
+        Ok(())
+    }
+
+    /// see `parse::parse_rule` for the syntax
+    pub(crate) fn add_str<I: Terms, O: Terms>(
+        &mut self,
+        inputs: &[I],
+        outputs: &[O],
+        text: &str,
+    ) -> Result<(), ParseError> {
+        let rule = parse_rule(text, inputs, outputs)?;
+        self.insert(outputs, rule);
+        Ok(())
+    }
+
+    /// one rule per line, see `parse::parse_rules`
+    pub(crate) fn add_strs<I: Terms, O: Terms>(
+        &mut self,
+        inputs: &[I],
+        outputs: &[O],
+        text: &str,
+    ) -> Result<(), ParseError> {
+        let rules = parse_rules(text, inputs, outputs)?;
+        rules
+            .into_iter()
+            .for_each(|rule| self.insert(outputs, rule));
+        Ok(())
+    }
+}
+
+impl Serialize for RuleBase {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rules.serialize(serializer)
+    }
+}
+
+/// input values ordered like `vars` from (variable name, value) pairs
+pub(crate) fn ordered_inputs<V: Terms>(
+    vars: &[V],
+    inputs: &[(&str, f64)],
+) -> Result<Vec<f64>, FuzzyError> {
+    vars.iter()
+        .map(|var| match inputs.iter().find(|x| x.0 == var.name()) {
+            Some(x) => Ok(x.1),
+            None => Err(FuzzyError::UnknownVariable(var.name().to_string())),
+        })
+        .collect()
+}
+
+/// fuzzy engine with its inputs and outputs only known at runtime,
+/// variables can be addressed by position or by their `name`
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "EngineDef")]
+pub struct DynFuzzyEngine {
+    #[serde(rename = "inputs")]
+    inputs_var: Vec<LinguisticVar>,
+    #[serde(rename = "outputs")]
+    outputs_var: Vec<LinguisticVar>,
+    rules: RuleBase,
+    defuzzifier: Defuzzifier,
+    #[serde(rename = "operators")]
+    ops: Operators,
+}
+
+/// deserialized form of DynFuzzyEngine, rules are checked before building the engine
+#[derive(Deserialize)]
+struct EngineDef {
+    inputs: Vec<LinguisticVar>,
+    outputs: Vec<LinguisticVar>,
+    rules: Vec<Rule>,
+    defuzzifier: Defuzzifier,
+    #[serde(default)]
+    operators: Operators,
+}
+
+impl TryFrom<EngineDef> for DynFuzzyEngine {
+    type Error = FuzzyError;
+
+    fn try_from(def: EngineDef) -> Result<Self, Self::Error> {
+        let mut engine = DynFuzzyEngine::new(def.inputs, def.outputs);
+        engine.set_defuzzifier(def.defuzzifier);
+        engine.try_set_operators(def.operators)?;
+        for rule in def.rules {
+            engine.try_push_rule(rule)?;
+        }
+        Ok(engine)
+    }
+}
+
+impl DynFuzzyEngine {
+    pub fn new(inputs_var: Vec<LinguisticVar>, outputs_var: Vec<LinguisticVar>) -> DynFuzzyEngine {
+        DynFuzzyEngine {
+            inputs_var,
+            outputs_var,
+            rules: RuleBase::default(),
+            defuzzifier: Defuzzifier::Centroid,
+            ops: Operators::default(),
+        }
+    }
+
+    /// t-norm, s-norm, implication and aggregation used by `calculate`, min/max by default
+    pub fn set_operators(&mut self, ops: Operators) {
+        or_panic(self.try_set_operators(ops))
+    }
+
+    /// fails for a Yager norm whose p isn't > 0
+    pub fn try_set_operators(&mut self, ops: Operators) -> Result<(), FuzzyError> {
+        ops.check()?;
+        self.ops = ops;
+        Ok(())
+    }
+
+    /// method used by `defuzzify`, centroid by default
+    pub fn set_defuzzifier(&mut self, defuzzifier: Defuzzifier) {
+        self.defuzzifier = defuzzifier;
+    }
+
+    pub fn inputs(&self) -> &[LinguisticVar] {
+        &self.inputs_var
+    }
+
+    pub fn outputs(&self) -> &[LinguisticVar] {
+        &self.outputs_var
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        self.rules.rules()
+    }
+
+    /// variables whose terms are rewritten in place by the tuners, names must stay the same
+    pub(crate) fn vars_mut(&mut self) -> (&mut [LinguisticVar], &mut [LinguisticVar]) {
+        (&mut self.inputs_var, &mut self.outputs_var)
+    }
+
+    pub fn operators(&self) -> Operators {
+        self.ops
+    }
+
+    pub fn defuzzifier(&self) -> Defuzzifier {
+        self.defuzzifier
+    }
+
+    pub fn input_index(&self, name: &str) -> Option<usize> {
+        self.inputs_var.iter().position(|v| v.name == name)
+    }
+
+    pub fn output_index(&self, name: &str) -> Option<usize> {
+        self.outputs_var.iter().position(|v| v.name == name)
+    }
+
+    /// add an already built rule, panics if it refers to a missing term
+    pub fn push_rule(&mut self, rule: Rule) {
+        or_panic(self.try_push_rule(rule))
+    }
+
+    pub fn try_push_rule(&mut self, rule: Rule) -> Result<(), FuzzyError> {
+        self.rules
+            .try_push(&self.inputs_var, &self.outputs_var, rule)
+    }
+
+    /// one condition per input joined by AND, each can be "any" or use
+    /// "not" and hedges, e.g. `["very low", "any"]`
+    pub fn add_rule(&mut self, cond: &[&str], res: &[&str]) {
+        or_panic(self.try_add_rule(cond, res))
+    }
+
+    pub fn try_add_rule(&mut self, cond: &[&str], res: &[&str]) -> Result<(), FuzzyError> {
+        self.try_add_rule_weighted(cond, res, 1.0)
+    }
+
+    /// like `add_rule` with the firing strength scaled by `weight` in [0, 1]
+    pub fn add_rule_weighted(&mut self, cond: &[&str], res: &[&str], weight: f64) {
+        or_panic(self.try_add_rule_weighted(cond, res, weight))
+    }
+
+    pub fn try_add_rule_weighted(
+        &mut self,
+        cond: &[&str],
+        res: &[&str],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add(&self.inputs_var, &self.outputs_var, cond, res, weight)
+    }
+
+    /// weight of the rule at `index`, in the order rules were added
+    pub fn set_rule_weight(&mut self, index: usize, weight: f64) {
+        or_panic(self.try_set_rule_weight(index, weight))
+    }
+
+    pub fn try_set_rule_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        self.rules.try_set_weight(index, weight)
+    }
+
+    /// rule with an arbitrary condition, e.g. `is(0, "low").or(!is(1, "short"))`
+    pub fn add_rule_expr(&mut self, cond: Antecedent, res: &[&str]) {
+        or_panic(self.try_add_rule_expr(cond, res))
+    }
+
+    pub fn try_add_rule_expr(&mut self, cond: Antecedent, res: &[&str]) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add_expr(&self.inputs_var, &self.outputs_var, cond, res, 1.0)
+    }
+
+    /// conditions and results as (variable name, term), inputs that aren't
+    /// listed are "any" and outputs that aren't listed are left out of the rule
+    pub fn add_rule_named(&mut self, cond: &[(&str, &str)], res: &[(&str, &str)]) {
+        or_panic(self.try_add_rule_named(cond, res))
+    }
+
+    pub fn try_add_rule_named(
+        &mut self,
+        cond: &[(&str, &str)],
+        res: &[(&str, &str)],
+    ) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add_named(&self.inputs_var, &self.outputs_var, cond, res)
+    }
+
+    /// parse a rule like `IF rsi IS low AND bb IS NOT short THEN long IS verystrong WITH 0.8`,
+    /// variables are looked up by their `name`. see `parse::parse_rule` for the syntax
+    pub fn add_rule_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.rules
+            .add_str(&self.inputs_var, &self.outputs_var, text)
+    }
+
+    /// one rule per line, see `parse::parse_rules`
+    pub fn add_rules_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.rules
+            .add_strs(&self.inputs_var, &self.outputs_var, text)
+    }
+
+    /// one value per input variable, in order
+    pub fn calculate(&self, inputs: &[f64]) -> Vec<FuzzySet> {
+        or_panic(self.try_calculate(inputs))
+    }
+
+    /// fails if there isn't exactly one value per input variable
+    pub fn try_calculate(&self, inputs: &[f64]) -> Result<Vec<FuzzySet>, FuzzyError> {
+        let mut strengths = vec![];
+        self.strengths_into(inputs, &mut strengths)?;
+        let mut res = self.empty_outputs();
+        self.aggregate_into(&strengths, &mut res);
+        Ok(res)
+    }
+
+    /// crisp value of every output variable using the configured defuzzifier
+    pub fn defuzzify(&self, inputs: &[f64]) -> Vec<f64> {
+        or_panic(self.try_defuzzify(inputs))
+    }
+
+    pub fn try_defuzzify(&self, inputs: &[f64]) -> Result<Vec<f64>, FuzzyError> {
+        let mut strengths = vec![];
+        self.strengths_into(inputs, &mut strengths)?;
+        let mut res = self.empty_outputs();
+        self.aggregate_into(&strengths, &mut res);
+        let mut crisp = vec![];
+        self.crisp_into(&strengths, &res, &mut crisp);
+        Ok(crisp)
+    }
+
+    /// inputs given as (variable name, value), every input must be listed
+    pub fn defuzzify_named(&self, inputs: &[(&str, f64)]) -> Vec<f64> {
+        or_panic(self.try_defuzzify_named(inputs))
+    }
+
+    pub fn try_defuzzify_named(&self, inputs: &[(&str, f64)]) -> Result<Vec<f64>, FuzzyError> {
+        self.try_defuzzify(&ordered_inputs(&self.inputs_var, inputs)?)
+    }
+
+    /// firing strength of every rule, weight included
+    pub(crate) fn strengths_into(
+        &self,
+        inputs: &[f64],
+        strengths: &mut Vec<f64>,
+    ) -> Result<(), FuzzyError> {
+        if inputs.len() != self.inputs_var.len() {
+            return Err(FuzzyError::Arity(
+                "inputs".into(),
+                self.inputs_var.len(),
+                inputs.len(),
+            ));
+        }
+        strengths.clear();
+        strengths.extend(
+            self.rules.rules().iter().map(|rule| {
+                rule.weight * rule.antecedent.eval(&self.inputs_var, inputs, &self.ops)
+            }),
+        );
+        Ok(())
+    }
+
+    /// one zero membership set per output variable, filled by `aggregate_into`
+    pub(crate) fn empty_outputs(&self) -> Vec<FuzzySet> {
+        self.outputs_var
+            .iter()
+            .map(|var| FuzzySet::empty(&var.universe, "".into()))
+            .collect()
+    }
+
+    /// implicate every consequent and aggregate them in place, `res` comes from `empty_outputs`
+    pub(crate) fn aggregate_into(&self, strengths: &[f64], res: &mut [FuzzySet]) {
+        for set in res.iter_mut() {
+            set.membership.iter_mut().for_each(|m| *m = 0.0);
+        }
+        for (terms, aj) in self.rules.terms().iter().zip(strengths.iter()) {
+            for (i, term) in terms.iter().enumerate() {
+                if let Some(t) = term {
+                    let term = &self.outputs_var[i].sets[*t];
+                    for (m, t) in res[i].membership.iter_mut().zip(term.membership.iter()) {
+                        *m = self
+                            .ops
+                            .aggregation
+                            .apply(*m, self.ops.implication.apply(*aj, *t));
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn crisp_into(&self, strengths: &[f64], res: &[FuzzySet], crisp: &mut Vec<f64>) {
+        crisp.clear();
+        for (i, set) in res.iter().enumerate() {
+            let fired = self
+                .rules
+                .terms()
+                .iter()
+                .zip(strengths.iter())
+                .filter_map(|(terms, aj)| Some((&self.outputs_var[i].sets[terms[i]?], *aj)));
+            crisp.push(self.defuzzifier.defuzzify_fired(set, fired));
+        }
+    }
+}
+
+/// DynFuzzyEngine with the number of inputs (N) and outputs (M) checked at compile time
+#[derive(Clone)]
 pub struct FuzzyEngine<const N: usize, const M: usize> {
-    inputs_var: [LinguisticVar; N],
-    outputs_var: [LinguisticVar; M],
-    rules: Vec<(Vec<String>, Vec<String>)>, // list of ([input1_term, input2_term, ...] -> output_term)
+    engine: DynFuzzyEngine,
 }
 
 impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
@@ -12,62 +536,164 @@ impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
         output_var: [LinguisticVar; M],
     ) -> FuzzyEngine<N, M> {
         FuzzyEngine {
-            inputs_var,
-            outputs_var: output_var,
-            rules: Vec::<(Vec<String>, Vec<String>)>::new(),
+            engine: DynFuzzyEngine::new(Vec::from(inputs_var), Vec::from(output_var)),
         }
     }
 
-    pub fn add_rule(&mut self, cond: [&str; N], res: [&str; M]) {
-        for i in 0..self.inputs_var.len() {
-            self.inputs_var[i].term(&cond[i]); // check if term "cond[i]" exist
-        }
-        for i in 0..self.outputs_var.len() {
-            self.outputs_var[i].term(&res[i]); // term() check if term "res" is exist
+    /// None if the engine doesn't have N inputs and M outputs
+    pub fn from_dyn(engine: DynFuzzyEngine) -> Option<FuzzyEngine<N, M>> {
+        if engine.inputs_var.len() != N || engine.outputs_var.len() != M {
+            return None;
         }
+        Some(FuzzyEngine { engine })
+    }
+
+    pub fn as_dyn(&self) -> &DynFuzzyEngine {
+        &self.engine
+    }
+
+    pub fn into_dyn(self) -> DynFuzzyEngine {
+        self.engine
+    }
+
+    pub(crate) fn as_dyn_mut(&mut self) -> &mut DynFuzzyEngine {
+        &mut self.engine
+    }
+
+    /// t-norm, s-norm, implication and aggregation used by `calculate`, min/max by default
+    pub fn set_operators(&mut self, ops: Operators) {
+        self.engine.set_operators(ops);
+    }
+
+    pub fn try_set_operators(&mut self, ops: Operators) -> Result<(), FuzzyError> {
+        self.engine.try_set_operators(ops)
+    }
+
+    /// method used by `defuzzify`, centroid by default
+    pub fn set_defuzzifier(&mut self, defuzzifier: Defuzzifier) {
+        self.engine.set_defuzzifier(defuzzifier);
+    }
+
+    pub fn inputs(&self) -> &[LinguisticVar; N] {
+        self.engine.inputs().try_into().unwrap()
+    }
+
+    pub fn outputs(&self) -> &[LinguisticVar; M] {
+        self.engine.outputs().try_into().unwrap()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        self.engine.rules()
+    }
+
+    pub fn operators(&self) -> Operators {
+        self.engine.operators()
+    }
+
+    pub fn defuzzifier(&self) -> Defuzzifier {
+        self.engine.defuzzifier()
+    }
+
+    /// add an already built rule, panics if it refers to a missing term
+    pub fn push_rule(&mut self, rule: Rule) {
+        self.engine.push_rule(rule);
+    }
+
+    pub fn try_push_rule(&mut self, rule: Rule) -> Result<(), FuzzyError> {
+        self.engine.try_push_rule(rule)
+    }
+
+    /// one condition per input joined by AND, each can be "any" or use
+    /// "not" and hedges, e.g. `["very low", "any"]`
+    pub fn add_rule(&mut self, cond: [&str; N], res: [&str; M]) {
+        self.engine.add_rule(&cond, &res);
+    }
+
+    pub fn try_add_rule(&mut self, cond: [&str; N], res: [&str; M]) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule(&cond, &res)
+    }
+
+    /// like `add_rule` with the firing strength scaled by `weight` in [0, 1]
+    pub fn add_rule_weighted(&mut self, cond: [&str; N], res: [&str; M], weight: f64) {
+        self.engine.add_rule_weighted(&cond, &res, weight);
+    }
+
+    pub fn try_add_rule_weighted(
+        &mut self,
+        cond: [&str; N],
+        res: [&str; M],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule_weighted(&cond, &res, weight)
+    }
+
+    /// weight of the rule at `index`, in the order rules were added
+    pub fn set_rule_weight(&mut self, index: usize, weight: f64) {
+        self.engine.set_rule_weight(index, weight);
+    }
+
+    pub fn try_set_rule_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        self.engine.try_set_rule_weight(index, weight)
+    }
+
+    /// rule with an arbitrary condition, e.g. `is(0, "low").or(!is(1, "short"))`
+    pub fn add_rule_expr(&mut self, cond: Antecedent, res: [&str; M]) {
+        self.engine.add_rule_expr(cond, &res);
+    }
+
+    pub fn try_add_rule_expr(
+        &mut self,
+        cond: Antecedent,
+        res: [&str; M],
+    ) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule_expr(cond, &res)
+    }
 
-        let conditions: Vec<String> = cond.iter().map(|x| x.to_string()).collect();
-        let results: Vec<String> = res.iter().map(|x| x.to_string()).collect();
-        self.rules.push((conditions, results));
+    /// see `DynFuzzyEngine::add_rule_str`
+    pub fn add_rule_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.engine.add_rule_str(text)
+    }
+
+    /// one rule per line, see `parse::parse_rules`
+    pub fn add_rules_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.engine.add_rules_str(text)
     }
 
     pub fn calculate(&self, inputs: [f64; N]) -> Vec<FuzzySet> {
-        let mut temp: Vec<Vec<FuzzySet>> = vec![];
-        for j in 0..self.rules.len() {
-            let mut aj = f64::MAX;
-            for i in 0..self.rules[j].0.len() {
-                let fuzzy_set = self.inputs_var[i].term(&self.rules[j].0[i]);
-                let v = fuzzy_set.degree_of(inputs[i]);
-                aj = aj.min(v);
-            }
+        self.engine.calculate(&inputs)
+    }
 
-            let mut t: Vec<FuzzySet> = vec![];
-            for i in 0..self.rules[j].1.len() {
-                t.push(
-                    self.outputs_var[i]
-                        .term(&self.rules[j].1[i])
-                        .min(aj, format!("f{}", j)),
-                );
-            }
+    /// crisp value of every output variable using the configured defuzzifier
+    pub fn defuzzify(&self, inputs: [f64; N]) -> Vec<f64> {
+        self.engine.defuzzify(&inputs)
+    }
+}
 
-            temp.push(t);
-        }
-        let mut res: Vec<FuzzySet> = vec![];
-        for i in 0..M {
-            res.push(temp[0][i].std_union(&temp[0][i], "".into()));
+impl<const N: usize, const M: usize> Serialize for FuzzyEngine<N, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.engine.serialize(serializer)
+    }
+}
+
+impl<'de, const N: usize, const M: usize> Deserialize<'de> for FuzzyEngine<N, M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let engine = DynFuzzyEngine::deserialize(deserializer)?;
+        if engine.inputs_var.len() != N {
+            let e = FuzzyError::Arity("inputs".into(), N, engine.inputs_var.len());
+            return Err(de::Error::custom(e));
         }
-        for j in 1..temp.len() {
-            for i in 0..M {
-                res[i] = res[i].std_union(&temp[j][i], "".into());
-            }
+        if engine.outputs_var.len() != M {
+            let e = FuzzyError::Arity("outputs".into(), M, engine.outputs_var.len());
+            return Err(de::Error::custom(e));
         }
-        res
+        Ok(FuzzyEngine { engine })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::antecedent::is;
     use crate::shape::*;
     use std::error::Error;
 
@@ -123,4 +749,222 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn expression_rules() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let signal = LinguisticVar::new(
+            vec![(&triangular(50f64, 1.0, 50f64), "strong")],
+            arange(0f64, 100f64, 0.01),
+        );
+
+        let mut f_engine = FuzzyEngine::new([rsi.clone(), rsi], [signal]);
+        f_engine.add_rule(["very low", "any"], ["strong"]);
+        let res = f_engine.calculate([25f64, 80f64]);
+        assert!((res[0].height() - 0.25).abs() < 1e-9);
+
+        f_engine.add_rule_expr(is(0, "high").or(!is(1, "high")), ["strong"]);
+        let res = f_engine.calculate([25f64, 80f64]);
+        assert!((res[0].height() - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn dynamic_engine() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let signal = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "weak"),
+                (&triangular(100f64, 1.0, 50f64), "strong"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+
+        let mut engine = DynFuzzyEngine::new(
+            vec![rsi.clone().named("rsi"), rsi.named("bb")],
+            vec![signal.named("long")],
+        );
+        engine.add_rule_named(&[("bb", "low")], &[("long", "strong")]);
+        engine.add_rule(&["high", "high"], &["weak"]);
+        assert_eq!(
+            engine.rules()[0].antecedent,
+            Antecedent::from_slots(&["any", "low"])
+        );
+        assert_eq!(engine.rules()[0].consequent, vec![Some("strong".into())]);
+
+        let a = engine.defuzzify_named(&[("bb", 20.0), ("rsi", 90.0)]);
+        let typed = FuzzyEngine::<2, 1>::from_dyn(engine.clone()).unwrap();
+        assert_eq!(a, typed.defuzzify([90.0, 20.0]));
+        assert!(FuzzyEngine::<1, 1>::from_dyn(engine).is_none());
+    }
+
+    #[test]
+    fn fallible() {
+        let x = LinguisticVar::new(
+            vec![(&triangular(0f64, 1.0, 50f64), "low")],
+            arange(0f64, 100f64, 0.01),
+        )
+        .named("x");
+        let mut engine = DynFuzzyEngine::new(vec![x.clone()], vec![x.named("y")]);
+
+        assert!(matches!(
+            engine.try_add_rule(&["high"], &["low"]),
+            Err(FuzzyError::UnknownTerm(v, t)) if v == "x" && t == "high"
+        ));
+        assert!(matches!(
+            engine.try_add_rule(&["low", "low"], &["low"]),
+            Err(FuzzyError::Arity(_, 1, 2))
+        ));
+        assert!(matches!(
+            engine.try_add_rule_named(&[("z", "low")], &[]),
+            Err(FuzzyError::UnknownVariable(v)) if v == "z"
+        ));
+        assert!(matches!(
+            engine.try_add_rule_weighted(&["kinda low"], &["low"], 0.5),
+            Err(FuzzyError::UnknownModifier(w, _)) if w == "kinda"
+        ));
+        assert!(engine.rules().is_empty());
+
+        engine.try_add_rule(&["low"], &["low"]).unwrap();
+        assert!(engine.try_calculate(&[1.0, 2.0]).is_err());
+        assert!(engine.try_defuzzify_named(&[("y", 1.0)]).is_err());
+        assert!(engine.try_defuzzify(&[10.0]).is_ok());
+        assert!(matches!(
+            crate::data::try_read_csv("no/such/file.csv"),
+            Err(FuzzyError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&sigmoid(-0.2, 30.0), "low"),
+                (&gaussian(50.0, 10.0), "medium"),
+                (&sigmoid(0.2, 70.0), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        )
+        .named("rsi");
+        let signal = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "weak"),
+                (&trapezoidal(40.0, 60.0, 100.0, 120.0, 1.0), "strong"),
+            ],
+            arange(0f64, 100f64, 0.5),
+        )
+        .named("long");
+
+        let mut engine = FuzzyEngine::new([rsi], [signal]);
+        engine
+            .add_rules_str("IF rsi IS very low THEN long IS strong\nIF rsi IS NOT low THEN long IS weak WITH 0.5")
+            .unwrap();
+        engine.set_defuzzifier(Defuzzifier::Bisector);
+        engine.set_operators(Operators {
+            or: SNorm::Yager(2.0),
+            ..Operators::default()
+        });
+
+        let json = serde_json::to_string(&engine).unwrap();
+        assert!(json.contains(r#""universe":{"start":0.0,"stop":100.0,"step":0.01}"#));
+        let copy: FuzzyEngine<1, 1> = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.rules(), engine.rules());
+        assert_eq!(copy.operators(), engine.operators());
+        for x in [5.0, 33.3, 80.0] {
+            assert_eq!(copy.defuzzify([x]), engine.defuzzify([x]));
+        }
+
+        assert!(serde_json::from_str::<FuzzyEngine<2, 1>>(&json).is_err());
+        let bad = json.replace(r#""name":"weak""#, r#""name":"feeble""#);
+        assert!(serde_json::from_str::<DynFuzzyEngine>(&bad).is_err());
+    }
+
+    #[test]
+    fn toml_and_yaml() {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&gaussian(100f64, 20f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.5),
+        );
+        let mut engine = DynFuzzyEngine::new(
+            vec![x.clone().named("rsi"), x.clone().named("bb")],
+            vec![x.clone().named("long"), x.named("short")],
+        );
+        engine.add_rule_weighted(&["low", "any"], &["high", "-"], 0.8);
+        engine.add_rule_expr(is(0, "high").or(!is(1, "low")), &["any", "high"]);
+        engine.set_operators(Operators {
+            and: TNorm::Yager(2.0),
+            ..Operators::default()
+        });
+
+        let toml = toml::to_string(&engine).unwrap();
+        let yaml = serde_yaml::to_string(&engine).unwrap();
+        for copy in [
+            toml::from_str::<DynFuzzyEngine>(&toml).unwrap(),
+            serde_yaml::from_str::<DynFuzzyEngine>(&yaml).unwrap(),
+        ] {
+            assert_eq!(copy.rules(), engine.rules());
+            assert_eq!(copy.operators(), engine.operators());
+            for x in [[10.0, 90.0], [60.0, 20.0]] {
+                assert_eq!(copy.defuzzify(&x), engine.defuzzify(&x));
+            }
+        }
+    }
+
+    #[test]
+    fn weights() {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.01),
+        );
+        let mut engine = FuzzyEngine::new([x.clone()], [x]);
+        engine.add_rule_weighted(["low"], ["high"], 0.5);
+        engine.add_rule(["high"], ["low"]);
+        assert!((engine.calculate([0.0])[0].height() - 0.5).abs() < 1e-9);
+
+        engine.set_rule_weight(0, 0.2);
+        assert!((engine.calculate([0.0])[0].height() - 0.2).abs() < 1e-9);
+        assert!(matches!(
+            engine.try_set_rule_weight(1, 1.5),
+            Err(FuzzyError::InvalidWeight(_))
+        ));
+        assert!(matches!(
+            engine.try_set_rule_weight(2, 0.5),
+            Err(FuzzyError::UnknownRule(2))
+        ));
+
+        let mut dynamic = engine.clone().into_dyn();
+        let mut rule = engine.rules()[0].clone();
+        rule.weight = -0.1;
+        assert!(dynamic.try_push_rule(rule).is_err());
+        assert!(dynamic
+            .try_add_rule_weighted(&["low"], &["low"], 2.0)
+            .is_err());
+        assert_eq!(dynamic.rules().len(), 2);
+
+        // a missing weight reads as 1 and out of range weights are rejected
+        let mut json = serde_json::to_value(&engine).unwrap();
+        json["rules"][1].as_object_mut().unwrap().remove("weight");
+        let copy: FuzzyEngine<1, 1> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(copy.rules()[0].weight, 0.2);
+        assert_eq!(copy.rules()[1].weight, 1.0);
+        json["rules"][0]["weight"] = 3.0.into();
+        assert!(serde_json::from_value::<FuzzyEngine<1, 1>>(json).is_err());
+    }
 }
@@ -1,12 +1,26 @@
+use crate::error::{or_panic, FuzzyError};
+use crate::ops::*;
 use crate::shape::*;
 use plotters::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::error::Error;
+use std::sync::Arc;
 
 pub fn arange(start: f64, stop: f64, interval: f64) -> Vec<f64> {
-    if stop < start {
-        panic!("end can not be less than start");
-    } else if interval <= 0f64 {
-        panic!("interval must be > 0");
+    or_panic(try_arange(start, stop, interval))
+}
+
+pub fn try_arange(start: f64, stop: f64, interval: f64) -> Result<Vec<f64>, FuzzyError> {
+    if !start.is_finite() || !stop.is_finite() {
+        return Err(FuzzyError::InvalidRange(
+            "start and end must be finite".into(),
+        ));
+    } else if stop < start {
+        return Err(FuzzyError::InvalidRange(
+            "end can not be less than start".into(),
+        ));
+    } else if interval.is_nan() || interval <= 0f64 {
+        return Err(FuzzyError::InvalidRange("interval must be > 0".into()));
     }
 
     let mut members: Vec<f64> = vec![];
@@ -19,11 +33,18 @@ pub fn arange(start: f64, stop: f64, interval: f64) -> Vec<f64> {
             n = (n * r).round() / r;
         }
     }
-    members
+    Ok(members)
+}
+
+/// universe for a range read from a file, about 1000-10000 points
+pub(crate) fn default_universe(lo: f64, hi: f64) -> Vec<f64> {
+    let step = 10f64.powf((hi - lo).log10().floor() - 3.0);
+    arange(lo, hi, step)
 }
 
 #[derive(Clone)]
 pub struct LinguisticVar {
+    pub name: String, // used by text rules, empty by default
     pub sets: Vec<FuzzySet>,
     pub universe: Vec<f64>,
 }
@@ -32,18 +53,28 @@ impl LinguisticVar {
     pub fn new(inputs: Vec<(&dyn Shape, &str)>, universe: Vec<f64>) -> LinguisticVar {
         let mut sets: Vec<FuzzySet> = vec![];
         for item in inputs {
-            sets.push(FuzzySet::new(&universe, item.0, item.1.to_string()));
+            sets.push(FuzzySet::analytic(&universe, item.0, item.1.to_string()));
+        }
+        LinguisticVar {
+            name: String::new(),
+            sets,
+            universe,
         }
-        LinguisticVar { sets, universe }
+    }
+
+    pub fn named(mut self, name: &str) -> LinguisticVar {
+        self.name = name.to_string();
+        self
     }
 
     pub fn term(&self, name: &str) -> &FuzzySet {
-        match self.sets.iter().find(|x| x.name == name.to_string()) {
-            Some(x) => x,
-            None => panic![
-                "there're no fuzzy set name {} in this linguistic variable",
-                name
-            ],
+        or_panic(self.try_term(name))
+    }
+
+    pub fn try_term(&self, name: &str) -> Result<&FuzzySet, FuzzyError> {
+        match self.sets.iter().find(|x| x.name == name) {
+            Some(x) => Ok(x),
+            None => Err(FuzzyError::UnknownTerm(self.name.clone(), name.to_string())),
         }
     }
 
@@ -105,6 +136,7 @@ pub struct FuzzySet {
     pub name: String,
     pub universe: Vec<f64>, // universe of discourse that own this set
     pub membership: Vec<f64>,
+    pub shape: Option<Arc<dyn Shape>>, // kept by analytic sets, degree_of evaluates it directly
 }
 
 impl FuzzySet {
@@ -117,9 +149,19 @@ impl FuzzySet {
             name: name.to_string(),
             universe: universe.clone(),
             membership,
+            shape: None,
         }
     }
 
+    /// same as `new` but keeps the shape, so `degree_of` is exact instead of
+    /// snapping to the nearest sampled point. the sampled membership is still
+    /// used for plotting, set operations and defuzzification
+    pub fn analytic(universe: &Vec<f64>, fuzzy_f: &dyn Shape, name: String) -> FuzzySet {
+        let mut set = FuzzySet::new(universe, fuzzy_f, name);
+        set.shape = Some(Arc::from(fuzzy_f.clone_box()));
+        set
+    }
+
     pub fn plot(&self, name: String, path: String) -> Result<(), Box<dyn Error>> {
         let root = SVGBackend::new(&path, (1024, 768)).into_drawing_area();
         root.fill(&WHITE)?;
@@ -165,6 +207,12 @@ impl FuzzySet {
     }
 
     pub fn degree_of(&self, input: f64) -> f64 {
+        if let Some(shape) = &self.shape {
+            // clamp like the sampled version does outside the universe
+            let x = input.clamp(self.universe[0], self.universe[self.universe.len() - 1]);
+            return shape.function(x);
+        }
+
         // edge case
         if input < self.universe[0] {
             return self.membership[0];
@@ -196,6 +244,69 @@ impl FuzzySet {
         top_sum / bot_sum
     }
 
+    /// point that splits the area under the set into two equal halves
+    pub fn bisector_defuzz(&self) -> f64 {
+        let total = self.membership.iter().fold(0.0, |s, v| s + v);
+        if total == 0.0 {
+            return 0.0;
+        }
+        let mut area = 0.0;
+        for (x, y) in self.universe.iter().zip(self.membership.iter()) {
+            area += y;
+            if area >= total / 2.0 {
+                return *x;
+            }
+        }
+        self.universe[self.universe.len() - 1]
+    }
+
+    /// mean of the points that reach the maximum membership
+    pub fn mom_defuzz(&self) -> f64 {
+        let maxima = self.maxima();
+        if maxima.is_empty() {
+            return 0.0;
+        }
+        maxima.iter().sum::<f64>() / maxima.len() as f64
+    }
+
+    /// smallest point that reach the maximum membership
+    pub fn som_defuzz(&self) -> f64 {
+        self.maxima().first().copied().unwrap_or(0.0)
+    }
+
+    /// largest point that reach the maximum membership
+    pub fn lom_defuzz(&self) -> f64 {
+        self.maxima().last().copied().unwrap_or(0.0)
+    }
+
+    /// highest membership value of the set
+    pub fn height(&self) -> f64 {
+        self.membership.iter().fold(0.0, |m, v| v.max(m))
+    }
+
+    fn maxima(&self) -> Vec<f64> {
+        let h = self.height();
+        if h == 0.0 {
+            return vec![];
+        }
+        self.universe
+            .iter()
+            .zip(self.membership.iter())
+            .filter(|(_, y)| (h - **y).abs() < 1e-12)
+            .map(|(x, _)| *x)
+            .collect()
+    }
+
+    /// set with zero membership everywhere, identity of every s-norm
+    pub fn empty(universe: &[f64], name: String) -> FuzzySet {
+        FuzzySet {
+            name,
+            universe: universe.to_vec(),
+            membership: vec![0.0; universe.len()],
+            shape: None,
+        }
+    }
+
     pub fn min(&self, input: f64, name: String) -> FuzzySet {
         let mut membership: Vec<f64> = vec![];
         for i in 0..self.membership.len() {
@@ -205,43 +316,240 @@ impl FuzzySet {
             name: name.to_string(),
             universe: self.universe.clone(),
             membership,
+            shape: None,
+        }
+    }
+
+    pub fn scale(&self, input: f64, name: String) -> FuzzySet {
+        FuzzySet {
+            name,
+            universe: self.universe.clone(),
+            membership: self.membership.iter().map(|m| m * input).collect(),
+            shape: None,
+        }
+    }
+
+    /// clip or scale the set by a rule's firing strength
+    pub fn implicate(&self, input: f64, implication: Implication, name: String) -> FuzzySet {
+        match implication {
+            Implication::Min => self.min(input, name),
+            Implication::Product => self.scale(input, name),
         }
     }
 
     pub fn std_union(&self, set: &FuzzySet, name: String) -> FuzzySet {
-        // check if domain is equal or not?
+        self.union(set, SNorm::Max, name)
+    }
+
+    pub fn std_intersect(&self, set: &FuzzySet, name: String) -> FuzzySet {
+        self.intersect(set, TNorm::Min, name)
+    }
+
+    /// union using any s-norm, `std_union` is the `SNorm::Max` case
+    pub fn union(&self, set: &FuzzySet, norm: SNorm, name: String) -> FuzzySet {
+        or_panic(self.try_union(set, norm, name))
+    }
+
+    /// intersection using any t-norm, `std_intersect` is the `TNorm::Min` case
+    pub fn intersect(&self, set: &FuzzySet, norm: TNorm, name: String) -> FuzzySet {
+        or_panic(self.try_intersect(set, norm, name))
+    }
+
+    /// fails if the two sets aren't defined over the same universe
+    pub fn try_union(
+        &self,
+        set: &FuzzySet,
+        norm: SNorm,
+        name: String,
+    ) -> Result<FuzzySet, FuzzyError> {
+        self.combine(set, |a, b| norm.apply(a, b), name)
+    }
+
+    /// fails if the two sets aren't defined over the same universe
+    pub fn try_intersect(
+        &self,
+        set: &FuzzySet,
+        norm: TNorm,
+        name: String,
+    ) -> Result<FuzzySet, FuzzyError> {
+        self.combine(set, |a, b| norm.apply(a, b), name)
+    }
+
+    fn combine(
+        &self,
+        set: &FuzzySet,
+        f: impl Fn(f64, f64) -> f64,
+        name: String,
+    ) -> Result<FuzzySet, FuzzyError> {
         if self.universe != set.universe {
-            panic!("domain needs to be equal");
+            return Err(FuzzyError::UniverseMismatch);
         }
+        Ok(FuzzySet {
+            name,
+            universe: self.universe.clone(),
+            membership: self
+                .membership
+                .iter()
+                .zip(set.membership.iter())
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+            shape: None,
+        })
+    }
+}
 
-        // if equal
-        let mut membership: Vec<f64> = vec![];
-        for i in 0..self.membership.len() {
-            membership.push(self.membership[i].max(set.membership[i]));
+/// universe as written by serde, ranges that `arange` reproduces up to rounding are kept
+/// as (start, stop, step)
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Universe {
+    Range { start: f64, stop: f64, step: f64 },
+    Points(Vec<f64>),
+}
+
+impl Universe {
+    fn of(universe: &[f64]) -> Universe {
+        if universe.len() > 2 {
+            let (start, stop) = (universe[0], universe[universe.len() - 1]);
+            // from the end points, the gap between the first two points is off by rounding
+            let step = (stop - start) / (universe.len() - 1) as f64;
+            let close = |u: Vec<f64>| {
+                u.len() == universe.len()
+                    && u.iter()
+                        .zip(universe.iter())
+                        .all(|(a, b)| (a - b).abs() <= step * 1e-6)
+            };
+            if try_arange(start, stop, step).is_ok_and(close) {
+                return Universe::Range { start, stop, step };
+            }
         }
-        FuzzySet {
-            name: name.to_string(),
-            universe: self.universe.clone(),
+        Universe::Points(universe.to_vec())
+    }
+
+    fn points(self) -> Result<Vec<f64>, FuzzyError> {
+        match self {
+            Universe::Range { start, stop, step } => try_arange(start, stop, step),
+            Universe::Points(points) => Ok(points),
+        }
+    }
+}
+
+/// shape parameters for analytic sets, the sampled values otherwise
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Membership {
+    Shape { kind: String, params: Vec<f64> },
+    Sampled { membership: Vec<f64> },
+}
+
+#[derive(Serialize, Deserialize)]
+struct TermDef {
+    name: String,
+    #[serde(flatten)]
+    membership: Membership,
+}
+
+impl TermDef {
+    fn of(set: &FuzzySet) -> TermDef {
+        let membership = match &set.shape {
+            Some(shape) => Membership::Shape {
+                kind: shape.kind().to_string(),
+                params: shape.params(),
+            },
+            None => Membership::Sampled {
+                membership: set.membership.clone(),
+            },
+        };
+        TermDef {
+            name: set.name.clone(),
             membership,
         }
     }
 
-    pub fn std_intersect(&self, set: &FuzzySet, name: String) -> FuzzySet {
-        // check if domain is equal or not?
-        if self.universe != set.universe {
-            panic!("domain needs to be equal");
+    fn build(self, universe: &Vec<f64>) -> Result<FuzzySet, FuzzyError> {
+        match self.membership {
+            Membership::Shape { kind, params } => match from_params(&kind, &params) {
+                Some(shape) => Ok(FuzzySet::analytic(universe, &*shape, self.name)),
+                None => Err(FuzzyError::InvalidShape(kind, params.len())),
+            },
+            Membership::Sampled { membership } => {
+                if membership.len() != universe.len() {
+                    return Err(FuzzyError::Arity(
+                        "membership values".into(),
+                        universe.len(),
+                        membership.len(),
+                    ));
+                }
+                Ok(FuzzySet {
+                    name: self.name,
+                    universe: universe.clone(),
+                    membership,
+                    shape: None,
+                })
+            }
         }
+    }
+}
 
-        // if equal
-        let mut membership: Vec<f64> = vec![];
-        for i in 0..self.membership.len() {
-            membership.push(self.membership[i].min(set.membership[i]));
+#[derive(Serialize, Deserialize)]
+struct VarDef {
+    #[serde(default)]
+    name: String,
+    universe: Universe,
+    terms: Vec<TermDef>,
+}
+
+impl Serialize for LinguisticVar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        VarDef {
+            name: self.name.clone(),
+            universe: Universe::of(&self.universe),
+            terms: self.sets.iter().map(TermDef::of).collect(),
         }
-        FuzzySet {
-            name: name.to_string(),
-            universe: self.universe.clone(),
-            membership,
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LinguisticVar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let def = VarDef::deserialize(deserializer)?;
+        let universe = def.universe.points().map_err(de::Error::custom)?;
+        let sets = def
+            .terms
+            .into_iter()
+            .map(|t| t.build(&universe))
+            .collect::<Result<Vec<FuzzySet>, FuzzyError>>()
+            .map_err(de::Error::custom)?;
+        Ok(LinguisticVar {
+            name: def.name,
+            sets,
+            universe,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SetDef {
+    universe: Universe,
+    #[serde(flatten)]
+    term: TermDef,
+}
+
+impl Serialize for FuzzySet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SetDef {
+            universe: Universe::of(&self.universe),
+            term: TermDef::of(self),
         }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FuzzySet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let def = SetDef::deserialize(deserializer)?;
+        let universe = def.universe.points().map_err(de::Error::custom)?;
+        def.term.build(&universe).map_err(de::Error::custom)
     }
 }
 
@@ -273,6 +581,85 @@ mod tests {
         assert_eq!(s1.degree_of(-1.0f64), 0.0);
     }
 
+    #[test]
+    fn test_analytic_degree() {
+        let universe = arange(0.0, 10.0, 0.5);
+        let sampled = FuzzySet::new(&universe, &triangular(5f64, 0.8f64, 3f64), "f1".into());
+        let exact = FuzzySet::analytic(&universe, &triangular(5f64, 0.8f64, 3f64), "f1".into());
+
+        assert_eq!(sampled.degree_of(3.4f64), 0.4);
+        assert!((exact.degree_of(3.4f64) - 0.8 * (1.0 - 1.6 / 3.0)).abs() < 1e-12);
+        assert_eq!(exact.degree_of(5.0f64), 0.8);
+        assert_eq!(exact.degree_of(-1.0f64), 0.0);
+        assert_eq!(exact.membership, sampled.membership);
+    }
+
+    #[test]
+    fn test_defuzz() {
+        let s1 = FuzzySet::new(
+            &arange(0.0, 10.0, 0.01),
+            &trapezoidal(2f64, 4f64, 6f64, 10f64, 1f64),
+            "f1".into(),
+        );
+
+        assert!((s1.bisector_defuzz() - 5.5).abs() < 0.011);
+        assert_eq!(s1.som_defuzz(), 4.0);
+        assert_eq!(s1.lom_defuzz(), 6.0);
+        assert!((s1.mom_defuzz() - 5.0).abs() < 1e-9);
+        assert_eq!(s1.height(), 1.0);
+    }
+
+    #[test]
+    fn test_norm_operations() {
+        let universe = arange(0.0, 10.0, 1.0);
+        let s1 = FuzzySet::new(&universe, &triangular(4f64, 1f64, 4f64), "f1".into());
+        let s2 = FuzzySet::new(&universe, &triangular(6f64, 1f64, 4f64), "f2".into());
+
+        let u = s1.union(&s2, SNorm::Max, "u".into());
+        assert_eq!(u.membership, s1.std_union(&s2, "".into()).membership);
+        let i = s1.intersect(&s2, TNorm::Product, "i".into());
+        assert_eq!(i.membership[5], 0.75 * 0.75);
+        let c = s1.implicate(0.5, Implication::Product, "c".into());
+        assert_eq!(c.membership[4], 0.5);
+        assert_eq!(c.membership[3], 0.375);
+    }
+
+    #[test]
+    fn fallible() {
+        assert!(matches!(
+            try_arange(1.0, 0.0, 0.1),
+            Err(FuzzyError::InvalidRange(_))
+        ));
+        assert!(try_arange(0.0, 1.0, 0.0).is_err());
+        assert!(try_arange(0.0, f64::NAN, 0.1).is_err());
+
+        let s1 = FuzzySet::new(
+            &arange(0.0, 10.0, 1.0),
+            &triangular(4f64, 1f64, 4f64),
+            "".into(),
+        );
+        let s2 = FuzzySet::new(
+            &arange(0.0, 5.0, 1.0),
+            &triangular(4f64, 1f64, 4f64),
+            "".into(),
+        );
+        assert!(matches!(
+            s1.try_union(&s2, SNorm::Max, "".into()),
+            Err(FuzzyError::UniverseMismatch)
+        ));
+
+        let var = LinguisticVar::new(
+            vec![(&triangular(5f64, 1.0, 3f64), "normal")],
+            arange(0.0, 10.0, 0.1),
+        )
+        .named("x");
+        assert!(var.try_term("normal").is_ok());
+        assert_eq!(
+            var.try_term("weak").unwrap_err().to_string(),
+            "there're no term name weak in variable \"x\""
+        );
+    }
+
     #[test]
     fn linguistic() {
         let var1 = LinguisticVar::new(
@@ -288,4 +675,46 @@ mod tests {
 
         var1.plot("var1".into(), "img/t.svg".into()).unwrap();
     }
+
+    #[test]
+    fn serde_var() {
+        let mut var = LinguisticVar::new(
+            vec![(&points(vec![(1.0, 0.0), (2.0, 1.0), (4.0, 0.0)]), "low")],
+            vec![0.0, 1.0, 2.5, 4.0],
+        );
+        var.sets.push(FuzzySet::new(
+            &var.universe,
+            &triangular(3.0, 1.0, 1.0),
+            "high".into(),
+        ));
+
+        let json = serde_json::to_string(&var).unwrap();
+        assert!(json.contains(r#""universe":[0.0,1.0,2.5,4.0]"#));
+        assert!(json.contains(r#""kind":"points""#));
+        let copy: LinguisticVar = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.universe, var.universe);
+        assert!(copy.sets[0].shape.is_some() && copy.sets[1].shape.is_none());
+        assert_eq!(copy.term("low").degree_of(1.5), 0.5);
+        assert_eq!(copy.term("high").membership, var.term("high").membership);
+
+        let set: FuzzySet = serde_json::from_str(
+            r#"{"name":"x","universe":{"start":0,"stop":1,"step":0.5},"kind":"gaussian","params":[0.5,0.1]}"#,
+        )
+        .unwrap();
+        assert_eq!(set.universe, vec![0.0, 0.5, 1.0]);
+        assert_eq!(set.degree_of(0.5), 1.0);
+        let bad = r#"{"name":"x","universe":[0,1],"kind":"gaussian","params":[0.5]}"#;
+        assert!(serde_json::from_str::<FuzzySet>(bad).is_err());
+
+        // the bb universe of main.rs, its first gap is off from 0.01 by rounding
+        let bb = LinguisticVar::new(
+            vec![(&gaussian(0.0, 20.0), "zero")],
+            arange(-150f64, 150f64, 0.01),
+        );
+        let json = serde_json::to_string(&bb).unwrap();
+        assert!(json.len() < 200, "{}", json.len());
+        let copy: LinguisticVar = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.universe.len(), bb.universe.len());
+        assert!((copy.universe[20000] - bb.universe[20000]).abs() < 1e-9);
+    }
 }
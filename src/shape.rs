@@ -1,7 +1,25 @@
-pub trait Shape {
+use std::fmt::Debug;
+
+pub trait Shape: ShapeClone + Debug + Send + Sync {
     fn function(&self, x: f64) -> f64;
+    /// name of the shape, `from_params(kind, &params)` rebuilds it
+    fn kind(&self) -> &'static str;
+    /// arguments of the shape's constructor function, in order
+    fn params(&self) -> Vec<f64>;
+}
+
+/// lets a `&dyn Shape` be copied into an owned `Box<dyn Shape>`
+pub trait ShapeClone {
+    fn clone_box(&self) -> Box<dyn Shape>;
+}
+
+impl<T: 'static + Shape + Clone> ShapeClone for T {
+    fn clone_box(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct Triangular {
     a: f64,
     b: f64,
@@ -15,12 +33,21 @@ impl Shape for Triangular {
         }
         0.0
     }
+
+    fn kind(&self) -> &'static str {
+        "triangular"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.b, self.s]
+    }
 }
 
 pub fn triangular(a: f64, b: f64, s: f64) -> Triangular {
     Triangular { a, b, s }
 }
 
+#[derive(Debug, Clone)]
 pub struct Trapezoidal {
     a: f64,
     b: f64,
@@ -40,8 +67,394 @@ impl Shape for Trapezoidal {
         }
         0.0
     }
+
+    fn kind(&self) -> &'static str {
+        "trapezoidal"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.b, self.c, self.d, self.e]
+    }
 }
 
 pub fn trapezoidal(a: f64, b: f64, c: f64, d: f64, e: f64) -> Trapezoidal {
     Trapezoidal { a, b, c, d, e }
 }
+
+#[derive(Debug, Clone)]
+pub struct Gaussian {
+    c: f64,
+    sigma: f64,
+}
+
+impl Shape for Gaussian {
+    fn function(&self, x: f64) -> f64 {
+        (-(x - self.c).powi(2) / (2.0 * self.sigma.powi(2))).exp()
+    }
+
+    fn kind(&self) -> &'static str {
+        "gaussian"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.c, self.sigma]
+    }
+}
+
+pub fn gaussian(c: f64, sigma: f64) -> Gaussian {
+    Gaussian { c, sigma }
+}
+
+/// left half is a gaussian centred at c1, right half one centred at c2,
+/// flat at 1 in between
+#[derive(Debug, Clone)]
+pub struct Gauss2 {
+    c1: f64,
+    s1: f64,
+    c2: f64,
+    s2: f64,
+}
+
+impl Shape for Gauss2 {
+    fn function(&self, x: f64) -> f64 {
+        let mut y = 1.0;
+        if x < self.c1 {
+            y *= gaussian(self.c1, self.s1).function(x);
+        }
+        if x > self.c2 {
+            y *= gaussian(self.c2, self.s2).function(x);
+        }
+        y
+    }
+
+    fn kind(&self) -> &'static str {
+        "gauss2"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.c1, self.s1, self.c2, self.s2]
+    }
+}
+
+pub fn gauss2(c1: f64, s1: f64, c2: f64, s2: f64) -> Gauss2 {
+    Gauss2 { c1, s1, c2, s2 }
+}
+
+/// generalized bell, a - width, b - slope, c - center
+#[derive(Debug, Clone)]
+pub struct Bell {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+impl Shape for Bell {
+    fn function(&self, x: f64) -> f64 {
+        1.0 / (1.0 + ((x - self.c) / self.a).abs().powf(2.0 * self.b))
+    }
+
+    fn kind(&self) -> &'static str {
+        "bell"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.b, self.c]
+    }
+}
+
+pub fn bell(a: f64, b: f64, c: f64) -> Bell {
+    Bell { a, b, c }
+}
+
+/// a - slope (negative opens to the left), c - crossover point
+#[derive(Debug, Clone)]
+pub struct Sigmoid {
+    a: f64,
+    c: f64,
+}
+
+impl Shape for Sigmoid {
+    fn function(&self, x: f64) -> f64 {
+        1.0 / (1.0 + (-self.a * (x - self.c)).exp())
+    }
+
+    fn kind(&self) -> &'static str {
+        "sigmoid"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.c]
+    }
+}
+
+pub fn sigmoid(a: f64, c: f64) -> Sigmoid {
+    Sigmoid { a, c }
+}
+
+/// |sigmoid(a1, c1) - sigmoid(a2, c2)|
+#[derive(Debug, Clone)]
+pub struct DSigmoid {
+    a1: f64,
+    c1: f64,
+    a2: f64,
+    c2: f64,
+}
+
+impl Shape for DSigmoid {
+    fn function(&self, x: f64) -> f64 {
+        (sigmoid(self.a1, self.c1).function(x) - sigmoid(self.a2, self.c2).function(x)).abs()
+    }
+
+    fn kind(&self) -> &'static str {
+        "dsigmoid"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a1, self.c1, self.a2, self.c2]
+    }
+}
+
+pub fn dsigmoid(a1: f64, c1: f64, a2: f64, c2: f64) -> DSigmoid {
+    DSigmoid { a1, c1, a2, c2 }
+}
+
+/// sigmoid(a1, c1) * sigmoid(a2, c2)
+#[derive(Debug, Clone)]
+pub struct PSigmoid {
+    a1: f64,
+    c1: f64,
+    a2: f64,
+    c2: f64,
+}
+
+impl Shape for PSigmoid {
+    fn function(&self, x: f64) -> f64 {
+        sigmoid(self.a1, self.c1).function(x) * sigmoid(self.a2, self.c2).function(x)
+    }
+
+    fn kind(&self) -> &'static str {
+        "psigmoid"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a1, self.c1, self.a2, self.c2]
+    }
+}
+
+pub fn psigmoid(a1: f64, c1: f64, a2: f64, c2: f64) -> PSigmoid {
+    PSigmoid { a1, c1, a2, c2 }
+}
+
+/// spline based curve rising from 0 at a to 1 at b
+#[derive(Debug, Clone)]
+pub struct SShape {
+    a: f64,
+    b: f64,
+}
+
+impl Shape for SShape {
+    fn function(&self, x: f64) -> f64 {
+        let mid = (self.a + self.b) / 2.0;
+        if x <= self.a {
+            0.0
+        } else if x <= mid {
+            2.0 * ((x - self.a) / (self.b - self.a)).powi(2)
+        } else if x <= self.b {
+            1.0 - 2.0 * ((x - self.b) / (self.b - self.a)).powi(2)
+        } else {
+            1.0
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        "s_shape"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.b]
+    }
+}
+
+pub fn s_shape(a: f64, b: f64) -> SShape {
+    SShape { a, b }
+}
+
+/// mirror of SShape, falling from 1 at a to 0 at b
+#[derive(Debug, Clone)]
+pub struct ZShape {
+    a: f64,
+    b: f64,
+}
+
+impl Shape for ZShape {
+    fn function(&self, x: f64) -> f64 {
+        1.0 - s_shape(self.a, self.b).function(x)
+    }
+
+    fn kind(&self) -> &'static str {
+        "z_shape"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.b]
+    }
+}
+
+pub fn z_shape(a: f64, b: f64) -> ZShape {
+    ZShape { a, b }
+}
+
+/// rises like SShape over [a, b], flat until c, falls like ZShape over [c, d]
+#[derive(Debug, Clone)]
+pub struct PiShape {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+}
+
+impl Shape for PiShape {
+    fn function(&self, x: f64) -> f64 {
+        s_shape(self.a, self.b).function(x) * z_shape(self.c, self.d).function(x)
+    }
+
+    fn kind(&self) -> &'static str {
+        "pi_shape"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        vec![self.a, self.b, self.c, self.d]
+    }
+}
+
+pub fn pi_shape(a: f64, b: f64, c: f64, d: f64) -> PiShape {
+    PiShape { a, b, c, d }
+}
+
+/// piecewise linear curve through (x, y) points, as used by FCL.
+/// constant before the first and after the last point, a vertical edge at either end
+/// like `(0, 0) (0, 1)` takes its inner value on the edge itself
+#[derive(Debug, Clone)]
+pub struct Points {
+    points: Vec<(f64, f64)>,
+}
+
+impl Shape for Points {
+    fn function(&self, x: f64) -> f64 {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        if x < first.0 {
+            return first.1;
+        } else if x > last.0 {
+            return last.1;
+        }
+        for w in self.points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+            if x >= x0 && x <= x1 {
+                if x1 == x0 {
+                    return y1;
+                }
+                return y0 + (y1 - y0) * (x - x0) / (x1 - x0);
+            }
+        }
+        0.0
+    }
+
+    fn kind(&self) -> &'static str {
+        "points"
+    }
+
+    fn params(&self) -> Vec<f64> {
+        self.points.iter().flat_map(|(x, y)| [*x, *y]).collect()
+    }
+}
+
+/// points must be sorted by x and not empty
+pub fn points(points: Vec<(f64, f64)>) -> Points {
+    if points.is_empty() {
+        panic!("points needs at least one point");
+    }
+    Points { points }
+}
+
+/// rebuild a shape from its `kind` and `params`, None if the kind is unknown
+/// or the number of params doesn't match
+pub fn from_params(kind: &str, p: &[f64]) -> Option<Box<dyn Shape>> {
+    let n = match kind {
+        "gaussian" | "sigmoid" | "s_shape" | "z_shape" => 2,
+        "triangular" | "bell" => 3,
+        "gauss2" | "dsigmoid" | "psigmoid" | "pi_shape" => 4,
+        "trapezoidal" => 5,
+        "points" if !p.is_empty() && p.len().is_multiple_of(2) => p.len(),
+        _ => return None,
+    };
+    if p.len() != n {
+        return None;
+    }
+    Some(match kind {
+        "triangular" => Box::new(triangular(p[0], p[1], p[2])),
+        "trapezoidal" => Box::new(trapezoidal(p[0], p[1], p[2], p[3], p[4])),
+        "gaussian" => Box::new(gaussian(p[0], p[1])),
+        "gauss2" => Box::new(gauss2(p[0], p[1], p[2], p[3])),
+        "bell" => Box::new(bell(p[0], p[1], p[2])),
+        "sigmoid" => Box::new(sigmoid(p[0], p[1])),
+        "dsigmoid" => Box::new(dsigmoid(p[0], p[1], p[2], p[3])),
+        "psigmoid" => Box::new(psigmoid(p[0], p[1], p[2], p[3])),
+        "s_shape" => Box::new(s_shape(p[0], p[1])),
+        "z_shape" => Box::new(z_shape(p[0], p[1])),
+        "pi_shape" => Box::new(pi_shape(p[0], p[1], p[2], p[3])),
+        _ => Box::new(points(p.chunks(2).map(|c| (c[0], c[1])).collect())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_parametric_shapes() {
+        assert!(close(gaussian(5.0, 2.0).function(5.0), 1.0));
+        assert!(close(gaussian(5.0, 2.0).function(7.0), (-0.5f64).exp()));
+        assert!(close(gauss2(2.0, 1.0, 4.0, 1.0).function(3.0), 1.0));
+        assert!(close(
+            gauss2(2.0, 1.0, 4.0, 1.0).function(5.0),
+            (-0.5f64).exp()
+        ));
+        assert!(close(bell(2.0, 4.0, 6.0).function(8.0), 0.5));
+        assert!(close(sigmoid(2.0, 4.0).function(4.0), 0.5));
+        assert!(dsigmoid(5.0, 2.0, 5.0, 7.0).function(4.5) > 0.99);
+        assert!(dsigmoid(5.0, 2.0, 5.0, 7.0).function(9.0) < 0.01);
+        assert!(psigmoid(5.0, 2.0, -5.0, 7.0).function(4.5) > 0.99);
+        assert!(psigmoid(5.0, 2.0, -5.0, 7.0).function(0.0) < 0.01);
+        assert!(close(s_shape(1.0, 8.0).function(4.5), 0.5));
+        assert!(close(s_shape(1.0, 8.0).function(8.0), 1.0));
+        assert!(close(z_shape(1.0, 8.0).function(1.0), 1.0));
+        assert!(close(pi_shape(1.0, 4.0, 5.0, 10.0).function(4.5), 1.0));
+        assert!(close(pi_shape(1.0, 4.0, 5.0, 10.0).function(10.0), 0.0));
+    }
+
+    #[test]
+    fn test_points_and_params() {
+        let p = points(vec![(0.0, 0.0), (4.0, 1.0), (6.0, 1.0), (10.0, 0.0)]);
+        assert!(close(p.function(2.0), 0.5));
+        assert!(close(p.function(5.0), 1.0));
+        assert!(close(p.function(11.0), 0.0));
+
+        let shapes: Vec<Box<dyn Shape>> = vec![
+            Box::new(triangular(5.0, 0.8, 3.0)),
+            Box::new(pi_shape(1.0, 4.0, 5.0, 10.0)),
+            Box::new(p),
+        ];
+        for shape in shapes {
+            let copy = from_params(shape.kind(), &shape.params()).unwrap();
+            assert_eq!(copy.params(), shape.params());
+            assert!(close(copy.function(3.3), shape.function(3.3)));
+        }
+        assert!(from_params("gaussian", &[1.0]).is_none());
+        assert!(from_params("circle", &[1.0]).is_none());
+    }
+}
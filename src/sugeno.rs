@@ -0,0 +1,540 @@
+use crate::antecedent::Antecedent;
+use crate::error::{or_panic, FuzzyError};
+use crate::ops::Operators;
+use crate::parse::ParseError;
+use crate::rule::{ordered_inputs, Rule, RuleBase};
+use crate::set::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// rule output of a Takagi-Sugeno-Kang system
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Consequent {
+    Constant(f64),
+    Linear(Vec<f64>, f64), // (coefficient for each input, constant term)
+}
+
+impl Consequent {
+    pub fn eval(&self, inputs: &[f64]) -> f64 {
+        match self {
+            Consequent::Constant(c) => *c,
+            Consequent::Linear(coef, c) => coef.iter().zip(inputs).fold(*c, |s, (a, x)| s + a * x),
+        }
+    }
+}
+
+pub fn constant(c: f64) -> Consequent {
+    Consequent::Constant(c)
+}
+
+pub fn linear(coef: Vec<f64>, c: f64) -> Consequent {
+    Consequent::Linear(coef, c)
+}
+
+/// how the rule outputs are combined into one crisp value per output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SugenoMethod {
+    #[default]
+    WeightedAverage, // sum of strength * output divided by the sum of strengths
+    WeightedSum, // sum of strength * output, MATLAB's wtsum
+}
+
+/// output variable of a SugenoEngine, each term is a crisp function of the inputs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SugenoVar {
+    #[serde(default)]
+    pub name: String, // used by text rules, empty by default
+    pub terms: Vec<(String, Consequent)>,
+}
+
+impl SugenoVar {
+    pub fn new(inputs: Vec<(Consequent, &str)>) -> SugenoVar {
+        let terms = inputs
+            .into_iter()
+            .map(|(f, name)| (name.to_string(), f))
+            .collect();
+        SugenoVar {
+            name: String::new(),
+            terms,
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> SugenoVar {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn term(&self, name: &str) -> &Consequent {
+        or_panic(self.try_term(name))
+    }
+
+    pub fn try_term(&self, name: &str) -> Result<&Consequent, FuzzyError> {
+        match self.terms.iter().find(|x| x.0 == name) {
+            Some(x) => Ok(&x.1),
+            None => Err(FuzzyError::UnknownTerm(self.name.clone(), name.to_string())),
+        }
+    }
+}
+
+/// sugeno engine with its inputs and outputs only known at runtime
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "EngineDef")]
+pub struct DynSugenoEngine {
+    #[serde(rename = "inputs")]
+    inputs_var: Vec<LinguisticVar>,
+    #[serde(rename = "outputs")]
+    outputs_var: Vec<SugenoVar>,
+    rules: RuleBase,
+    #[serde(rename = "operators")]
+    ops: Operators,
+    method: SugenoMethod,
+}
+
+/// deserialized form of DynSugenoEngine, rules are checked before building the engine
+#[derive(Deserialize)]
+struct EngineDef {
+    inputs: Vec<LinguisticVar>,
+    outputs: Vec<SugenoVar>,
+    rules: Vec<Rule>,
+    #[serde(default)]
+    operators: Operators,
+    #[serde(default)]
+    method: SugenoMethod,
+}
+
+impl TryFrom<EngineDef> for DynSugenoEngine {
+    type Error = FuzzyError;
+
+    fn try_from(def: EngineDef) -> Result<Self, Self::Error> {
+        let mut engine = DynSugenoEngine::try_new(def.inputs, def.outputs)?;
+        engine.try_set_operators(def.operators)?;
+        engine.set_method(def.method);
+        for rule in def.rules {
+            engine.try_push_rule(rule)?;
+        }
+        Ok(engine)
+    }
+}
+
+impl DynSugenoEngine {
+    /// panics if a linear consequent doesn't have one coefficient per input
+    pub fn new(inputs_var: Vec<LinguisticVar>, outputs_var: Vec<SugenoVar>) -> DynSugenoEngine {
+        or_panic(DynSugenoEngine::try_new(inputs_var, outputs_var))
+    }
+
+    pub fn try_new(
+        inputs_var: Vec<LinguisticVar>,
+        outputs_var: Vec<SugenoVar>,
+    ) -> Result<DynSugenoEngine, FuzzyError> {
+        for var in outputs_var.iter() {
+            for (name, f) in var.terms.iter() {
+                if let Consequent::Linear(coef, _) = f {
+                    if coef.len() != inputs_var.len() {
+                        return Err(FuzzyError::Arity(
+                            format!("coefficients in {}", name),
+                            inputs_var.len(),
+                            coef.len(),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(DynSugenoEngine {
+            inputs_var,
+            outputs_var,
+            rules: RuleBase::default(),
+            ops: Operators::default(),
+            method: SugenoMethod::default(),
+        })
+    }
+
+    /// only the `and`/`or` norms apply, sugeno outputs are never implicated or aggregated
+    pub fn set_operators(&mut self, ops: Operators) {
+        or_panic(self.try_set_operators(ops))
+    }
+
+    /// fails for a Yager norm whose p isn't > 0
+    pub fn try_set_operators(&mut self, ops: Operators) -> Result<(), FuzzyError> {
+        ops.check()?;
+        self.ops = ops;
+        Ok(())
+    }
+
+    /// weighted average by default
+    pub fn set_method(&mut self, method: SugenoMethod) {
+        self.method = method;
+    }
+
+    pub fn inputs(&self) -> &[LinguisticVar] {
+        &self.inputs_var
+    }
+
+    pub fn outputs(&self) -> &[SugenoVar] {
+        &self.outputs_var
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        self.rules.rules()
+    }
+
+    /// variables whose terms are rewritten in place by training, names must stay the same
+    pub(crate) fn vars_mut(&mut self) -> (&mut [LinguisticVar], &mut [SugenoVar]) {
+        (&mut self.inputs_var, &mut self.outputs_var)
+    }
+
+    pub fn operators(&self) -> Operators {
+        self.ops
+    }
+
+    pub fn method(&self) -> SugenoMethod {
+        self.method
+    }
+
+    pub fn input_index(&self, name: &str) -> Option<usize> {
+        self.inputs_var.iter().position(|v| v.name == name)
+    }
+
+    pub fn output_index(&self, name: &str) -> Option<usize> {
+        self.outputs_var.iter().position(|v| v.name == name)
+    }
+
+    /// add an already built rule, panics if it refers to a missing term
+    pub fn push_rule(&mut self, rule: Rule) {
+        or_panic(self.try_push_rule(rule))
+    }
+
+    pub fn try_push_rule(&mut self, rule: Rule) -> Result<(), FuzzyError> {
+        self.rules
+            .try_push(&self.inputs_var, &self.outputs_var, rule)
+    }
+
+    /// same condition syntax as `DynFuzzyEngine::add_rule`
+    pub fn add_rule(&mut self, cond: &[&str], res: &[&str]) {
+        or_panic(self.try_add_rule(cond, res))
+    }
+
+    pub fn try_add_rule(&mut self, cond: &[&str], res: &[&str]) -> Result<(), FuzzyError> {
+        self.try_add_rule_weighted(cond, res, 1.0)
+    }
+
+    /// like `add_rule` with the firing strength scaled by `weight` in [0, 1]
+    pub fn add_rule_weighted(&mut self, cond: &[&str], res: &[&str], weight: f64) {
+        or_panic(self.try_add_rule_weighted(cond, res, weight))
+    }
+
+    pub fn try_add_rule_weighted(
+        &mut self,
+        cond: &[&str],
+        res: &[&str],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add(&self.inputs_var, &self.outputs_var, cond, res, weight)
+    }
+
+    /// weight of the rule at `index`, in the order rules were added
+    pub fn set_rule_weight(&mut self, index: usize, weight: f64) {
+        or_panic(self.try_set_rule_weight(index, weight))
+    }
+
+    pub fn try_set_rule_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        self.rules.try_set_weight(index, weight)
+    }
+
+    pub fn add_rule_expr(&mut self, cond: Antecedent, res: &[&str]) {
+        or_panic(self.try_add_rule_expr(cond, res))
+    }
+
+    pub fn try_add_rule_expr(&mut self, cond: Antecedent, res: &[&str]) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add_expr(&self.inputs_var, &self.outputs_var, cond, res, 1.0)
+    }
+
+    /// see `DynFuzzyEngine::add_rule_named`
+    pub fn add_rule_named(&mut self, cond: &[(&str, &str)], res: &[(&str, &str)]) {
+        or_panic(self.try_add_rule_named(cond, res))
+    }
+
+    pub fn try_add_rule_named(
+        &mut self,
+        cond: &[(&str, &str)],
+        res: &[(&str, &str)],
+    ) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add_named(&self.inputs_var, &self.outputs_var, cond, res)
+    }
+
+    /// see `parse::parse_rule` for the syntax
+    pub fn add_rule_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.rules
+            .add_str(&self.inputs_var, &self.outputs_var, text)
+    }
+
+    /// one rule per line, see `parse::parse_rules`
+    pub fn add_rules_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.rules
+            .add_strs(&self.inputs_var, &self.outputs_var, text)
+    }
+
+    /// weighted average or sum of the rule outputs, one crisp value per output variable
+    pub fn calculate(&self, inputs: &[f64]) -> Vec<f64> {
+        or_panic(self.try_calculate(inputs))
+    }
+
+    /// fails if there isn't exactly one value per input variable
+    pub fn try_calculate(&self, inputs: &[f64]) -> Result<Vec<f64>, FuzzyError> {
+        if inputs.len() != self.inputs_var.len() {
+            return Err(FuzzyError::Arity(
+                "inputs".into(),
+                self.inputs_var.len(),
+                inputs.len(),
+            ));
+        }
+        let mut top_sum = vec![0.0; self.outputs_var.len()];
+        let mut bot_sum = vec![0.0; self.outputs_var.len()];
+        for rule in self.rules.rules().iter() {
+            let w = rule.weight * rule.antecedent.eval(&self.inputs_var, inputs, &self.ops);
+            for (i, term) in rule.consequent.iter().enumerate() {
+                if let Some(term) = term {
+                    top_sum[i] += w * self.outputs_var[i].term(term).eval(inputs);
+                    bot_sum[i] += w;
+                }
+            }
+        }
+        if self.method == SugenoMethod::WeightedSum {
+            return Ok(top_sum);
+        }
+        Ok(top_sum
+            .iter()
+            .zip(bot_sum.iter())
+            .map(|(t, b)| if *b == 0.0 { 0.0 } else { t / b })
+            .collect())
+    }
+
+    /// inputs given as (variable name, value), every input must be listed
+    pub fn calculate_named(&self, inputs: &[(&str, f64)]) -> Vec<f64> {
+        or_panic(self.try_calculate_named(inputs))
+    }
+
+    pub fn try_calculate_named(&self, inputs: &[(&str, f64)]) -> Result<Vec<f64>, FuzzyError> {
+        self.try_calculate(&ordered_inputs(&self.inputs_var, inputs)?)
+    }
+}
+
+/// DynSugenoEngine with the number of inputs (N) and outputs (M) checked at compile time
+#[derive(Clone)]
+pub struct SugenoEngine<const N: usize, const M: usize> {
+    engine: DynSugenoEngine,
+}
+
+impl<const N: usize, const M: usize> SugenoEngine<N, M> {
+    pub fn new(inputs_var: [LinguisticVar; N], outputs_var: [SugenoVar; M]) -> SugenoEngine<N, M> {
+        SugenoEngine {
+            engine: DynSugenoEngine::new(Vec::from(inputs_var), Vec::from(outputs_var)),
+        }
+    }
+
+    /// None if the engine doesn't have N inputs and M outputs
+    pub fn from_dyn(engine: DynSugenoEngine) -> Option<SugenoEngine<N, M>> {
+        if engine.inputs_var.len() != N || engine.outputs_var.len() != M {
+            return None;
+        }
+        Some(SugenoEngine { engine })
+    }
+
+    pub fn as_dyn(&self) -> &DynSugenoEngine {
+        &self.engine
+    }
+
+    pub fn into_dyn(self) -> DynSugenoEngine {
+        self.engine
+    }
+
+    pub(crate) fn as_dyn_mut(&mut self) -> &mut DynSugenoEngine {
+        &mut self.engine
+    }
+
+    /// only the `and`/`or` norms apply, sugeno outputs are never implicated or aggregated
+    pub fn set_operators(&mut self, ops: Operators) {
+        self.engine.set_operators(ops);
+    }
+
+    pub fn try_set_operators(&mut self, ops: Operators) -> Result<(), FuzzyError> {
+        self.engine.try_set_operators(ops)
+    }
+
+    /// weighted average by default
+    pub fn set_method(&mut self, method: SugenoMethod) {
+        self.engine.set_method(method);
+    }
+
+    pub fn inputs(&self) -> &[LinguisticVar; N] {
+        self.engine.inputs().try_into().unwrap()
+    }
+
+    pub fn outputs(&self) -> &[SugenoVar; M] {
+        self.engine.outputs().try_into().unwrap()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        self.engine.rules()
+    }
+
+    pub fn operators(&self) -> Operators {
+        self.engine.operators()
+    }
+
+    pub fn method(&self) -> SugenoMethod {
+        self.engine.method()
+    }
+
+    /// add an already built rule, panics if it refers to a missing term
+    pub fn push_rule(&mut self, rule: Rule) {
+        self.engine.push_rule(rule);
+    }
+
+    pub fn try_push_rule(&mut self, rule: Rule) -> Result<(), FuzzyError> {
+        self.engine.try_push_rule(rule)
+    }
+
+    /// same condition syntax as `FuzzyEngine::add_rule`
+    pub fn add_rule(&mut self, cond: [&str; N], res: [&str; M]) {
+        self.engine.add_rule(&cond, &res);
+    }
+
+    pub fn try_add_rule(&mut self, cond: [&str; N], res: [&str; M]) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule(&cond, &res)
+    }
+
+    /// like `add_rule` with the firing strength scaled by `weight` in [0, 1]
+    pub fn add_rule_weighted(&mut self, cond: [&str; N], res: [&str; M], weight: f64) {
+        self.engine.add_rule_weighted(&cond, &res, weight);
+    }
+
+    pub fn try_add_rule_weighted(
+        &mut self,
+        cond: [&str; N],
+        res: [&str; M],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule_weighted(&cond, &res, weight)
+    }
+
+    /// weight of the rule at `index`, in the order rules were added
+    pub fn set_rule_weight(&mut self, index: usize, weight: f64) {
+        self.engine.set_rule_weight(index, weight);
+    }
+
+    pub fn try_set_rule_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        self.engine.try_set_rule_weight(index, weight)
+    }
+
+    pub fn add_rule_expr(&mut self, cond: Antecedent, res: [&str; M]) {
+        self.engine.add_rule_expr(cond, &res);
+    }
+
+    pub fn try_add_rule_expr(
+        &mut self,
+        cond: Antecedent,
+        res: [&str; M],
+    ) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule_expr(cond, &res)
+    }
+
+    /// see `parse::parse_rule` for the syntax
+    pub fn add_rule_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.engine.add_rule_str(text)
+    }
+
+    /// one rule per line, see `parse::parse_rules`
+    pub fn add_rules_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.engine.add_rules_str(text)
+    }
+
+    /// weighted average or sum of the rule outputs, one crisp value per output variable
+    pub fn calculate(&self, inputs: [f64; N]) -> Vec<f64> {
+        self.engine.calculate(&inputs)
+    }
+}
+
+impl<const N: usize, const M: usize> Serialize for SugenoEngine<N, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.engine.serialize(serializer)
+    }
+}
+
+impl<'de, const N: usize, const M: usize> Deserialize<'de> for SugenoEngine<N, M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let engine = DynSugenoEngine::deserialize(deserializer)?;
+        if engine.inputs_var.len() != N {
+            let e = FuzzyError::Arity("inputs".into(), N, engine.inputs_var.len());
+            return Err(de::Error::custom(e));
+        }
+        if engine.outputs_var.len() != M {
+            let e = FuzzyError::Arity("outputs".into(), M, engine.outputs_var.len());
+            return Err(de::Error::custom(e));
+        }
+        Ok(SugenoEngine { engine })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn weighted_average() {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 10f64), "low"),
+                (&triangular(10f64, 1.0, 10f64), "high"),
+            ],
+            arange(0f64, 10f64, 0.01),
+        );
+        let y = SugenoVar::new(vec![
+            (constant(10.0), "small"),
+            (linear(vec![2.0], 1.0), "big"),
+        ]);
+
+        let mut engine = SugenoEngine::new([x.clone()], [y.clone()]);
+        engine.add_rule(["low"], ["small"]);
+        engine.add_rule(["high"], ["big"]);
+
+        // low = 0.75, high = 0.25 -> (0.75 * 10 + 0.25 * 6) / 1.0
+        let res = engine.calculate([2.5]);
+        assert!((res[0] - 9.0).abs() < 1e-9);
+        assert_eq!(engine.calculate([10.0])[0], 21.0);
+
+        let mut engine = SugenoEngine::new([x.named("x")], [y.named("y")]);
+        engine
+            .add_rules_str("IF x IS low THEN y IS small\nIF x IS high THEN y IS big WITH 0.5")
+            .unwrap();
+        // (0.75 * 10 + 0.125 * 6) / 0.875
+        assert!((engine.calculate([2.5])[0] - 8.25 / 0.875).abs() < 1e-9);
+
+        let engine = engine.into_dyn();
+        assert_eq!(
+            engine.calculate_named(&[("x", 2.5)]),
+            engine.calculate(&[2.5])
+        );
+        let json = serde_json::to_string(&engine).unwrap();
+        let copy: DynSugenoEngine = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.calculate(&[2.5]), engine.calculate(&[2.5]));
+        assert!(SugenoEngine::<2, 1>::from_dyn(engine.clone()).is_none());
+
+        let mut engine = engine;
+        engine.set_method(SugenoMethod::WeightedSum);
+        assert!((engine.calculate(&[2.5])[0] - 8.25).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_coefficients() {
+        let x = LinguisticVar::new(
+            vec![(&triangular(0f64, 1.0, 10f64), "low")],
+            arange(0f64, 10f64, 0.01),
+        );
+        let y = SugenoVar::new(vec![(linear(vec![1.0, 2.0], 0.0), "big")]);
+        SugenoEngine::new([x], [y]);
+    }
+}
@@ -0,0 +1,345 @@
+// what an engine does over its input space: two-input control surfaces and one-input response curves
+
+use crate::error::{or_panic, FuzzyError};
+use crate::parse::label;
+use crate::rule::{DynFuzzyEngine, FuzzyEngine};
+use crate::table::spaced;
+use plotters::prelude::*;
+use std::error::Error;
+
+/// crisp output sampled over a grid of two inputs
+#[derive(Debug, Clone)]
+pub struct Surface {
+    pub x_name: String,
+    pub y_name: String,
+    pub z_name: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub z: Vec<Vec<f64>>,    // z[i][j] is the output at (x[i], y[j])
+    pub z_range: (f64, f64), // universe of the output
+}
+
+/// crisp output along one input with the others held fixed
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub x_name: String,
+    pub y_name: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub y_range: (f64, f64), // universe of the output
+}
+
+impl DynFuzzyEngine {
+    /// sweep inputs `x` and `y` over their universes with `points` values each.
+    /// the other inputs take their value from `fixed`, which has one value per input
+    pub fn surface(
+        &self,
+        x: usize,
+        y: usize,
+        output: usize,
+        fixed: &[f64],
+        points: usize,
+    ) -> Surface {
+        or_panic(self.try_surface(x, y, output, fixed, points))
+    }
+
+    pub fn try_surface(
+        &self,
+        x: usize,
+        y: usize,
+        output: usize,
+        fixed: &[f64],
+        points: usize,
+    ) -> Result<Surface, FuzzyError> {
+        self.check_sweep(&[x, y], output, fixed)?;
+        let xs = spaced(&self.inputs()[x], points)?;
+        let ys = spaced(&self.inputs()[y], points)?;
+        let mut rows: Vec<Vec<f64>> = Vec::with_capacity(points * points);
+        for a in xs.iter() {
+            for b in ys.iter() {
+                let mut row = fixed.to_vec();
+                row[x] = *a;
+                row[y] = *b;
+                rows.push(row);
+            }
+        }
+        let res = self.try_defuzzify_batch(&rows)?;
+        let z = res
+            .chunks(points)
+            .map(|chunk| chunk.iter().map(|out| out[output]).collect())
+            .collect();
+
+        let universe = &self.outputs()[output].universe;
+        Ok(Surface {
+            x_name: label(&self.inputs()[x], x, "input"),
+            y_name: label(&self.inputs()[y], y, "input"),
+            z_name: label(&self.outputs()[output], output, "output"),
+            x: xs,
+            y: ys,
+            z,
+            z_range: (universe[0], universe[universe.len() - 1]),
+        })
+    }
+
+    /// sweep `input` over its universe with `points` values, the other inputs take
+    /// their value from `fixed`, which has one value per input
+    pub fn response(&self, input: usize, output: usize, fixed: &[f64], points: usize) -> Response {
+        or_panic(self.try_response(input, output, fixed, points))
+    }
+
+    pub fn try_response(
+        &self,
+        input: usize,
+        output: usize,
+        fixed: &[f64],
+        points: usize,
+    ) -> Result<Response, FuzzyError> {
+        self.check_sweep(&[input], output, fixed)?;
+        let xs = spaced(&self.inputs()[input], points)?;
+        let rows: Vec<Vec<f64>> = xs
+            .iter()
+            .map(|a| {
+                let mut row = fixed.to_vec();
+                row[input] = *a;
+                row
+            })
+            .collect();
+        let ys = self
+            .try_defuzzify_batch(&rows)?
+            .iter()
+            .map(|out| out[output])
+            .collect();
+
+        let universe = &self.outputs()[output].universe;
+        Ok(Response {
+            x_name: label(&self.inputs()[input], input, "input"),
+            y_name: label(&self.outputs()[output], output, "output"),
+            x: xs,
+            y: ys,
+            y_range: (universe[0], universe[universe.len() - 1]),
+        })
+    }
+
+    fn check_sweep(
+        &self,
+        inputs: &[usize],
+        output: usize,
+        fixed: &[f64],
+    ) -> Result<(), FuzzyError> {
+        for i in inputs {
+            if *i >= self.inputs().len() {
+                return Err(FuzzyError::UnknownVariable(format!("#{}", i)));
+            }
+        }
+        if output >= self.outputs().len() {
+            return Err(FuzzyError::UnknownVariable(format!("#{}", output)));
+        }
+        if fixed.len() != self.inputs().len() {
+            return Err(FuzzyError::Arity(
+                "inputs".into(),
+                self.inputs().len(),
+                fixed.len(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// see `DynFuzzyEngine::surface`
+    pub fn surface(
+        &self,
+        x: usize,
+        y: usize,
+        output: usize,
+        fixed: [f64; N],
+        points: usize,
+    ) -> Surface {
+        self.as_dyn().surface(x, y, output, &fixed, points)
+    }
+
+    /// see `DynFuzzyEngine::response`
+    pub fn response(
+        &self,
+        input: usize,
+        output: usize,
+        fixed: [f64; N],
+        points: usize,
+    ) -> Response {
+        self.as_dyn().response(input, output, &fixed, points)
+    }
+}
+
+/// blue for the bottom of the output universe to red for the top
+fn heat(value: f64, range: (f64, f64)) -> HSLColor {
+    let t = ((value - range.0) / (range.1 - range.0)).clamp(0.0, 1.0);
+    HSLColor(0.66 * (1.0 - t), 0.8, 0.5)
+}
+
+impl Surface {
+    /// top view, each grid cell coloured by its mean output
+    pub fn plot_heatmap(&self, name: String, path: String) -> Result<(), Box<dyn Error>> {
+        let root = SVGBackend::new(&path, (1024, 768)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let n = self.x.len();
+        let mut chart = ChartBuilder::on(&root)
+            .caption(name, ("Hack", 44, FontStyle::Bold).into_font())
+            .set_label_area_size(LabelAreaPosition::Left, 60)
+            .set_label_area_size(LabelAreaPosition::Bottom, 60)
+            .margin(20)
+            .build_cartesian_2d(self.x[0]..self.x[n - 1], self.y[0]..self.y[n - 1])?;
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .x_desc(self.x_name.as_str())
+            .y_desc(self.y_name.as_str())
+            .draw()?;
+
+        chart.draw_series((0..n - 1).flat_map(|i| {
+            (0..n - 1).map(move |j| {
+                let z = (self.z[i][j] + self.z[i + 1][j] + self.z[i][j + 1] + self.z[i + 1][j + 1])
+                    / 4.0;
+                Rectangle::new(
+                    [(self.x[i], self.y[j]), (self.x[i + 1], self.y[j + 1])],
+                    heat(z, self.z_range).filled(),
+                )
+            })
+        }))?;
+
+        root.present()?;
+        Ok(())
+    }
+
+    pub fn plot_3d(&self, name: String, path: String) -> Result<(), Box<dyn Error>> {
+        let root = SVGBackend::new(&path, (1024, 768)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let n = self.x.len();
+        let mut chart = ChartBuilder::on(&root)
+            .caption(name, ("Hack", 44, FontStyle::Bold).into_font())
+            .margin(20)
+            .build_cartesian_3d(
+                self.x[0]..self.x[n - 1],
+                self.z_range.0..self.z_range.1,
+                self.y[0]..self.y[n - 1],
+            )?;
+
+        chart.with_projection(|mut pb| {
+            pb.yaw = 0.5;
+            pb.scale = 0.9;
+            pb.into_matrix()
+        });
+
+        chart
+            .configure_axes()
+            .light_grid_style(BLACK.mix(0.15))
+            .max_light_lines(3)
+            .draw()?;
+
+        // the series gives back grid values, look up their position in the grid
+        let index = |values: &[f64], v: f64| values.iter().position(|x| *x == v).unwrap_or(0);
+        let range = self.z_range;
+        let style = move |z: &f64| heat(*z, range).mix(0.8).filled();
+        chart.draw_series(
+            SurfaceSeries::xoz(self.x.iter().copied(), self.y.iter().copied(), |a, b| {
+                self.z[index(&self.x, a)][index(&self.y, b)]
+            })
+            .style_func(&style),
+        )?;
+
+        let axis_style = ("Hack", 20, &BLACK).into_text_style(&root);
+        chart.draw_series(
+            [
+                (
+                    self.x_name.as_str(),
+                    (self.x[n - 1], self.z_range.0, self.y[0]),
+                ),
+                (self.z_name.as_str(), (self.x[0], self.z_range.1, self.y[0])),
+                (
+                    self.y_name.as_str(),
+                    (self.x[0], self.z_range.0, self.y[n - 1]),
+                ),
+            ]
+            .map(|(label, position)| Text::new(label.to_string(), position, &axis_style)),
+        )?;
+
+        root.present()?;
+        Ok(())
+    }
+}
+
+impl Response {
+    pub fn plot(&self, name: String, path: String) -> Result<(), Box<dyn Error>> {
+        let root = SVGBackend::new(&path, (1024, 768)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption(name, ("Hack", 44, FontStyle::Bold).into_font())
+            .set_label_area_size(LabelAreaPosition::Left, 60)
+            .set_label_area_size(LabelAreaPosition::Bottom, 60)
+            .margin(20)
+            .build_cartesian_2d(
+                self.x[0]..self.x[self.x.len() - 1],
+                self.y_range.0..self.y_range.1,
+            )?;
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_desc(self.x_name.as_str())
+            .y_desc(self.y_name.as_str())
+            .draw()?;
+
+        chart.draw_series(LineSeries::new(
+            self.x.iter().zip(self.y.iter()).map(|(x, y)| (*x, *y)),
+            Palette99::pick(0).stroke_width(2),
+        ))?;
+
+        root.present()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::*;
+    use crate::shape::*;
+
+    #[test]
+    fn sweeps() -> Result<(), Box<dyn Error>> {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 100f64), "low"),
+                (&triangular(100f64, 1.0, 100f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.1),
+        );
+        let mut engine = FuzzyEngine::new([x.clone().named("rsi"), x.clone()], [x]);
+        engine.add_rule(["low", "low"], ["low"]);
+        engine.add_rule(["high", "any"], ["high"]);
+        engine.add_rule(["any", "high"], ["high"]);
+
+        let surface = engine.surface(0, 1, 0, [0.0, 0.0], 11);
+        assert_eq!(surface.x_name, "rsi");
+        assert_eq!(surface.y_name, "input1");
+        assert_eq!(surface.z.len(), 11);
+        assert_eq!(surface.z[3][7], engine.defuzzify([30.0, 70.0])[0]);
+        surface.plot_heatmap("surface".into(), "img/test/heatmap.svg".into())?;
+        surface.plot_3d("surface".into(), "img/test/surface.svg".into())?;
+
+        let response = engine.response(1, 0, [20.0, 0.0], 21);
+        assert_eq!(response.y[10], engine.defuzzify([20.0, 50.0])[0]);
+        response.plot("response".into(), "img/test/response.svg".into())?;
+
+        let dynamic = engine.as_dyn();
+        assert!(dynamic.try_surface(0, 2, 0, &[0.0, 0.0], 11).is_err());
+        assert!(dynamic.try_response(0, 1, &[0.0, 0.0], 11).is_err());
+        assert!(dynamic.try_response(0, 0, &[0.0], 11).is_err());
+        assert!(dynamic.try_response(0, 0, &[0.0, 0.0], 1).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+// crisp outputs of an engine precomputed over a grid and answered by n-linear interpolation
+
+use crate::error::{or_panic, FuzzyError};
+use crate::rule::{DynFuzzyEngine, FuzzyEngine};
+use crate::set::LinguisticVar;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LookupTable {
+    axes: Vec<Vec<f64>>,   // increasing grid points of each input
+    values: Vec<Vec<f64>>, // for each output, grid values with the last axis varying fastest
+    max_error: Vec<f64>,   // for each output, largest error seen at the cell and edge midpoints
+}
+
+impl LookupTable {
+    /// `points[i]` evenly spaced grid points over the universe of input i
+    pub fn new(engine: &DynFuzzyEngine, points: &[usize]) -> LookupTable {
+        or_panic(LookupTable::try_new(engine, points))
+    }
+
+    pub fn try_new(engine: &DynFuzzyEngine, points: &[usize]) -> Result<LookupTable, FuzzyError> {
+        if points.len() != engine.inputs().len() {
+            return Err(FuzzyError::Arity(
+                "grid sizes".into(),
+                engine.inputs().len(),
+                points.len(),
+            ));
+        }
+        let axes = engine
+            .inputs()
+            .iter()
+            .zip(points.iter())
+            .map(|(var, n)| spaced(var, *n))
+            .collect::<Result<_, _>>()?;
+        LookupTable::try_with_axes(engine, axes)
+    }
+
+    /// grid given explicitly, every axis needs at least two increasing points.
+    /// the error estimate evaluates the engine on a grid twice as fine, about 2^inputs
+    /// evaluations per grid cell
+    pub fn with_axes(engine: &DynFuzzyEngine, axes: Vec<Vec<f64>>) -> LookupTable {
+        or_panic(LookupTable::try_with_axes(engine, axes))
+    }
+
+    pub fn try_with_axes(
+        engine: &DynFuzzyEngine,
+        axes: Vec<Vec<f64>>,
+    ) -> Result<LookupTable, FuzzyError> {
+        if axes.len() != engine.inputs().len() {
+            return Err(FuzzyError::Arity(
+                "axes".into(),
+                engine.inputs().len(),
+                axes.len(),
+            ));
+        }
+        for axis in axes.iter() {
+            if axis.len() < 2 || !axis.windows(2).all(|w| w[0] < w[1]) {
+                return Err(FuzzyError::InvalidRange(
+                    "grid axes need at least two increasing points".into(),
+                ));
+            }
+        }
+
+        let nodes = grid(&axes.iter().map(|a| a.as_slice()).collect::<Vec<&[f64]>>());
+        let mut values = vec![Vec::with_capacity(nodes.len()); engine.outputs().len()];
+        for out in evaluate(engine, &nodes)? {
+            for (column, x) in values.iter_mut().zip(out) {
+                column.push(x);
+            }
+        }
+        let mut table = LookupTable {
+            axes,
+            values,
+            max_error: vec![],
+        };
+
+        // every node and the midpoint after it along each axis, the interpolation is exact
+        // where all coordinates are nodes so those are left out
+        let fine: Vec<Vec<f64>> = table
+            .axes
+            .iter()
+            .map(|a| {
+                a.windows(2)
+                    .flat_map(|w| [w[0], (w[0] + w[1]) / 2.0])
+                    .chain([a[a.len() - 1]])
+                    .collect()
+            })
+            .collect();
+        let samples: Vec<Vec<f64>> =
+            grid(&fine.iter().map(|a| a.as_slice()).collect::<Vec<&[f64]>>())
+                .into_iter()
+                .filter(|x| {
+                    !x.iter()
+                        .zip(table.axes.iter())
+                        .all(|(x, axis)| axis.binary_search_by(|a| a.total_cmp(x)).is_ok())
+                })
+                .collect();
+        let mut max_error = vec![0f64; engine.outputs().len()];
+        for (x, exact) in samples.iter().zip(evaluate(engine, &samples)?) {
+            for (e, (a, b)) in max_error
+                .iter_mut()
+                .zip(table.calculate(x).iter().zip(exact.iter()))
+            {
+                *e = e.max((a - b).abs());
+            }
+        }
+        table.max_error = max_error;
+        Ok(table)
+    }
+
+    pub fn axes(&self) -> &[Vec<f64>] {
+        &self.axes
+    }
+
+    /// largest absolute difference from the engine found at the centres of the grid cells and
+    /// the midpoints of their edges and faces, one value per output. it's an estimate, not a
+    /// bound: the error can be larger between those points
+    pub fn max_error(&self) -> &[f64] {
+        &self.max_error
+    }
+
+    /// interpolated outputs, inputs outside the grid are clamped to its edges
+    pub fn calculate(&self, inputs: &[f64]) -> Vec<f64> {
+        or_panic(self.try_calculate(inputs))
+    }
+
+    pub fn try_calculate(&self, inputs: &[f64]) -> Result<Vec<f64>, FuzzyError> {
+        if inputs.len() != self.axes.len() {
+            return Err(FuzzyError::Arity(
+                "inputs".into(),
+                self.axes.len(),
+                inputs.len(),
+            ));
+        }
+        // lower grid index and position inside the cell along each axis
+        let cell: Vec<(usize, f64)> = self
+            .axes
+            .iter()
+            .zip(inputs.iter())
+            .map(|(axis, x)| {
+                let x = x.clamp(axis[0], axis[axis.len() - 1]);
+                let i = axis.partition_point(|a| *a <= x).clamp(1, axis.len() - 1) - 1;
+                (i, (x - axis[i]) / (axis[i + 1] - axis[i]))
+            })
+            .collect();
+        let strides = self.strides();
+
+        let mut res = vec![0.0; self.values.len()];
+        for corner in 0..(1usize << self.axes.len()) {
+            let mut index = 0;
+            let mut weight = 1.0;
+            for (d, (i, t)) in cell.iter().enumerate() {
+                if corner >> d & 1 == 1 {
+                    index += (i + 1) * strides[d];
+                    weight *= t;
+                } else {
+                    index += i * strides[d];
+                    weight *= 1.0 - t;
+                }
+            }
+            if weight == 0.0 {
+                continue;
+            }
+            for (r, values) in res.iter_mut().zip(self.values.iter()) {
+                *r += weight * values[index];
+            }
+        }
+        Ok(res)
+    }
+
+    fn strides(&self) -> Vec<usize> {
+        let mut strides = vec![1; self.axes.len()];
+        for d in (0..self.axes.len().saturating_sub(1)).rev() {
+            strides[d] = strides[d + 1] * self.axes[d + 1].len();
+        }
+        strides
+    }
+}
+
+/// `n` evenly spaced points from the first to the last value of the universe of `var`,
+/// at least two of them
+pub(crate) fn spaced(var: &LinguisticVar, n: usize) -> Result<Vec<f64>, FuzzyError> {
+    if n < 2 {
+        return Err(FuzzyError::InvalidRange(
+            "need at least two points over a universe".into(),
+        ));
+    }
+    let (lo, hi) = (var.universe[0], var.universe[var.universe.len() - 1]);
+    let step = (hi - lo) / (n - 1) as f64;
+    Ok((0..n).map(|k| lo + step * k as f64).collect())
+}
+
+/// every combination of axis points, the last axis varying fastest
+pub(crate) fn grid(axes: &[&[f64]]) -> Vec<Vec<f64>> {
+    let mut points: Vec<Vec<f64>> = vec![vec![]];
+    for axis in axes {
+        points = points
+            .iter()
+            .flat_map(|p| {
+                axis.iter().map(move |x| {
+                    let mut p = p.clone();
+                    p.push(*x);
+                    p
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+fn evaluate(engine: &DynFuzzyEngine, rows: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, FuzzyError> {
+    #[cfg(feature = "parallel")]
+    return engine.try_par_defuzzify_batch(rows);
+    #[cfg(not(feature = "parallel"))]
+    engine.try_defuzzify_batch(rows)
+}
+
+impl DynFuzzyEngine {
+    /// see `LookupTable::new`
+    pub fn lookup_table(&self, points: &[usize]) -> LookupTable {
+        LookupTable::new(self, points)
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// `points[i]` evenly spaced grid points over the universe of input i
+    pub fn lookup_table(&self, points: [usize; N]) -> LookupTable {
+        LookupTable::new(self.as_dyn(), &points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::*;
+    use crate::shape::*;
+
+    #[test]
+    fn interpolation() {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 100f64), "low"),
+                (&triangular(100f64, 1.0, 100f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.1),
+        );
+        let mut engine = FuzzyEngine::new([x.clone(), x.clone()], [x]);
+        engine.add_rule(["low", "low"], ["low"]);
+        engine.add_rule(["high", "any"], ["high"]);
+        engine.add_rule(["any", "high"], ["high"]);
+
+        let table = engine.lookup_table([21, 11]);
+        assert_eq!(table.axes()[0].len(), 21);
+        assert_eq!(table.axes()[1][1], 10.0);
+
+        // exact on the grid nodes, clamped outside it
+        assert_eq!(
+            table.calculate(&[15.0, 30.0]),
+            engine.defuzzify([15.0, 30.0])
+        );
+        assert_eq!(
+            table.calculate(&[120.0, -5.0]),
+            engine.defuzzify([100.0, 0.0])
+        );
+
+        // the estimate is the worst of the sampled midpoints, and holds here between them too
+        let error =
+            |a: f64, b: f64| (table.calculate(&[a, b])[0] - engine.defuzzify([a, b])[0]).abs();
+        let midpoints = [(12.5, 30.0), (70.0, 5.0), (97.5, 95.0), (2.5, 55.0)];
+        let worst = midpoints
+            .iter()
+            .fold(0f64, |m, (a, b)| m.max(error(*a, *b)));
+        assert!(worst > 0.0);
+        assert!(
+            worst <= table.max_error()[0],
+            "{} {:?}",
+            worst,
+            table.max_error()
+        );
+        for (a, b) in [(12.5, 33.0), (71.0, 4.0), (99.0, 99.0), (3.3, 58.8)] {
+            assert!(error(a, b) <= table.max_error()[0]);
+        }
+
+        let finer = engine.lookup_table([81, 41]);
+        assert!(finer.max_error()[0] < table.max_error()[0]);
+
+        assert!(LookupTable::try_new(engine.as_dyn(), &[10]).is_err());
+        assert!(
+            LookupTable::try_with_axes(engine.as_dyn(), vec![vec![0.0, 1.0], vec![2.0, 1.0]])
+                .is_err()
+        );
+        assert!(table.try_calculate(&[1.0]).is_err());
+    }
+}
@@ -0,0 +1,481 @@
+// genetic algorithm tuning the parameters of every analytic term of an engine against a fitness.
+// runs are reproducible: the population is evolved by a seeded StdRng and evaluated in order
+
+use crate::error::{or_panic, FuzzyError};
+use crate::rule::{DynFuzzyEngine, FuzzyEngine};
+use crate::set::{FuzzySet, LinguisticVar};
+use crate::shape::from_params;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// settings of the genetic algorithm, `fitness` is maximised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneticTuner {
+    pub population: usize,
+    pub generations: usize,
+    pub elitism: usize, // best individuals copied unchanged into the next generation
+    pub tournament: usize, // individuals competing for each parent
+    pub crossover_rate: f64, // chance two parents are blended instead of copied
+    pub mutation_rate: f64, // chance each parameter is mutated
+    pub mutation_scale: f64, // standard deviation of a mutation, relative to the universe width
+    pub seed: u64,
+}
+
+impl Default for GeneticTuner {
+    fn default() -> Self {
+        GeneticTuner {
+            population: 30,
+            generations: 50,
+            elitism: 2,
+            tournament: 3,
+            crossover_rate: 0.8,
+            mutation_rate: 0.1,
+            mutation_scale: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tuning<E> {
+    pub engine: E, // best engine found
+    pub fitness: f64,
+    pub history: Vec<f64>, // best fitness of the initial population, then after each generation
+}
+
+/// what the tuner may do to one shape parameter
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    Position, // a point of the universe, kept inside it and in order within the term
+    Width,    // a positive spread
+    Fixed,    // heights, slopes and the parameters of unknown shapes
+}
+
+fn roles(kind: &str, n: usize) -> Vec<Role> {
+    use Role::*;
+    match kind {
+        "triangular" => vec![Position, Fixed, Width],
+        "trapezoidal" => vec![Position, Position, Position, Position, Fixed],
+        "gaussian" => vec![Position, Width],
+        "gauss2" => vec![Position, Width, Position, Width],
+        "bell" => vec![Width, Fixed, Position],
+        "sigmoid" => vec![Fixed, Position],
+        "dsigmoid" | "psigmoid" => vec![Fixed, Position, Fixed, Position],
+        "s_shape" | "z_shape" | "pi_shape" => vec![Position; n],
+        "points" => (0..n)
+            .map(|i| match i % 2 {
+                0 => Position,
+                _ => Fixed,
+            })
+            .collect(),
+        _ => vec![Fixed; n],
+    }
+}
+
+/// one analytic term and where its parameters sit in the chromosome
+struct Gene {
+    output: bool,
+    var: usize,
+    set: usize,
+    kind: &'static str,
+    start: usize,
+    roles: Vec<Role>,
+    bounds: (f64, f64), // universe of the variable
+}
+
+impl Gene {
+    fn positions<'a>(&'a self, genes: &'a mut [f64]) -> impl Iterator<Item = &'a mut f64> {
+        genes[self.start..self.start + self.roles.len()]
+            .iter_mut()
+            .zip(self.roles.iter())
+            .filter(|(_, r)| **r == Role::Position)
+            .map(|(g, _)| g)
+    }
+
+    fn centre(&self, genes: &[f64]) -> f64 {
+        let positions: Vec<f64> = self
+            .roles
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| **r == Role::Position)
+            .map(|(i, _)| genes[self.start + i])
+            .collect();
+        positions.iter().sum::<f64>() / positions.len() as f64
+    }
+}
+
+/// layout of the chromosome: the terms in order, and per variable the terms sorted by
+/// their initial centre, which the constraints keep
+struct Layout {
+    genes: Vec<Gene>,
+    order: Vec<Vec<usize>>,
+}
+
+impl Layout {
+    fn new(engine: &DynFuzzyEngine) -> (Layout, Vec<f64>) {
+        let mut genes = vec![];
+        let mut chromosome = vec![];
+        let mut order = vec![];
+        let vars = engine
+            .inputs()
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (false, i, v))
+            .chain(
+                engine
+                    .outputs()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (true, i, v)),
+            );
+        for (output, var, v) in vars {
+            let bounds = (v.universe[0], v.universe[v.universe.len() - 1]);
+            let first = genes.len();
+            for (set, term) in v.sets.iter().enumerate() {
+                let shape = match &term.shape {
+                    Some(shape) => shape,
+                    None => continue,
+                };
+                let params = shape.params();
+                let roles = roles(shape.kind(), params.len());
+                if !roles.contains(&Role::Position) {
+                    continue;
+                }
+                genes.push(Gene {
+                    output,
+                    var,
+                    set,
+                    kind: shape.kind(),
+                    start: chromosome.len(),
+                    roles,
+                    bounds,
+                });
+                chromosome.extend(params);
+            }
+            let mut sorted: Vec<usize> = (first..genes.len()).collect();
+            sorted.sort_by(|a, b| {
+                genes[*a]
+                    .centre(&chromosome)
+                    .total_cmp(&genes[*b].centre(&chromosome))
+            });
+            order.push(sorted);
+        }
+        (Layout { genes, order }, chromosome)
+    }
+
+    /// keep positions inside the universe and in order, widths positive,
+    /// and the centres of each variable's terms in their initial order
+    fn repair(&self, genes: &mut [f64]) {
+        for g in self.genes.iter() {
+            let (lo, hi) = g.bounds;
+            for (i, role) in g.roles.iter().enumerate() {
+                let x = &mut genes[g.start + i];
+                match role {
+                    Role::Position => *x = x.clamp(lo, hi),
+                    Role::Width => *x = x.clamp((hi - lo) * 1e-3, hi - lo),
+                    Role::Fixed => {}
+                }
+            }
+            let mut positions: Vec<f64> = g.positions(genes).map(|x| *x).collect();
+            positions.sort_by(|a, b| a.total_cmp(b));
+            for (x, p) in g.positions(genes).zip(positions) {
+                *x = p;
+            }
+        }
+        for sorted in self.order.iter() {
+            for w in sorted.windows(2) {
+                let (prev, g) = (&self.genes[w[0]], &self.genes[w[1]]);
+                let floor = prev.centre(genes);
+                let delta = floor - g.centre(genes);
+                if delta <= 0.0 {
+                    continue;
+                }
+                g.positions(genes)
+                    .for_each(|x| *x = (*x + delta).min(g.bounds.1));
+                if g.centre(genes) < floor {
+                    g.positions(genes).for_each(|x| *x = x.max(floor));
+                }
+            }
+        }
+    }
+
+    /// write the terms of the chromosome into the engine
+    fn decode(&self, genes: &[f64], engine: &mut DynFuzzyEngine) {
+        let (inputs, outputs) = engine.vars_mut();
+        for g in self.genes.iter() {
+            let var: &mut LinguisticVar = match g.output {
+                false => &mut inputs[g.var],
+                true => &mut outputs[g.var],
+            };
+            let params = &genes[g.start..g.start + g.roles.len()];
+            // the kind and number of parameters come from an existing shape
+            let shape = from_params(g.kind, params).unwrap();
+            let name = std::mem::take(&mut var.sets[g.set].name);
+            var.sets[g.set] = FuzzySet::analytic(&var.universe, shape.as_ref(), name);
+        }
+    }
+}
+
+/// standard normal sample, Box-Muller
+fn normal(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+impl GeneticTuner {
+    /// evolve the term parameters of `engine`, rules and operators are left as they are.
+    /// fitness is maximised, NaN counts as the worst possible value
+    pub fn tune(
+        &self,
+        engine: &DynFuzzyEngine,
+        fitness: impl FnMut(&DynFuzzyEngine) -> f64,
+    ) -> Tuning<DynFuzzyEngine> {
+        or_panic(self.try_tune(engine, fitness))
+    }
+
+    pub fn try_tune(
+        &self,
+        engine: &DynFuzzyEngine,
+        fitness: impl FnMut(&DynFuzzyEngine) -> f64,
+    ) -> Result<Tuning<DynFuzzyEngine>, FuzzyError> {
+        self.evolve(engine, engine.clone(), |e| e, fitness)
+    }
+
+    fn check(&self) -> Result<(), FuzzyError> {
+        let rate = |r: f64| (0.0..=1.0).contains(&r);
+        if self.population < 2 || self.elitism >= self.population || self.tournament == 0 {
+            return Err(FuzzyError::InvalidRange(
+                "needs a population of at least 2, larger than the elitism, and a tournament of at least 1".into(),
+            ));
+        }
+        if !rate(self.crossover_rate) || !rate(self.mutation_rate) {
+            return Err(FuzzyError::InvalidRange(
+                "crossover and mutation rates must be in [0, 1]".into(),
+            ));
+        }
+        if !self.mutation_scale.is_finite() || self.mutation_scale < 0.0 {
+            return Err(FuzzyError::InvalidRange(
+                "mutation scale must be finite and >= 0".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `candidate` is the engine handed to `fitness`, `as_dyn` gives the dynamic engine inside it
+    fn evolve<E: Clone>(
+        &self,
+        engine: &DynFuzzyEngine,
+        mut candidate: E,
+        as_dyn: impl Fn(&mut E) -> &mut DynFuzzyEngine,
+        mut fitness: impl FnMut(&E) -> f64,
+    ) -> Result<Tuning<E>, FuzzyError> {
+        self.check()?;
+        let (layout, initial) = Layout::new(engine);
+        if layout.genes.is_empty() {
+            return Err(FuzzyError::InvalidRange(
+                "the engine has no analytic terms to tune".into(),
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut evaluate = |genes: &[f64]| {
+            layout.decode(genes, as_dyn(&mut candidate));
+            let f = fitness(&candidate);
+            match f.is_nan() {
+                true => f64::NEG_INFINITY,
+                false => f,
+            }
+        };
+
+        let mutate = |genes: &mut Vec<f64>, rate: f64, rng: &mut StdRng| {
+            for g in layout.genes.iter() {
+                let scale = self.mutation_scale * (g.bounds.1 - g.bounds.0);
+                for (i, role) in g.roles.iter().enumerate() {
+                    if *role != Role::Fixed && rng.gen::<f64>() < rate {
+                        genes[g.start + i] += scale * normal(rng);
+                    }
+                }
+            }
+            layout.repair(genes);
+        };
+
+        // the engine as given, and mutants of it
+        let mut population: Vec<(Vec<f64>, f64)> = vec![];
+        let mut start = initial.clone();
+        layout.repair(&mut start);
+        population.push((start.clone(), evaluate(&start)));
+        while population.len() < self.population {
+            let mut genes = start.clone();
+            mutate(&mut genes, 1.0, &mut rng);
+            let f = evaluate(&genes);
+            population.push((genes, f));
+        }
+        population.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut history = vec![population[0].1];
+
+        for _ in 0..self.generations {
+            let mut next: Vec<(Vec<f64>, f64)> = population[..self.elitism].to_vec();
+            while next.len() < self.population {
+                let pick = |rng: &mut StdRng| {
+                    (0..self.tournament)
+                        .map(|_| rng.gen_range(0..population.len()))
+                        .min()
+                        .unwrap()
+                };
+                let (a, b) = (pick(&mut rng), pick(&mut rng));
+                let mut child = population[a].0.clone();
+                if rng.gen::<f64>() < self.crossover_rate {
+                    // blend whole terms so their shape stays consistent
+                    for g in layout.genes.iter() {
+                        let t: f64 = rng.gen();
+                        let range = g.start..g.start + g.roles.len();
+                        for (x, y) in child[range.clone()].iter_mut().zip(&population[b].0[range]) {
+                            *x = t * *x + (1.0 - t) * y;
+                        }
+                    }
+                }
+                mutate(&mut child, self.mutation_rate, &mut rng);
+                let f = evaluate(&child);
+                next.push((child, f));
+            }
+            next.sort_by(|a, b| b.1.total_cmp(&a.1));
+            population = next;
+            history.push(population[0].1);
+        }
+
+        let (best, fitness) = population.swap_remove(0);
+        layout.decode(&best, as_dyn(&mut candidate));
+        Ok(Tuning {
+            engine: candidate,
+            fitness,
+            history,
+        })
+    }
+}
+
+impl DynFuzzyEngine {
+    /// see `GeneticTuner::tune`
+    pub fn tune(
+        &self,
+        tuner: &GeneticTuner,
+        fitness: impl FnMut(&DynFuzzyEngine) -> f64,
+    ) -> Tuning<DynFuzzyEngine> {
+        tuner.tune(self, fitness)
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// see `GeneticTuner::tune`
+    pub fn tune(
+        &self,
+        tuner: &GeneticTuner,
+        fitness: impl FnMut(&FuzzyEngine<N, M>) -> f64,
+    ) -> Tuning<FuzzyEngine<N, M>> {
+        or_panic(tuner.evolve(self.as_dyn(), self.clone(), |e| e.as_dyn_mut(), fitness))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::f_net_profit;
+    use crate::partition::Partition;
+    use crate::set::*;
+    use crate::shape::*;
+
+    fn engine() -> FuzzyEngine<1, 1> {
+        let x = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 30f64), "low"),
+                (&trapezoidal(20f64, 40f64, 60f64, 80f64, 1.0), "medium"),
+                (&gaussian(100f64, 15f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.5),
+        );
+        let y = LinguisticVar::uniform(Partition::Triangular, 3, arange(0f64, 100f64, 0.5));
+        let mut engine = FuzzyEngine::new([x], [y]);
+        engine.add_rule(["low"], ["high"]);
+        engine.add_rule(["medium"], ["low"]);
+        engine.add_rule(["high"], ["medium"]);
+        engine
+    }
+
+    #[test]
+    fn reproducible_and_constrained() {
+        let engine = engine();
+        // fit the curve y = x
+        let fitness = |e: &FuzzyEngine<1, 1>| {
+            -(0..=20)
+                .map(|i| {
+                    let x = i as f64 * 5.0;
+                    (e.defuzzify([x])[0] - x).powi(2)
+                })
+                .sum::<f64>()
+        };
+        let tuner = GeneticTuner {
+            population: 16,
+            generations: 15,
+            seed: 7,
+            ..GeneticTuner::default()
+        };
+
+        let a = engine.tune(&tuner, fitness);
+        let b = engine.tune(&tuner, fitness);
+        assert_eq!(a.fitness, b.fitness);
+        assert_eq!(a.history, b.history);
+        assert_eq!(a.history.len(), 16);
+        // elitism never loses the best individual
+        assert!(a.history.windows(2).all(|w| w[0] <= w[1]));
+        assert!(a.fitness > a.history[0]);
+        assert_eq!(a.fitness, fitness(&a.engine));
+
+        for var in a.engine.inputs().iter().chain(a.engine.outputs().iter()) {
+            assert!(var.quality().ordered, "{}", var.quality());
+            for set in var.sets.iter() {
+                let p = set.shape.as_ref().unwrap().params();
+                let positions: Vec<f64> = match set.shape.as_ref().unwrap().kind() {
+                    "triangular" => vec![p[0]],
+                    "trapezoidal" => p[..4].to_vec(),
+                    "gaussian" => vec![p[0]],
+                    _ => p.iter().step_by(2).copied().collect(),
+                };
+                assert!(positions.iter().all(|x| (0.0..=100.0).contains(x)));
+                assert!(positions.windows(2).all(|w| w[0] <= w[1]));
+            }
+        }
+        assert_eq!(a.engine.inputs()[0].sets[1].name, "medium");
+
+        let other = engine.tune(&GeneticTuner { seed: 8, ..tuner }, fitness);
+        assert_ne!(other.history, a.history);
+    }
+
+    #[test]
+    fn backtest_fitness() {
+        let engine = engine();
+        let price: Vec<f64> = (0..200)
+            .map(|i| 100.0 + 30.0 * (i as f64 / 15.0).sin())
+            .collect();
+        let signal: Vec<f64> = price
+            .iter()
+            .map(|p| 100.0 - (p - 70.0) * 100.0 / 60.0)
+            .collect();
+        let tuner = GeneticTuner {
+            population: 6,
+            generations: 3,
+            ..GeneticTuner::default()
+        };
+        let tuned = engine.as_dyn().tune(&tuner, |e| {
+            let out = e.defuzzify_columns(&[&signal])[0].clone();
+            f_net_profit(&price, &out, false)
+        });
+        assert!(tuned.fitness >= tuned.history[0]);
+
+        let bad = GeneticTuner {
+            elitism: 6,
+            ..tuner
+        };
+        assert!(bad.try_tune(engine.as_dyn(), |_| 0.0).is_err());
+        let flat = DynFuzzyEngine::new(vec![], vec![]);
+        assert!(tuner.try_tune(&flat, |_| 0.0).is_err());
+    }
+}
@@ -0,0 +1,802 @@
+// interval type-2 fuzzy sets and inference. every degree is an interval between a lower and
+// an upper membership function, the aggregated output footprint is type-reduced to an interval
+// of centroids by karnik-mendel or eiasc and defuzzified to its midpoint
+
+use crate::antecedent::Antecedent;
+use crate::error::{or_panic, FuzzyError};
+use crate::ops::Operators;
+use crate::parse::{ParseError, Terms};
+use crate::rule::{ordered_inputs, DynFuzzyEngine, FuzzyEngine, Rule, RuleBase};
+use crate::set::{FuzzySet, LinguisticVar};
+use crate::shape::Shape;
+use serde::{Deserialize, Serialize};
+
+/// term whose membership is only known to lie between `lower` and `upper`,
+/// the area in between is the footprint of uncertainty
+#[derive(Debug, Clone)]
+pub struct Type2Set {
+    pub name: String,
+    pub upper: FuzzySet,
+    pub lower: FuzzySet, // kept at or below `upper`
+}
+
+impl Type2Set {
+    /// analytic upper and lower sets, `lower` is clipped to `upper`
+    pub fn new(universe: &[f64], upper: &dyn Shape, lower: &dyn Shape, name: String) -> Type2Set {
+        let universe = universe.to_vec();
+        let upper = FuzzySet::analytic(&universe, upper, name.clone());
+        let mut lower = FuzzySet::analytic(&universe, lower, name.clone());
+        for (l, u) in lower.membership.iter_mut().zip(upper.membership.iter()) {
+            *l = l.min(*u);
+        }
+        Type2Set { name, upper, lower }
+    }
+
+    /// `set` as the upper membership and the same set scaled by 1 - `blur` as the lower one
+    fn blurred(set: &FuzzySet, blur: f64) -> Type2Set {
+        Type2Set {
+            name: set.name.clone(),
+            upper: set.clone(),
+            lower: set.scale(1.0 - blur, set.name.clone()),
+        }
+    }
+
+    /// (lower, upper) degree of `input`
+    pub fn degree_of(&self, input: f64) -> (f64, f64) {
+        let upper = self.upper.degree_of(input);
+        (self.lower.degree_of(input).min(upper), upper)
+    }
+
+    /// interval of the centroids of every type-1 set inside the footprint
+    pub fn centroid(&self, reducer: TypeReducer) -> (f64, f64) {
+        reducer.reduce(
+            &self.upper.universe,
+            &self.lower.membership,
+            &self.upper.membership,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Type2Var {
+    pub name: String, // used by text rules, empty by default
+    pub sets: Vec<Type2Set>,
+    pub universe: Vec<f64>,
+}
+
+impl Type2Var {
+    /// terms given as (upper, lower, name)
+    pub fn new(inputs: Vec<(&dyn Shape, &dyn Shape, &str)>, universe: Vec<f64>) -> Type2Var {
+        let sets = inputs
+            .into_iter()
+            .map(|(upper, lower, name)| Type2Set::new(&universe, upper, lower, name.to_string()))
+            .collect();
+        Type2Var {
+            name: String::new(),
+            sets,
+            universe,
+        }
+    }
+
+    /// every term of `var` with an uncertain height: the upper membership is the term itself
+    /// and the lower one the term scaled by 1 - `blur`, `blur` in [0, 1]. a blur of 0 behaves
+    /// exactly like the type-1 variable
+    pub fn from_type1(var: &LinguisticVar, blur: f64) -> Type2Var {
+        or_panic(Type2Var::try_from_type1(var, blur))
+    }
+
+    pub fn try_from_type1(var: &LinguisticVar, blur: f64) -> Result<Type2Var, FuzzyError> {
+        if !(0.0..=1.0).contains(&blur) {
+            return Err(FuzzyError::InvalidRange(format!(
+                "blur {} is not in [0, 1]",
+                blur
+            )));
+        }
+        Ok(Type2Var {
+            name: var.name.clone(),
+            sets: var
+                .sets
+                .iter()
+                .map(|set| Type2Set::blurred(set, blur))
+                .collect(),
+            universe: var.universe.clone(),
+        })
+    }
+
+    pub fn named(mut self, name: &str) -> Type2Var {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn term(&self, name: &str) -> &Type2Set {
+        or_panic(self.try_term(name))
+    }
+
+    pub fn try_term(&self, name: &str) -> Result<&Type2Set, FuzzyError> {
+        match self.sets.iter().find(|x| x.name == name) {
+            Some(x) => Ok(x),
+            None => Err(FuzzyError::UnknownTerm(self.name.clone(), name.to_string())),
+        }
+    }
+}
+
+impl Terms for Type2Var {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn term_index(&self, term: &str) -> Option<usize> {
+        self.sets.iter().position(|x| x.name == term)
+    }
+}
+
+/// algorithm finding the interval of centroids of a footprint of uncertainty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TypeReducer {
+    KarnikMendel, // iterates on the switch points from the mean of lower and upper
+    Eiasc,        // enhanced iterative algorithm with stop condition, one pass from each end
+}
+
+impl TypeReducer {
+    /// (left, right) centroid of the footprint between `lower` and `upper` sampled at the
+    /// increasing points `universe`, (0, 0) if the upper membership is 0 everywhere
+    pub fn reduce(&self, universe: &[f64], lower: &[f64], upper: &[f64]) -> (f64, f64) {
+        if upper.iter().all(|u| *u == 0.0) {
+            return (0.0, 0.0);
+        }
+        match self {
+            TypeReducer::KarnikMendel => karnik_mendel(universe, lower, upper),
+            TypeReducer::Eiasc => eiasc(universe, lower, upper),
+        }
+    }
+}
+
+fn weighted_mean(x: &[f64], w: &[f64]) -> f64 {
+    let top_sum = x.iter().zip(w.iter()).fold(0.0, |s, (x, w)| s + x * w);
+    let bot_sum = w.iter().fold(0.0, |s, w| s + w);
+    top_sum / bot_sum
+}
+
+fn karnik_mendel(x: &[f64], lower: &[f64], upper: &[f64]) -> (f64, f64) {
+    // the left centroid gives the points left of the switch their upper degree and the rest
+    // their lower one, the right centroid the other way round
+    let end = |left: bool| {
+        let mut w: Vec<f64> = lower
+            .iter()
+            .zip(upper.iter())
+            .map(|(l, u)| (l + u) / 2.0)
+            .collect();
+        let mut y = weighted_mean(x, &w);
+        let mut switch = usize::MAX;
+        // converges in at most as many steps as there are points
+        for _ in 0..=x.len() {
+            let k = x.partition_point(|xi| *xi <= y);
+            if k == switch {
+                break;
+            }
+            switch = k;
+            for (i, w) in w.iter_mut().enumerate() {
+                *w = match (i < k) == left {
+                    true => upper[i],
+                    false => lower[i],
+                };
+            }
+            y = weighted_mean(x, &w);
+        }
+        y
+    };
+    (end(true), end(false))
+}
+
+fn eiasc(x: &[f64], lower: &[f64], upper: &[f64]) -> (f64, f64) {
+    // start from the lower membership and raise points to their upper degree, from the left
+    // for the left centroid and from the right for the right one, until the centroid stops
+    // moving past the next point
+    let a0 = x.iter().zip(lower.iter()).fold(0.0, |s, (x, l)| s + x * l);
+    let b0 = lower.iter().fold(0.0, |s, l| s + l);
+    let n = x.len();
+
+    let (mut a, mut b, mut left) = (a0, b0, 0.0);
+    for i in 0..n {
+        let d = upper[i] - lower[i];
+        a += x[i] * d;
+        b += d;
+        left = a / b;
+        // NaN while every point so far has a membership of 0, keep going
+        if i + 1 == n || left <= x[i + 1] {
+            break;
+        }
+    }
+    let (mut a, mut b, mut right) = (a0, b0, 0.0);
+    for i in (0..n).rev() {
+        let d = upper[i] - lower[i];
+        a += x[i] * d;
+        b += d;
+        right = a / b;
+        if i == 0 || right >= x[i - 1] {
+            break;
+        }
+    }
+    (left, right)
+}
+
+/// (lower, upper) degree to which the inputs satisfy `antecedent`. norms and hedges are
+/// increasing so they apply to each bound, NOT swaps them
+fn eval_interval(
+    antecedent: &Antecedent,
+    inputs_var: &[Type2Var],
+    inputs: &[f64],
+    ops: &Operators,
+) -> (f64, f64) {
+    let eval = |a: &Antecedent| eval_interval(a, inputs_var, inputs, ops);
+    match antecedent {
+        Antecedent::Any => (1.0, 1.0),
+        Antecedent::Is(i, term) => inputs_var[*i].term(term).degree_of(inputs[*i]),
+        Antecedent::Not(a) => {
+            let (l, u) = eval(a);
+            (1.0 - u, 1.0 - l)
+        }
+        Antecedent::Hedge(h, a) => {
+            let (l, u) = eval(a);
+            (h.apply(l), h.apply(u))
+        }
+        Antecedent::And(v) => v.iter().fold((1.0, 1.0), |(sl, su), a| {
+            let (l, u) = eval(a);
+            (ops.and.apply(sl, l), ops.and.apply(su, u))
+        }),
+        Antecedent::Or(v) => v.iter().fold((0.0, 0.0), |(sl, su), a| {
+            let (l, u) = eval(a);
+            (ops.or.apply(sl, l), ops.or.apply(su, u))
+        }),
+    }
+}
+
+/// interval type-2 mamdani engine with its inputs and outputs only known at runtime.
+/// rules are the same as for `DynFuzzyEngine`, each fires with an interval of strengths
+#[derive(Debug, Clone)]
+pub struct DynType2Engine {
+    inputs_var: Vec<Type2Var>,
+    outputs_var: Vec<Type2Var>,
+    rules: RuleBase,
+    reducer: TypeReducer,
+    ops: Operators,
+}
+
+impl DynType2Engine {
+    pub fn new(inputs_var: Vec<Type2Var>, outputs_var: Vec<Type2Var>) -> DynType2Engine {
+        DynType2Engine {
+            inputs_var,
+            outputs_var,
+            rules: RuleBase::default(),
+            reducer: TypeReducer::KarnikMendel,
+            ops: Operators::default(),
+        }
+    }
+
+    /// same variables, rules and operators as `engine` with every term blurred like
+    /// `Type2Var::from_type1`. the type-1 defuzzifier is replaced by centroid type reduction,
+    /// so with a blur of 0 this matches `engine` using `Defuzzifier::Centroid`
+    pub fn from_type1(engine: &DynFuzzyEngine, blur: f64) -> DynType2Engine {
+        or_panic(DynType2Engine::try_from_type1(engine, blur))
+    }
+
+    pub fn try_from_type1(
+        engine: &DynFuzzyEngine,
+        blur: f64,
+    ) -> Result<DynType2Engine, FuzzyError> {
+        let blurred = |vars: &[LinguisticVar]| {
+            vars.iter()
+                .map(|var| Type2Var::try_from_type1(var, blur))
+                .collect::<Result<Vec<Type2Var>, FuzzyError>>()
+        };
+        let mut res = DynType2Engine {
+            inputs_var: blurred(engine.inputs())?,
+            outputs_var: blurred(engine.outputs())?,
+            rules: RuleBase::default(),
+            reducer: TypeReducer::KarnikMendel,
+            ops: engine.operators(),
+        };
+        for rule in engine.rules() {
+            res.try_push_rule(rule.clone())?;
+        }
+        Ok(res)
+    }
+
+    /// t-norm, s-norm, implication and aggregation, applied to the lower and upper
+    /// memberships separately. min/max by default
+    pub fn set_operators(&mut self, ops: Operators) {
+        or_panic(self.try_set_operators(ops))
+    }
+
+    /// fails for a Yager norm whose p isn't > 0
+    pub fn try_set_operators(&mut self, ops: Operators) -> Result<(), FuzzyError> {
+        ops.check()?;
+        self.ops = ops;
+        Ok(())
+    }
+
+    /// type reduction used by `reduce` and `defuzzify`, karnik-mendel by default
+    pub fn set_reducer(&mut self, reducer: TypeReducer) {
+        self.reducer = reducer;
+    }
+
+    pub fn inputs(&self) -> &[Type2Var] {
+        &self.inputs_var
+    }
+
+    pub fn outputs(&self) -> &[Type2Var] {
+        &self.outputs_var
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        self.rules.rules()
+    }
+
+    pub fn operators(&self) -> Operators {
+        self.ops
+    }
+
+    pub fn reducer(&self) -> TypeReducer {
+        self.reducer
+    }
+
+    pub fn input_index(&self, name: &str) -> Option<usize> {
+        self.inputs_var.iter().position(|v| v.name == name)
+    }
+
+    pub fn output_index(&self, name: &str) -> Option<usize> {
+        self.outputs_var.iter().position(|v| v.name == name)
+    }
+
+    /// add an already built rule, panics if it refers to a missing term
+    pub fn push_rule(&mut self, rule: Rule) {
+        or_panic(self.try_push_rule(rule))
+    }
+
+    pub fn try_push_rule(&mut self, rule: Rule) -> Result<(), FuzzyError> {
+        self.rules
+            .try_push(&self.inputs_var, &self.outputs_var, rule)
+    }
+
+    /// one condition per input joined by AND, see `DynFuzzyEngine::add_rule`
+    pub fn add_rule(&mut self, cond: &[&str], res: &[&str]) {
+        or_panic(self.try_add_rule(cond, res))
+    }
+
+    pub fn try_add_rule(&mut self, cond: &[&str], res: &[&str]) -> Result<(), FuzzyError> {
+        self.try_add_rule_weighted(cond, res, 1.0)
+    }
+
+    /// like `add_rule` with both firing strengths scaled by `weight` in [0, 1]
+    pub fn add_rule_weighted(&mut self, cond: &[&str], res: &[&str], weight: f64) {
+        or_panic(self.try_add_rule_weighted(cond, res, weight))
+    }
+
+    pub fn try_add_rule_weighted(
+        &mut self,
+        cond: &[&str],
+        res: &[&str],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add(&self.inputs_var, &self.outputs_var, cond, res, weight)
+    }
+
+    /// weight of the rule at `index`, in the order rules were added
+    pub fn set_rule_weight(&mut self, index: usize, weight: f64) {
+        or_panic(self.try_set_rule_weight(index, weight))
+    }
+
+    pub fn try_set_rule_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        self.rules.try_set_weight(index, weight)
+    }
+
+    /// rule with an arbitrary condition, e.g. `is(0, "low").or(!is(1, "short"))`
+    pub fn add_rule_expr(&mut self, cond: Antecedent, res: &[&str]) {
+        or_panic(self.try_add_rule_expr(cond, res))
+    }
+
+    pub fn try_add_rule_expr(&mut self, cond: Antecedent, res: &[&str]) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add_expr(&self.inputs_var, &self.outputs_var, cond, res, 1.0)
+    }
+
+    /// see `DynFuzzyEngine::add_rule_named`
+    pub fn add_rule_named(&mut self, cond: &[(&str, &str)], res: &[(&str, &str)]) {
+        or_panic(self.try_add_rule_named(cond, res))
+    }
+
+    pub fn try_add_rule_named(
+        &mut self,
+        cond: &[(&str, &str)],
+        res: &[(&str, &str)],
+    ) -> Result<(), FuzzyError> {
+        self.rules
+            .try_add_named(&self.inputs_var, &self.outputs_var, cond, res)
+    }
+
+    /// see `parse::parse_rule` for the syntax
+    pub fn add_rule_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.rules
+            .add_str(&self.inputs_var, &self.outputs_var, text)
+    }
+
+    /// one rule per line, see `parse::parse_rules`
+    pub fn add_rules_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.rules
+            .add_strs(&self.inputs_var, &self.outputs_var, text)
+    }
+
+    /// aggregated footprint of every output variable, one value per input variable in order
+    pub fn calculate(&self, inputs: &[f64]) -> Vec<Type2Set> {
+        or_panic(self.try_calculate(inputs))
+    }
+
+    /// fails if there isn't exactly one value per input variable
+    pub fn try_calculate(&self, inputs: &[f64]) -> Result<Vec<Type2Set>, FuzzyError> {
+        if inputs.len() != self.inputs_var.len() {
+            return Err(FuzzyError::Arity(
+                "inputs".into(),
+                self.inputs_var.len(),
+                inputs.len(),
+            ));
+        }
+        let strengths: Vec<(f64, f64)> = self
+            .rules
+            .rules()
+            .iter()
+            .map(|rule| {
+                let (l, u) = eval_interval(&rule.antecedent, &self.inputs_var, inputs, &self.ops);
+                (rule.weight * l, rule.weight * u)
+            })
+            .collect();
+
+        let mut res: Vec<Type2Set> = self
+            .outputs_var
+            .iter()
+            .map(|var| Type2Set {
+                name: "".into(),
+                upper: FuzzySet::empty(&var.universe, "".into()),
+                lower: FuzzySet::empty(&var.universe, "".into()),
+            })
+            .collect();
+        let ops = &self.ops;
+        for (rule, (fl, fu)) in self.rules.rules().iter().zip(strengths.iter()) {
+            for (i, term) in rule.consequent.iter().enumerate() {
+                if let Some(term) = term {
+                    let term = self.outputs_var[i].term(term);
+                    for (m, t) in res[i]
+                        .lower
+                        .membership
+                        .iter_mut()
+                        .zip(&term.lower.membership)
+                    {
+                        *m = ops.aggregation.apply(*m, ops.implication.apply(*fl, *t));
+                    }
+                    for (m, t) in res[i]
+                        .upper
+                        .membership
+                        .iter_mut()
+                        .zip(&term.upper.membership)
+                    {
+                        *m = ops.aggregation.apply(*m, ops.implication.apply(*fu, *t));
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// type-reduced (left, right) centroid of every output variable
+    pub fn reduce(&self, inputs: &[f64]) -> Vec<(f64, f64)> {
+        or_panic(self.try_reduce(inputs))
+    }
+
+    pub fn try_reduce(&self, inputs: &[f64]) -> Result<Vec<(f64, f64)>, FuzzyError> {
+        Ok(self
+            .try_calculate(inputs)?
+            .iter()
+            .map(|set| set.centroid(self.reducer))
+            .collect())
+    }
+
+    /// crisp value of every output variable, the middle of its type-reduced interval
+    pub fn defuzzify(&self, inputs: &[f64]) -> Vec<f64> {
+        or_panic(self.try_defuzzify(inputs))
+    }
+
+    pub fn try_defuzzify(&self, inputs: &[f64]) -> Result<Vec<f64>, FuzzyError> {
+        Ok(self
+            .try_reduce(inputs)?
+            .iter()
+            .map(|(l, r)| (l + r) / 2.0)
+            .collect())
+    }
+
+    /// inputs given as (variable name, value), every input must be listed
+    pub fn defuzzify_named(&self, inputs: &[(&str, f64)]) -> Vec<f64> {
+        or_panic(self.try_defuzzify_named(inputs))
+    }
+
+    pub fn try_defuzzify_named(&self, inputs: &[(&str, f64)]) -> Result<Vec<f64>, FuzzyError> {
+        self.try_defuzzify(&ordered_inputs(&self.inputs_var, inputs)?)
+    }
+}
+
+/// DynType2Engine with the number of inputs (N) and outputs (M) checked at compile time
+#[derive(Debug, Clone)]
+pub struct Type2Engine<const N: usize, const M: usize> {
+    engine: DynType2Engine,
+}
+
+impl<const N: usize, const M: usize> Type2Engine<N, M> {
+    pub fn new(inputs_var: [Type2Var; N], outputs_var: [Type2Var; M]) -> Type2Engine<N, M> {
+        Type2Engine {
+            engine: DynType2Engine::new(Vec::from(inputs_var), Vec::from(outputs_var)),
+        }
+    }
+
+    /// see `DynType2Engine::from_type1`
+    pub fn from_type1(engine: &FuzzyEngine<N, M>, blur: f64) -> Type2Engine<N, M> {
+        Type2Engine {
+            engine: DynType2Engine::from_type1(engine.as_dyn(), blur),
+        }
+    }
+
+    /// None if the engine doesn't have N inputs and M outputs
+    pub fn from_dyn(engine: DynType2Engine) -> Option<Type2Engine<N, M>> {
+        if engine.inputs_var.len() != N || engine.outputs_var.len() != M {
+            return None;
+        }
+        Some(Type2Engine { engine })
+    }
+
+    pub fn as_dyn(&self) -> &DynType2Engine {
+        &self.engine
+    }
+
+    pub fn into_dyn(self) -> DynType2Engine {
+        self.engine
+    }
+
+    pub fn set_operators(&mut self, ops: Operators) {
+        self.engine.set_operators(ops);
+    }
+
+    pub fn try_set_operators(&mut self, ops: Operators) -> Result<(), FuzzyError> {
+        self.engine.try_set_operators(ops)
+    }
+
+    pub fn set_reducer(&mut self, reducer: TypeReducer) {
+        self.engine.set_reducer(reducer);
+    }
+
+    pub fn inputs(&self) -> &[Type2Var; N] {
+        self.engine.inputs_var.as_slice().try_into().unwrap()
+    }
+
+    pub fn outputs(&self) -> &[Type2Var; M] {
+        self.engine.outputs_var.as_slice().try_into().unwrap()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        self.engine.rules()
+    }
+
+    pub fn push_rule(&mut self, rule: Rule) {
+        self.engine.push_rule(rule);
+    }
+
+    pub fn try_push_rule(&mut self, rule: Rule) -> Result<(), FuzzyError> {
+        self.engine.try_push_rule(rule)
+    }
+
+    pub fn add_rule(&mut self, cond: [&str; N], res: [&str; M]) {
+        self.engine.add_rule(&cond, &res);
+    }
+
+    pub fn try_add_rule(&mut self, cond: [&str; N], res: [&str; M]) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule(&cond, &res)
+    }
+
+    pub fn add_rule_weighted(&mut self, cond: [&str; N], res: [&str; M], weight: f64) {
+        self.engine.add_rule_weighted(&cond, &res, weight);
+    }
+
+    pub fn try_add_rule_weighted(
+        &mut self,
+        cond: [&str; N],
+        res: [&str; M],
+        weight: f64,
+    ) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule_weighted(&cond, &res, weight)
+    }
+
+    pub fn set_rule_weight(&mut self, index: usize, weight: f64) {
+        self.engine.set_rule_weight(index, weight);
+    }
+
+    pub fn try_set_rule_weight(&mut self, index: usize, weight: f64) -> Result<(), FuzzyError> {
+        self.engine.try_set_rule_weight(index, weight)
+    }
+
+    pub fn add_rule_expr(&mut self, cond: Antecedent, res: [&str; M]) {
+        self.engine.add_rule_expr(cond, &res);
+    }
+
+    pub fn try_add_rule_expr(
+        &mut self,
+        cond: Antecedent,
+        res: [&str; M],
+    ) -> Result<(), FuzzyError> {
+        self.engine.try_add_rule_expr(cond, &res)
+    }
+
+    pub fn add_rule_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.engine.add_rule_str(text)
+    }
+
+    pub fn add_rules_str(&mut self, text: &str) -> Result<(), ParseError> {
+        self.engine.add_rules_str(text)
+    }
+
+    pub fn calculate(&self, inputs: [f64; N]) -> Vec<Type2Set> {
+        self.engine.calculate(&inputs)
+    }
+
+    pub fn reduce(&self, inputs: [f64; N]) -> Vec<(f64, f64)> {
+        self.engine.reduce(&inputs)
+    }
+
+    pub fn defuzzify(&self, inputs: [f64; N]) -> Vec<f64> {
+        self.engine.defuzzify(&inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antecedent::is;
+    use crate::set::arange;
+    use crate::shape::*;
+
+    #[test]
+    fn type_reduction() {
+        let x = arange(0.0, 10.0, 0.5);
+        let upper: Vec<f64> = x
+            .iter()
+            .map(|x| (1.0 - (x - 4.0).abs() / 5.0).max(0.0))
+            .collect();
+        let lower: Vec<f64> = x
+            .iter()
+            .zip(upper.iter())
+            .map(|(x, u)| u * (0.2 + 0.06 * x))
+            .collect();
+
+        // every embedded set switching between lower and upper at one point
+        let (mut left, mut right) = (f64::INFINITY, f64::NEG_INFINITY);
+        for k in 0..=x.len() {
+            let w = |left: bool| -> Vec<f64> {
+                (0..x.len())
+                    .map(|i| match (i < k) == left {
+                        true => upper[i],
+                        false => lower[i],
+                    })
+                    .collect()
+            };
+            left = left.min(weighted_mean(&x, &w(true)));
+            right = right.max(weighted_mean(&x, &w(false)));
+        }
+        for reducer in [TypeReducer::KarnikMendel, TypeReducer::Eiasc] {
+            let (l, r) = reducer.reduce(&x, &lower, &upper);
+            assert!((l - left).abs() < 1e-9, "{:?} {} {}", reducer, l, left);
+            assert!((r - right).abs() < 1e-9, "{:?} {} {}", reducer, r, right);
+
+            // no uncertainty, both ends are the type-1 centroid
+            let (l, r) = reducer.reduce(&x, &upper, &upper);
+            let centroid = weighted_mean(&x, &upper);
+            assert!((l - centroid).abs() < 1e-9 && (r - centroid).abs() < 1e-9);
+            assert_eq!(reducer.reduce(&x, &[0.0; 21], &[0.0; 21]), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn interval_inference() {
+        let rsi = LinguisticVar::new(
+            vec![
+                (&triangular(0f64, 1.0, 50f64), "low"),
+                (&triangular(50f64, 1.0, 50f64), "medium"),
+                (&triangular(100f64, 1.0, 50f64), "high"),
+            ],
+            arange(0f64, 100f64, 0.5),
+        )
+        .named("rsi");
+        let signal = rsi.clone().named("signal");
+        let mut engine = FuzzyEngine::new([rsi], [signal]);
+        engine.add_rule(["low"], ["high"]);
+        engine.add_rule(["medium"], ["medium"]);
+        engine.add_rule(["high"], ["low"]);
+
+        let crisp = Type2Engine::from_type1(&engine, 0.0);
+        let blurred = Type2Engine::from_type1(&engine, 0.4);
+        let mut eiasc = blurred.clone();
+        eiasc.set_reducer(TypeReducer::Eiasc);
+        for x in [10.0, 30.0, 65.0] {
+            let type1 = engine.defuzzify([x])[0];
+            assert!((crisp.defuzzify([x])[0] - type1).abs() < 1e-9);
+            // the type-1 output is one of the sets inside the footprint
+            let (l, r) = blurred.reduce([x])[0];
+            assert!(l < type1 && type1 < r, "{} {} {}", l, type1, r);
+            let (el, er) = eiasc.reduce([x])[0];
+            assert!((el - l).abs() < 1e-9 && (er - r).abs() < 1e-9);
+        }
+
+        // gaussian terms with an uncertain spread
+        let universe = arange(0f64, 100f64, 0.5);
+        let rsi = Type2Var::new(
+            vec![
+                (&gaussian(20.0, 15.0), &gaussian(20.0, 10.0), "low"),
+                (&gaussian(80.0, 15.0), &gaussian(80.0, 10.0), "high"),
+            ],
+            universe.clone(),
+        )
+        .named("rsi");
+        let (l, u) = rsi.term("low").degree_of(35.0);
+        assert!(l < u);
+        let mut engine = DynType2Engine::new(
+            vec![rsi],
+            vec![Type2Var::from_type1(
+                &LinguisticVar::new(
+                    vec![
+                        (&triangular(0f64, 1.0, 50f64), "short"),
+                        (&triangular(100f64, 1.0, 50f64), "long"),
+                    ],
+                    universe,
+                ),
+                0.2,
+            )
+            .named("position")],
+        );
+        engine
+            .add_rule_str("IF rsi IS low THEN position IS long")
+            .unwrap();
+        engine.add_rule_expr(!is(0, "low"), &["short"]);
+        assert!(engine.defuzzify_named(&[("rsi", 10.0)])[0] > 50.0);
+        assert!(engine.defuzzify(&[90.0])[0] < 50.0);
+        assert!(engine.try_defuzzify(&[1.0, 2.0]).is_err());
+        assert!(engine.try_add_rule(&["medium"], &["long"]).is_err());
+        assert!(engine.try_add_rule(&["kinda low"], &["long"]).is_err());
+        assert!(Type2Var::try_from_type1(&LinguisticVar::new(vec![], vec![0.0]), 1.5).is_err());
+    }
+
+    #[test]
+    fn rule_management() {
+        let universe = arange(0f64, 100f64, 0.5);
+        let var = |name: &str| {
+            Type2Var::from_type1(
+                &LinguisticVar::new(
+                    vec![
+                        (&triangular(0f64, 1.0, 50f64), "low"),
+                        (&triangular(100f64, 1.0, 50f64), "high"),
+                    ],
+                    universe.clone(),
+                ),
+                0.2,
+            )
+            .named(name)
+        };
+        let mut engine = DynType2Engine::new(vec![var("rsi")], vec![var("position")]);
+        engine.add_rule_named(&[("rsi", "low")], &[("position", "high")]);
+        engine.add_rule(&["high"], &["low"]);
+        engine.set_rule_weight(1, 0.5);
+        assert_eq!(engine.rules()[0].consequent, vec![Some("high".to_string())]);
+        assert_eq!(engine.rules()[1].weight, 0.5);
+        assert!(engine.try_set_rule_weight(2, 0.5).is_err());
+        assert!(engine.try_add_rule_named(&[("bb", "low")], &[]).is_err());
+        assert!(engine
+            .try_add_rule_weighted(&["low"], &["high"], 1.5)
+            .is_err());
+        assert!(engine.try_add_rule(&["low"], &["medium"]).is_err());
+        assert_eq!(engine.rules().len(), 2);
+    }
+}
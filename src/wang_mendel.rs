@@ -0,0 +1,203 @@
+// Wang-Mendel rule generation: every sample proposes the rule made of the terms it belongs
+// to most, and rules sharing a condition keep, per output, the term of their strongest sample
+
+use crate::antecedent::{is, Antecedent};
+use crate::error::{or_panic, FuzzyError};
+use crate::partition::in_universe;
+use crate::rule::{DynFuzzyEngine, FuzzyEngine, Rule};
+use crate::set::LinguisticVar;
+use std::collections::HashMap;
+
+/// index and degree of the term `x` belongs to most, None if it's outside every term or
+/// the universe, where `degree_of` would clamp it onto the edge terms
+fn best_term(var: &LinguisticVar, x: f64) -> Option<(usize, f64)> {
+    if !in_universe(&var.universe, x) {
+        return None;
+    }
+    var.sets
+        .iter()
+        .map(|set| set.degree_of(x))
+        .enumerate()
+        .filter(|(_, d)| *d > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// one rule per distinct combination of input terms found in the samples, in order of first
+/// appearance. a sample's degree is the product of its memberships in the rule's input terms
+/// and the output term, conflicting samples are resolved per output by the highest degree.
+/// values outside a variable's universe are missing, see `partition::in_universe`. a sample
+/// missing an input is skipped, a missing output leaves that output to the other samples
+/// and a condition left without any output makes no rule
+pub fn wang_mendel<R: AsRef<[f64]>, T: AsRef<[f64]>>(
+    inputs_var: &[LinguisticVar],
+    outputs_var: &[LinguisticVar],
+    inputs: &[R],
+    outputs: &[T],
+) -> Result<Vec<Rule>, FuzzyError> {
+    if outputs.len() != inputs.len() {
+        return Err(FuzzyError::Arity(
+            "output samples".into(),
+            inputs.len(),
+            outputs.len(),
+        ));
+    }
+    let mut conditions: Vec<Vec<usize>> = vec![];
+    let mut found: HashMap<Vec<usize>, usize> = HashMap::new();
+    // for every condition, the (term, degree) of each output
+    let mut consequents: Vec<Vec<Option<(usize, f64)>>> = vec![];
+    for (x, y) in inputs.iter().zip(outputs.iter()) {
+        let (x, y) = (x.as_ref(), y.as_ref());
+        if x.len() != inputs_var.len() {
+            return Err(FuzzyError::Arity(
+                "inputs".into(),
+                inputs_var.len(),
+                x.len(),
+            ));
+        }
+        if y.len() != outputs_var.len() {
+            return Err(FuzzyError::Arity(
+                "outputs".into(),
+                outputs_var.len(),
+                y.len(),
+            ));
+        }
+        let terms: Option<Vec<(usize, f64)>> = inputs_var
+            .iter()
+            .zip(x.iter())
+            .map(|(var, x)| best_term(var, *x))
+            .collect();
+        let terms = match terms {
+            Some(terms) => terms,
+            None => continue,
+        };
+        let strength: f64 = terms.iter().map(|(_, d)| d).product();
+        let condition: Vec<usize> = terms.iter().map(|(t, _)| *t).collect();
+        let j = *found.entry(condition.clone()).or_insert_with(|| {
+            conditions.push(condition);
+            consequents.push(vec![None; outputs_var.len()]);
+            conditions.len() - 1
+        });
+        for (k, (var, y)) in outputs_var.iter().zip(y.iter()).enumerate() {
+            if let Some((t, d)) = best_term(var, *y) {
+                let degree = strength * d;
+                if consequents[j][k].is_none_or(|(_, best)| degree > best) {
+                    consequents[j][k] = Some((t, degree));
+                }
+            }
+        }
+    }
+
+    Ok(conditions
+        .into_iter()
+        .zip(consequents)
+        .filter(|(_, consequent)| consequent.iter().any(|c| c.is_some()))
+        .map(|(condition, consequent)| Rule {
+            antecedent: Antecedent::And(
+                condition
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| is(i, &inputs_var[i].sets[*t].name))
+                    .collect(),
+            ),
+            consequent: consequent
+                .iter()
+                .enumerate()
+                .map(|(k, c)| c.map(|(t, _)| outputs_var[k].sets[t].name.clone()))
+                .collect(),
+            weight: 1.0,
+        })
+        .collect())
+}
+
+impl DynFuzzyEngine {
+    /// add the rules `wang_mendel` generates from the samples after the existing ones,
+    /// returns how many were added
+    pub fn learn_rules<R: AsRef<[f64]>, T: AsRef<[f64]>>(
+        &mut self,
+        inputs: &[R],
+        outputs: &[T],
+    ) -> usize {
+        or_panic(self.try_learn_rules(inputs, outputs))
+    }
+
+    pub fn try_learn_rules<R: AsRef<[f64]>, T: AsRef<[f64]>>(
+        &mut self,
+        inputs: &[R],
+        outputs: &[T],
+    ) -> Result<usize, FuzzyError> {
+        let rules = wang_mendel(self.inputs(), self.outputs(), inputs, outputs)?;
+        let n = rules.len();
+        for rule in rules {
+            self.try_push_rule(rule)?;
+        }
+        Ok(n)
+    }
+}
+
+impl<const N: usize, const M: usize> FuzzyEngine<N, M> {
+    /// see `DynFuzzyEngine::learn_rules`
+    pub fn learn_rules(&mut self, inputs: &[[f64; N]], outputs: &[[f64; M]]) -> usize {
+        self.as_dyn_mut().learn_rules(inputs, outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partition::Partition;
+    use crate::set::arange;
+
+    #[test]
+    fn generated_rules() {
+        let x = LinguisticVar::uniform(Partition::Triangular, 3, arange(0f64, 100f64, 0.5))
+            .named("rsi");
+        let y = LinguisticVar::uniform(Partition::Triangular, 3, arange(0f64, 100f64, 0.5));
+        let mut engine = FuzzyEngine::new(
+            [x.clone(), x.clone().named("bb")],
+            [y.clone().named("signal")],
+        );
+
+        // a warm-up sample first, as data::rsi gives -1.0 for it. clamped into "low" it would
+        // claim (low, low) -> low before the real (0, 0) -> 100 could
+        let mut inputs = vec![[-1.0, -1.0]];
+        let mut outputs = vec![[-1.0]];
+        // signal falls as rsi rises, bb doesn't matter
+        for i in 0..=10 {
+            for j in 0..=10 {
+                inputs.push([i as f64 * 10.0, j as f64 * 10.0]);
+                outputs.push([100.0 - i as f64 * 10.0]);
+            }
+        }
+        // a weak sample (20, 20) -> 60 disagreeing with (0, 0) -> 100 loses the conflict
+        inputs.push([20.0, 20.0]);
+        outputs.push([60.0]);
+        // missing values
+        inputs.push([f64::NAN, 50.0]);
+        outputs.push([0.0]);
+        inputs.push([50.0, 50.0]);
+        outputs.push([-1.0]);
+
+        assert_eq!(engine.learn_rules(&inputs, &outputs), 9);
+        assert_eq!(
+            engine.rules()[0].antecedent,
+            Antecedent::from_slots(&["low", "low"])
+        );
+        assert_eq!(engine.rules()[0].consequent, vec![Some("high".to_string())]);
+        let last = &engine.rules()[8];
+        assert_eq!(last.antecedent, Antecedent::from_slots(&["high", "high"]));
+        assert_eq!(last.consequent, vec![Some("low".to_string())]);
+        // centroids pull towards the middle, but the signal still falls with rsi
+        let out: Vec<f64> = [10.0, 50.0, 90.0]
+            .iter()
+            .map(|rsi| engine.defuzzify([*rsi, 30.0])[0])
+            .collect();
+        assert!(out[0] > out[1] && out[1] > out[2], "{:?}", out);
+        assert!((out[1] - 50.0).abs() < 5.0, "{:?}", out);
+
+        let mut dynamic = engine.into_dyn();
+        assert!(dynamic.try_learn_rules(&inputs, &outputs[..3]).is_err());
+        assert!(dynamic.try_learn_rules(&[vec![1.0]], &[vec![1.0]]).is_err());
+        // a condition whose outputs are all missing makes no rule
+        assert_eq!(dynamic.learn_rules(&[[50.0, 50.0]], &[[101.0]]), 0);
+    }
+}